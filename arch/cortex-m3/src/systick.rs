@@ -133,6 +133,16 @@ impl kernel::SysTick for SysTick {
         value > tics
     }
 
+    fn get_value(&self) -> u32 {
+        let value = SYSTICK_BASE.syst_cvr.read(CurrentValue::CURRENT) as u64;
+        let hertz = self.hertz() as u64;
+        if hertz == 0 {
+            return 0;
+        }
+
+        (value * 1_000_000 / hertz) as u32
+    }
+
     fn overflowed(&self) -> bool {
         SYSTICK_BASE.syst_csr.is_set(ControlAndStatus::COUNTFLAG)
     }
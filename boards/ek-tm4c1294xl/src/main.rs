@@ -43,6 +43,9 @@ struct EkTm4c1294xl {
     >,
     gpio: &'static capsules::gpio::GPIO<'static, tm4c129x::gpio::GPIOPin>,
     ipc: kernel::ipc::IPC,
+    ipc_messages: kernel::ipc::MessageIPC,
+    process_info: capsules::process_info::ProcessInfo,
+    crash_log: capsules::crash_log::CrashLog,
     led: &'static capsules::led::LED<'static, tm4c129x::gpio::GPIOPin>,
     button: &'static capsules::button::Button<'static, tm4c129x::gpio::GPIOPin>,
 }
@@ -58,6 +61,9 @@ impl Platform for EkTm4c1294xl {
             capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
             capsules::gpio::DRIVER_NUM => f(Some(self.gpio)),
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MESSAGE_DRIVER_NUM => f(Some(&self.ipc_messages)),
+            capsules::process_info::DRIVER_NUM => f(Some(&self.process_info)),
+            capsules::crash_log::DRIVER_NUM => f(Some(&self.crash_log)),
             capsules::led::DRIVER_NUM => f(Some(self.led)),
             capsules::button::DRIVER_NUM => f(Some(self.button)),
             _ => f(None),
@@ -167,7 +173,7 @@ pub unsafe fn reset_handler() {
     );
     let gpio = static_init!(
         capsules::gpio::GPIO<'static, tm4c129x::gpio::GPIOPin>,
-        capsules::gpio::GPIO::new(gpio_pins)
+        capsules::gpio::GPIO::new(gpio_pins, kernel::Grant::create())
     );
     for pin in gpio_pins.iter() {
         pin.set_client(gpio);
@@ -178,6 +184,9 @@ pub unsafe fn reset_handler() {
         alarm: alarm,
         gpio: gpio,
         ipc: kernel::ipc::IPC::new(),
+        ipc_messages: kernel::ipc::MessageIPC::new(),
+        process_info: capsules::process_info::ProcessInfo::new(kernel::Grant::create()),
+        crash_log: capsules::crash_log::CrashLog::new(kernel::Grant::create()),
         led: led,
         button: button,
     };
@@ -190,6 +199,9 @@ pub unsafe fn reset_handler() {
     let kc = static_init!(capsules::console::App, capsules::console::App::default());
     kernel::debug::assign_console_driver(Some(tm4c1294.console), kc);
 
+    // Report any crashes from earlier boots
+    tm4c1294.crash_log.report();
+
     debug!("Initialization complete. Entering main loop...\r");
 
     extern "C" {
@@ -204,5 +216,11 @@ pub unsafe fn reset_handler() {
         &mut PROCESSES,
         FAULT_RESPONSE,
     );
-    kernel::main(&tm4c1294, &mut chip, &mut PROCESSES, Some(&tm4c1294.ipc));
+    kernel::main(
+        &tm4c1294,
+        &mut chip,
+        &mut PROCESSES,
+        Some(&tm4c1294.ipc),
+        None,
+    );
 }
@@ -18,6 +18,7 @@ extern crate sam4l;
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use capsules::virtual_i2c::{I2CDevice, MuxI2C};
 use capsules::virtual_spi::{MuxSpiMaster, VirtualSpiMasterDevice};
+use kernel::common::dynamic_deferred_call;
 use kernel::hil;
 use kernel::hil::spi::SpiMaster;
 use kernel::hil::Controller;
@@ -42,6 +43,10 @@ const NUM_PROCS: usize = 20;
 // How should the kernel respond when a process faults.
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
+// How long the kernel main loop may go without making progress before the
+// watchdog resets the board.
+const WATCHDOG_TIMEOUT_MS: usize = 5000;
+
 // RAM to be shared by all application processes.
 #[link_section = ".app_memory"]
 static mut APP_MEMORY: [u8; 49152] = [0; 49152];
@@ -65,18 +70,44 @@ struct Hail {
     temp: &'static capsules::temperature::TemperatureSensor<'static>,
     ninedof: &'static capsules::ninedof::NineDof<'static>,
     humidity: &'static capsules::humidity::HumiditySensor<'static>,
+    sensor_sampler: &'static capsules::sensor_sampler::SensorSampler<
+        'static,
+        VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>,
+    >,
     spi: &'static capsules::spi::Spi<'static, VirtualSpiMasterDevice<'static, sam4l::spi::SpiHw>>,
     nrf51822: &'static capsules::nrf51822_serialization::Nrf51822Serialization<
         'static,
         sam4l::usart::USART,
     >,
     adc: &'static capsules::adc::Adc<'static, sam4l::adc::Adc>,
-    led: &'static capsules::led::LED<'static, sam4l::gpio::GPIOPin>,
-    button: &'static capsules::button::Button<'static, sam4l::gpio::GPIOPin>,
-    rng: &'static capsules::rng::SimpleRng<'static, sam4l::trng::Trng<'static>>,
+    led: &'static capsules::led_pattern::LedPattern<
+        'static,
+        sam4l::gpio::GPIOPin,
+        VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>,
+    >,
+    button: &'static capsules::debounced_button::DebouncedButton<
+        'static,
+        sam4l::gpio::GPIOPin,
+        VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>,
+    >,
+    rng: &'static capsules::rng::SimpleRng<
+        'static,
+        capsules::drbg::VirtualDrbg<'static, sam4l::trng::Trng<'static>, sam4l::aes::Aes<'static>>,
+    >,
     ipc: kernel::ipc::IPC,
+    ipc_messages: kernel::ipc::MessageIPC,
+    process_info: capsules::process_info::ProcessInfo,
+    crash_log: capsules::crash_log::CrashLog,
     crc: &'static capsules::crc::Crc<'static, sam4l::crccu::Crccu<'static>>,
+    date_time: &'static capsules::date_time::DateTimeDriver<
+        'static,
+        capsules::software_date_time::SoftwareDateTime<
+            'static,
+            VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>,
+        >,
+    >,
     dac: &'static capsules::dac::Dac<'static>,
+    i2c_master: &'static capsules::i2c_master::I2CMasterDriver<'static>,
 }
 
 /// Mapping of integer syscalls to objects that implement syscalls.
@@ -94,19 +125,25 @@ impl Platform for Hail {
             capsules::nrf51822_serialization::DRIVER_NUM => f(Some(self.nrf51822)),
             capsules::ambient_light::DRIVER_NUM => f(Some(self.ambient_light)),
             capsules::adc::DRIVER_NUM => f(Some(self.adc)),
-            capsules::led::DRIVER_NUM => f(Some(self.led)),
-            capsules::button::DRIVER_NUM => f(Some(self.button)),
+            capsules::led_pattern::DRIVER_NUM => f(Some(self.led)),
+            capsules::debounced_button::DRIVER_NUM => f(Some(self.button)),
             capsules::humidity::DRIVER_NUM => f(Some(self.humidity)),
             capsules::temperature::DRIVER_NUM => f(Some(self.temp)),
             capsules::ninedof::DRIVER_NUM => f(Some(self.ninedof)),
+            capsules::sensor_sampler::DRIVER_NUM => f(Some(self.sensor_sampler)),
 
             capsules::rng::DRIVER_NUM => f(Some(self.rng)),
 
             capsules::crc::DRIVER_NUM => f(Some(self.crc)),
+            capsules::date_time::DRIVER_NUM => f(Some(self.date_time)),
 
             capsules::dac::DRIVER_NUM => f(Some(self.dac)),
+            capsules::i2c_master::DRIVER_NUM => f(Some(self.i2c_master)),
 
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MESSAGE_DRIVER_NUM => f(Some(&self.ipc_messages)),
+            capsules::process_info::DRIVER_NUM => f(Some(&self.process_info)),
+            capsules::crash_log::DRIVER_NUM => f(Some(&self.crash_log)),
             _ => f(None),
         }
     }
@@ -250,18 +287,6 @@ pub unsafe fn reset_handler() {
     si7021_i2c.set_client(si7021);
     si7021_virtual_alarm.set_client(si7021);
 
-    let temp = static_init!(
-        capsules::temperature::TemperatureSensor<'static>,
-        capsules::temperature::TemperatureSensor::new(si7021, kernel::Grant::create())
-    );
-    kernel::hil::sensors::TemperatureDriver::set_client(si7021, temp);
-
-    let humidity = static_init!(
-        capsules::humidity::HumiditySensor<'static>,
-        capsules::humidity::HumiditySensor::new(si7021, kernel::Grant::create())
-    );
-    kernel::hil::sensors::HumidityDriver::set_client(si7021, humidity);
-
     // Configure the ISL29035, device address 0x44
     let isl29035_i2c = static_init!(I2CDevice, I2CDevice::new(sensors_i2c, 0x44));
     let isl29035_virtual_alarm = static_init!(
@@ -279,12 +304,6 @@ pub unsafe fn reset_handler() {
     isl29035_i2c.set_client(isl29035);
     isl29035_virtual_alarm.set_client(isl29035);
 
-    let ambient_light = static_init!(
-        capsules::ambient_light::AmbientLight<'static>,
-        capsules::ambient_light::AmbientLight::new(isl29035, kernel::Grant::create())
-    );
-    hil::sensors::AmbientLight::set_client(isl29035, ambient_light);
-
     // Alarm
     let virtual_alarm1 = static_init!(
         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
@@ -296,6 +315,34 @@ pub unsafe fn reset_handler() {
     );
     virtual_alarm1.set_client(alarm);
 
+    // Date and time, kept in software on top of the AST
+    let date_time_alarm = static_init!(
+        VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let software_date_time = static_init!(
+        capsules::software_date_time::SoftwareDateTime<
+            'static,
+            VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        >,
+        capsules::software_date_time::SoftwareDateTime::new(date_time_alarm)
+    );
+    let date_time_setters = static_init!([&'static str; 1], ["clock_sync"]);
+    let date_time = static_init!(
+        capsules::date_time::DateTimeDriver<
+            'static,
+            capsules::software_date_time::SoftwareDateTime<
+                'static,
+                VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+            >,
+        >,
+        capsules::date_time::DateTimeDriver::new(
+            software_date_time,
+            date_time_setters,
+            kernel::Grant::create()
+        )
+    );
+
     // FXOS8700CQ accelerometer, device address 0x1e
     let fxos8700_i2c = static_init!(I2CDevice, I2CDevice::new(sensors_i2c, 0x1e));
     let fxos8700 = static_init!(
@@ -309,11 +356,72 @@ pub unsafe fn reset_handler() {
     fxos8700_i2c.set_client(fxos8700);
     sam4l::gpio::PA[9].set_client(fxos8700);
 
+    // Userspace access to devices on the sensor bus. The example thermostat
+    // app may talk to temperature sensors at 0x48 and 0x49.
+    let i2c_master_device = static_init!(I2CDevice, I2CDevice::new(sensors_i2c, 0));
+    let i2c_master_permissions = static_init!(
+        [(&'static str, u8); 2],
+        [("thermostat", 0x48), ("thermostat", 0x49)]
+    );
+    let i2c_master = static_init!(
+        capsules::i2c_master::I2CMasterDriver<'static>,
+        capsules::i2c_master::I2CMasterDriver::new(
+            i2c_master_device,
+            i2c_master_permissions,
+            &mut capsules::i2c_master::BUFFER,
+            kernel::Grant::create()
+        )
+    );
+    i2c_master_device.set_client(i2c_master);
+
+    // Periodic sampling of the sensors, shared with the one-shot drivers
+    let sensor_sampler_alarm = static_init!(
+        VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let sensor_sampler = static_init!(
+        capsules::sensor_sampler::SensorSampler<
+            'static,
+            VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        >,
+        capsules::sensor_sampler::SensorSampler::new(
+            sensor_sampler_alarm,
+            Some(si7021),
+            Some(si7021),
+            Some(isl29035),
+            Some(fxos8700),
+            kernel::Grant::create()
+        )
+    );
+    sensor_sampler_alarm.set_client(sensor_sampler);
+    hil::sensors::TemperatureDriver::set_client(si7021, sensor_sampler);
+    hil::sensors::HumidityDriver::set_client(si7021, sensor_sampler);
+    hil::sensors::AmbientLight::set_client(isl29035, sensor_sampler);
+    hil::sensors::NineDof::set_client(fxos8700, sensor_sampler);
+
+    let temp = static_init!(
+        capsules::temperature::TemperatureSensor<'static>,
+        capsules::temperature::TemperatureSensor::new(sensor_sampler, kernel::Grant::create())
+    );
+    hil::sensors::TemperatureDriver::set_client(sensor_sampler, temp);
+
+    let humidity = static_init!(
+        capsules::humidity::HumiditySensor<'static>,
+        capsules::humidity::HumiditySensor::new(sensor_sampler, kernel::Grant::create())
+    );
+    hil::sensors::HumidityDriver::set_client(sensor_sampler, humidity);
+
+    let ambient_light = static_init!(
+        capsules::ambient_light::AmbientLight<'static>,
+        capsules::ambient_light::AmbientLight::new(sensor_sampler, kernel::Grant::create())
+    );
+    hil::sensors::AmbientLight::set_client(sensor_sampler, ambient_light);
+
     let ninedof = static_init!(
         capsules::ninedof::NineDof<'static>,
-        capsules::ninedof::NineDof::new(fxos8700, kernel::Grant::create())
+        capsules::ninedof::NineDof::new(sensor_sampler, kernel::Grant::create())
     );
-    hil::sensors::NineDof::set_client(fxos8700, ninedof);
+    hil::sensors::NineDof::set_client(sensor_sampler, ninedof);
 
     // Initialize and enable SPI HAL
     // Set up an SPI MUX, so there can be multiple clients
@@ -343,42 +451,64 @@ pub unsafe fn reset_handler() {
 
     // LEDs
     let led_pins = static_init!(
-        [(&'static sam4l::gpio::GPIOPin, capsules::led::ActivationMode); 3],
+        [capsules::led_pattern::LedPin<'static, sam4l::gpio::GPIOPin>; 3],
         [
-            (
+            capsules::led_pattern::LedPin::new(
                 &sam4l::gpio::PA[13],
                 capsules::led::ActivationMode::ActiveLow
             ), // Red
-            (
+            capsules::led_pattern::LedPin::new(
                 &sam4l::gpio::PA[15],
                 capsules::led::ActivationMode::ActiveLow
             ), // Green
-            (
+            capsules::led_pattern::LedPin::new(
                 &sam4l::gpio::PA[14],
                 capsules::led::ActivationMode::ActiveLow
-            ),
+            ), // Blue
         ]
-    ); // Blue
+    );
+    let led_alarm = static_init!(
+        VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
     let led = static_init!(
-        capsules::led::LED<'static, sam4l::gpio::GPIOPin>,
-        capsules::led::LED::new(led_pins)
+        capsules::led_pattern::LedPattern<
+            'static,
+            sam4l::gpio::GPIOPin,
+            VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        >,
+        capsules::led_pattern::LedPattern::new(led_pins, led_alarm, kernel::Grant::create())
     );
+    led_alarm.set_client(led);
 
     // BUTTONs
     let button_pins = static_init!(
-        [(&'static sam4l::gpio::GPIOPin, capsules::button::GpioMode); 1],
-        [(
+        [capsules::debounced_button::ButtonPin<'static, sam4l::gpio::GPIOPin>; 1],
+        [capsules::debounced_button::ButtonPin::new(
             &sam4l::gpio::PA[16],
             capsules::button::GpioMode::LowWhenPressed
         )]
     );
+    let button_alarm = static_init!(
+        VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
     let button = static_init!(
-        capsules::button::Button<'static, sam4l::gpio::GPIOPin>,
-        capsules::button::Button::new(button_pins, kernel::Grant::create())
+        capsules::debounced_button::DebouncedButton<
+            'static,
+            sam4l::gpio::GPIOPin,
+            VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        >,
+        capsules::debounced_button::DebouncedButton::new(
+            button_pins,
+            button_alarm,
+            kernel::Grant::create()
+        )
     );
-    for &(btn, _) in button_pins.iter() {
-        btn.set_client(button);
+    for btn in button_pins.iter() {
+        btn.pin().set_client(button);
     }
+    button_alarm.set_client(button);
 
     // Setup ADC
     let adc_channels = static_init!(
@@ -404,12 +534,33 @@ pub unsafe fn reset_handler() {
     );
     sam4l::adc::ADC0.set_client(adc);
 
-    // Setup RNG
+    // Setup RNG: seed a DRBG from the TRNG and serve applications from it
+    let drbg = static_init!(
+        capsules::drbg::CtrDrbg<'static, sam4l::trng::Trng, sam4l::aes::Aes>,
+        capsules::drbg::CtrDrbg::new(
+            &sam4l::trng::TRNG,
+            &sam4l::aes::AES,
+            &mut capsules::drbg::INPUT_BUF,
+            &mut capsules::drbg::OUTPUT_BUF
+        )
+    );
+    drbg.initialize_deferred_call(
+        dynamic_deferred_call::register(drbg).expect("no deferred call for drbg"),
+    );
+    sam4l::trng::TRNG.set_client(drbg);
+    hil::symmetric_encryption::AES128::set_client(&sam4l::aes::AES, drbg);
+    let rng_drbg = static_init!(
+        capsules::drbg::VirtualDrbg<'static, sam4l::trng::Trng, sam4l::aes::Aes>,
+        capsules::drbg::VirtualDrbg::new(drbg)
+    );
     let rng = static_init!(
-        capsules::rng::SimpleRng<'static, sam4l::trng::Trng>,
-        capsules::rng::SimpleRng::new(&sam4l::trng::TRNG, kernel::Grant::create())
+        capsules::rng::SimpleRng<
+            'static,
+            capsules::drbg::VirtualDrbg<'static, sam4l::trng::Trng, sam4l::aes::Aes>,
+        >,
+        capsules::rng::SimpleRng::new(rng_drbg, kernel::Grant::create())
     );
-    sam4l::trng::TRNG.set_client(rng);
+    rng_drbg.set_client(rng);
 
     // set GPIO driver controlling remaining GPIO pins
     let gpio_pins = static_init!(
@@ -423,11 +574,17 @@ pub unsafe fn reset_handler() {
     ); // D7
     let gpio = static_init!(
         capsules::gpio::GPIO<'static, sam4l::gpio::GPIOPin>,
-        capsules::gpio::GPIO::new(gpio_pins)
+        capsules::gpio::GPIO::new(gpio_pins, kernel::Grant::create())
     );
     for pin in gpio_pins.iter() {
         pin.set_client(gpio);
     }
+    // D6 and D7 drive the relay board, which only the relay app may switch
+    let gpio_owners = static_init!(
+        [Option<&'static str>; 4],
+        [None, None, Some("relay"), Some("relay")]
+    );
+    gpio.set_owners(gpio_owners);
 
     // CRC
     let crc = static_init!(
@@ -450,6 +607,7 @@ pub unsafe fn reset_handler() {
         temp: temp,
         humidity: humidity,
         ninedof: ninedof,
+        sensor_sampler: sensor_sampler,
         spi: spi_syscalls,
         nrf51822: nrf_serialization,
         adc: adc,
@@ -457,8 +615,13 @@ pub unsafe fn reset_handler() {
         button: button,
         rng: rng,
         ipc: kernel::ipc::IPC::new(),
+        ipc_messages: kernel::ipc::MessageIPC::new(),
+        process_info: capsules::process_info::ProcessInfo::new(kernel::Grant::create()),
+        crash_log: capsules::crash_log::CrashLog::new(kernel::Grant::create()),
         crc: crc,
+        date_time: date_time,
         dac: dac,
+        i2c_master: i2c_master,
     };
 
     // Need to reset the nRF on boot
@@ -476,6 +639,9 @@ pub unsafe fn reset_handler() {
     let kc = static_init!(capsules::console::App, capsules::console::App::default());
     kernel::debug::assign_console_driver(Some(hail.console), kc);
 
+    // Report any crashes from earlier boots
+    hail.crash_log.report();
+
     hail.nrf51822.initialize();
 
     // Uncomment to measure overheads for TakeCell and MapCell:
@@ -496,5 +662,12 @@ pub unsafe fn reset_handler() {
         &mut PROCESSES,
         FAULT_RESPONSE,
     );
-    kernel::main(&hail, &mut chip, &mut PROCESSES, Some(&hail.ipc));
+    hil::watchdog::Watchdog::start(&sam4l::wdt::WDT, WATCHDOG_TIMEOUT_MS);
+    kernel::main(
+        &hail,
+        &mut chip,
+        &mut PROCESSES,
+        Some(&hail.ipc),
+        Some(&sam4l::wdt::WDT),
+    );
 }
@@ -37,6 +37,6 @@ unsafe fn static_init_test_cbc() -> &'static mut TestAes128Cbc<'static, Aes<'sta
 
     static_init!(
         TestAes128Cbc<'static, Aes>,
-        TestAes128Cbc::new(&AES, key, iv, source, data)
+        TestAes128Cbc::new(&AES, key, iv, source, data, false)
     )
 }
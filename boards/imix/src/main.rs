@@ -64,6 +64,10 @@ const NUM_PROCS: usize = 2;
 // how should the kernel respond when a process faults
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
+// How long the kernel main loop may go without making progress before the
+// watchdog resets the board.
+const WATCHDOG_TIMEOUT_MS: usize = 5000;
+
 #[link_section = ".app_memory"]
 static mut APP_MEMORY: [u8; 16384] = [0; 16384];
 
@@ -86,6 +90,9 @@ struct Imix {
     button: &'static capsules::button::Button<'static, sam4l::gpio::GPIOPin>,
     spi: &'static capsules::spi::Spi<'static, VirtualSpiMasterDevice<'static, sam4l::spi::SpiHw>>,
     ipc: kernel::ipc::IPC,
+    ipc_messages: kernel::ipc::MessageIPC,
+    process_info: capsules::process_info::ProcessInfo,
+    crash_log: capsules::crash_log::CrashLog,
     ninedof: &'static capsules::ninedof::NineDof<'static>,
     radio_driver: &'static capsules::ieee802154::RadioDriver<'static>,
     crc: &'static capsules::crc::Crc<'static, sam4l::crccu::Crccu<'static>>,
@@ -147,6 +154,9 @@ impl kernel::Platform for Imix {
             capsules::nrf51822_serialization::DRIVER_NUM => f(Some(self.nrf51822)),
             capsules::nonvolatile_storage_driver::DRIVER_NUM => f(Some(self.nonvolatile_storage)),
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MESSAGE_DRIVER_NUM => f(Some(&self.ipc_messages)),
+            capsules::process_info::DRIVER_NUM => f(Some(&self.process_info)),
+            capsules::crash_log::DRIVER_NUM => f(Some(&self.crash_log)),
             _ => f(None),
         }
     }
@@ -457,7 +467,7 @@ pub unsafe fn reset_handler() {
 
     let gpio = static_init!(
         capsules::gpio::GPIO<'static, sam4l::gpio::GPIOPin>,
-        capsules::gpio::GPIO::new(gpio_pins)
+        capsules::gpio::GPIO::new(gpio_pins, kernel::Grant::create())
     );
     for pin in gpio_pins.iter() {
         pin.set_client(gpio);
@@ -614,6 +624,9 @@ pub unsafe fn reset_handler() {
         crc: crc,
         spi: spi_syscalls,
         ipc: kernel::ipc::IPC::new(),
+        ipc_messages: kernel::ipc::MessageIPC::new(),
+        process_info: capsules::process_info::ProcessInfo::new(kernel::Grant::create()),
+        crash_log: capsules::crash_log::CrashLog::new(kernel::Grant::create()),
         ninedof: ninedof,
         radio_driver: radio_driver,
         usb_driver: usb_driver,
@@ -640,6 +653,9 @@ pub unsafe fn reset_handler() {
     rf233.reset();
     rf233.start();
 
+    // Report any crashes from earlier boots
+    imix.crash_log.report();
+
     debug!("Initialization complete. Entering main loop");
     extern "C" {
         /// Beginning of the ROM region containing app images.
@@ -652,5 +668,12 @@ pub unsafe fn reset_handler() {
         FAULT_RESPONSE,
     );
 
-    kernel::main(&imix, &mut chip, &mut PROCESSES, Some(&imix.ipc));
+    hil::watchdog::Watchdog::start(&sam4l::wdt::WDT, WATCHDOG_TIMEOUT_MS);
+    kernel::main(
+        &imix,
+        &mut chip,
+        &mut PROCESSES,
+        Some(&imix.ipc),
+        Some(&sam4l::wdt::WDT),
+    );
 }
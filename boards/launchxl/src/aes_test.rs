@@ -0,0 +1,68 @@
+//! Test the software AES implementation against the vectors in
+//! `capsules::test::aes`.
+
+use capsules::software_aes::SoftwareAes128;
+use capsules::test::aes::{TestAes128Cbc, TestAes128Ctr};
+use kernel::common::dynamic_deferred_call;
+use kernel::hil::symmetric_encryption::{AES128, AES128_BLOCK_SIZE, AES128_KEY_SIZE};
+
+type Aes = SoftwareAes128<'static>;
+
+pub unsafe fn run_aes128_ctr() {
+    let aes = static_init_aes();
+    let t = static_init_test_ctr(aes);
+    aes.set_client(t);
+
+    t.run();
+}
+
+pub unsafe fn run_aes128_cbc() {
+    let aes = static_init_aes();
+    let t = static_init_test_cbc(aes, false);
+    aes.set_client(t);
+
+    t.run();
+}
+
+pub unsafe fn run_aes128_ecb() {
+    let aes = static_init_aes();
+    let t = static_init_test_cbc(aes, true);
+    aes.set_client(t);
+
+    t.run();
+}
+
+unsafe fn static_init_aes() -> &'static Aes {
+    let aes = static_init!(Aes, SoftwareAes128::new());
+    aes.initialize_deferred_call(
+        dynamic_deferred_call::register(aes).expect("no deferred call for software_aes"),
+    );
+    aes
+}
+
+unsafe fn static_init_test_ctr(aes: &'static Aes) -> &'static mut TestAes128Ctr<'static, Aes> {
+    let source = static_init!([u8; 4 * AES128_BLOCK_SIZE], [0; 4 * AES128_BLOCK_SIZE]);
+    let data = static_init!([u8; 6 * AES128_BLOCK_SIZE], [0; 6 * AES128_BLOCK_SIZE]);
+    let key = static_init!([u8; AES128_KEY_SIZE], [0; AES128_KEY_SIZE]);
+    let iv = static_init!([u8; AES128_BLOCK_SIZE], [0; AES128_BLOCK_SIZE]);
+
+    static_init!(
+        TestAes128Ctr<'static, Aes>,
+        TestAes128Ctr::new(aes, key, iv, source, data)
+    )
+}
+
+unsafe fn static_init_test_cbc(
+    aes: &'static Aes,
+    ecb: bool,
+) -> &'static mut TestAes128Cbc<'static, Aes> {
+    let source = static_init!([u8; 4 * AES128_BLOCK_SIZE], [0; 4 * AES128_BLOCK_SIZE]);
+    let data = static_init!([u8; 6 * AES128_BLOCK_SIZE], [0; 6 * AES128_BLOCK_SIZE]);
+    let key = static_init!([u8; AES128_KEY_SIZE], [0; AES128_KEY_SIZE]);
+    let iv = static_init!([u8; AES128_BLOCK_SIZE], [0; AES128_BLOCK_SIZE]);
+
+    static_init!(
+        TestAes128Cbc<'static, Aes>,
+        TestAes128Cbc::new(aes, key, iv, source, data, ecb)
+    )
+}
@@ -12,19 +12,30 @@ extern crate kernel;
 
 use cc26x2::aon;
 use cc26x2::prcm;
+use kernel::common::dynamic_deferred_call;
 
 #[macro_use]
 pub mod io;
 
+#[allow(dead_code)]
+mod aes_test;
+
 #[allow(dead_code)]
 mod i2c_tests;
 
+#[allow(dead_code)]
+mod sha256_test;
+
 #[allow(dead_code)]
 mod hello_world;
 
 // How should the kernel respond when a process faults.
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
+// How long the kernel main loop may go without making progress before the
+// watchdog resets the board.
+const WATCHDOG_TIMEOUT_MS: usize = 5000;
+
 // Number of concurrent processes this platform supports.
 const NUM_PROCS: usize = 3;
 static mut PROCESSES: [Option<&'static mut kernel::procs::Process<'static>>; NUM_PROCS] =
@@ -44,8 +55,18 @@ pub struct Platform {
         capsules::virtual_alarm::VirtualMuxAlarm<'static, cc26x2::rtc::Rtc>,
     >,
     rng: &'static capsules::rng::SimpleRng<'static, cc26x2::trng::Trng>,
+    aes: &'static capsules::aes::AesDriver<
+        'static,
+        SoftwareAes,
+        capsules::aes_ccm::AES128CCM<'static, SoftwareAes>,
+    >,
+    digest: &'static capsules::digest::DigestDriver<'static, SoftwareSha256>,
 }
 
+type SoftwareAes = capsules::software_aes::SoftwareAes128<'static>;
+
+type SoftwareSha256 = capsules::software_sha256::SoftwareSha256<'static>;
+
 impl kernel::Platform for Platform {
     fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
     where
@@ -58,6 +79,8 @@ impl kernel::Platform for Platform {
             capsules::button::DRIVER_NUM => f(Some(self.button)),
             capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
             capsules::rng::DRIVER_NUM => f(Some(self.rng)),
+            capsules::aes::DRIVER_NUM => f(Some(self.aes)),
+            capsules::digest::DRIVER_NUM => f(Some(self.digest)),
             _ => f(None),
         }
     }
@@ -213,7 +236,7 @@ pub unsafe fn reset_handler() {
     );
     let gpio = static_init!(
         capsules::gpio::GPIO<'static, cc26x2::gpio::GPIOPin>,
-        capsules::gpio::GPIO::new(gpio_pins)
+        capsules::gpio::GPIO::new(gpio_pins, kernel::Grant::create())
     );
     for pin in gpio_pins.iter() {
         pin.set_client(gpio);
@@ -247,6 +270,51 @@ pub unsafe fn reset_handler() {
     );
     cc26x2::trng::TRNG.set_client(rng);
 
+    // AES in software, shared by CTR and CCM* requests from apps
+    let software_aes = static_init!(SoftwareAes, capsules::software_aes::SoftwareAes128::new());
+    software_aes.initialize_deferred_call(
+        dynamic_deferred_call::register(software_aes).expect("no deferred call for software_aes"),
+    );
+    let aes_ccm = static_init!(
+        capsules::aes_ccm::AES128CCM<'static, SoftwareAes>,
+        capsules::aes_ccm::AES128CCM::new(software_aes, &mut capsules::aes::CCM_CRYPT_BUF)
+    );
+    let aes = static_init!(
+        capsules::aes::AesDriver<
+            'static,
+            SoftwareAes,
+            capsules::aes_ccm::AES128CCM<'static, SoftwareAes>,
+        >,
+        capsules::aes::AesDriver::new(
+            software_aes,
+            aes_ccm,
+            &mut capsules::aes::CTR_BUF,
+            &mut capsules::aes::CCM_BUF,
+            kernel::Grant::create()
+        )
+    );
+    kernel::hil::symmetric_encryption::AES128::set_client(software_aes, aes);
+    kernel::hil::symmetric_encryption::AES128CCM::set_client(aes_ccm, aes);
+
+    let software_sha256 = static_init!(
+        SoftwareSha256,
+        capsules::software_sha256::SoftwareSha256::new()
+    );
+    software_sha256.initialize_deferred_call(
+        dynamic_deferred_call::register(software_sha256)
+            .expect("no deferred call for software_sha256"),
+    );
+    let digest = static_init!(
+        capsules::digest::DigestDriver<'static, SoftwareSha256>,
+        capsules::digest::DigestDriver::new(
+            software_sha256,
+            &mut capsules::digest::DATA_BUF,
+            &mut capsules::digest::DEST_BUF,
+            kernel::Grant::create()
+        )
+    );
+    kernel::hil::digest::Digest::set_client(software_sha256, digest);
+
     let launchxl = Platform {
         console,
         gpio,
@@ -254,6 +322,8 @@ pub unsafe fn reset_handler() {
         button,
         alarm,
         rng,
+        aes,
+        digest,
     };
 
     let mut chip = cc26x2::chip::Cc26X2::new();
@@ -283,10 +353,12 @@ pub unsafe fn reset_handler() {
     hello_world.start();
     //// Chalmers Tutorial ////
 
+    kernel::hil::watchdog::Watchdog::start(&cc26x2::wdt::WDT, WATCHDOG_TIMEOUT_MS);
     kernel::main(
         &launchxl,
         &mut chip,
         &mut PROCESSES,
         Some(&kernel::ipc::IPC::new()),
+        Some(&cc26x2::wdt::WDT),
     );
 }
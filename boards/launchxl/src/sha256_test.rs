@@ -0,0 +1,23 @@
+//! Test the software SHA-256 implementation against the vectors in
+//! `capsules::test::sha256`.
+
+use capsules::software_sha256::SoftwareSha256;
+use capsules::test::sha256::TestSha256;
+use kernel::common::dynamic_deferred_call;
+use kernel::hil::digest::{Digest, SHA256_DIGEST_LEN};
+
+type Sha = SoftwareSha256<'static>;
+
+pub unsafe fn run_sha256() {
+    let sha = static_init!(Sha, SoftwareSha256::new());
+    sha.initialize_deferred_call(
+        dynamic_deferred_call::register(sha).expect("no deferred call for software_sha256"),
+    );
+
+    let data = static_init!([u8; 64], [0; 64]);
+    let dest = static_init!([u8; SHA256_DIGEST_LEN], [0; SHA256_DIGEST_LEN]);
+    let t = static_init!(TestSha256<'static, Sha>, TestSha256::new(sha, data, dest));
+    sha.set_client(t);
+
+    t.run();
+}
@@ -217,7 +217,7 @@ pub unsafe fn reset_handler() {
 
     let gpio = static_init!(
         capsules::gpio::GPIO<'static, nrf5x::gpio::GPIOPin>,
-        capsules::gpio::GPIO::new(gpio_pins)
+        capsules::gpio::GPIO::new(gpio_pins, kernel::Grant::create())
     );
     for pin in gpio_pins.iter() {
         pin.set_client(gpio);
@@ -350,5 +350,6 @@ pub unsafe fn reset_handler() {
         &mut chip,
         &mut PROCESSES,
         Some(&kernel::ipc::IPC::new()),
+        None,
     );
 }
@@ -125,6 +125,8 @@ pub unsafe fn reset_handler() {
         LED3_PIN,
         led_pins,
         button_pins,
+        &nrf5x::gpio::PORT[31],
+        None,
         &mut APP_MEMORY,
         &mut PROCESSES,
         FAULT_RESPONSE,
@@ -18,7 +18,6 @@
 //! * P0.25 -> (top left header)
 //! * P0.24 -> (top left header)
 //! * P0.23 -> (top left header)
-//! * P0.22 -> (top left header)
 //! * P0.12 -> (top mid header)
 //! * P0.11 -> (top mid header)
 //! * P0.03 -> (bottom right header)
@@ -28,6 +27,9 @@
 //! * P0.30 -> (bottom right header)
 //! * P0.31 -> (bottom right header)
 //!
+//! ### `PWM`
+//! * P0.22 -> (top left header), for the process named `dimmer`
+//!
 //! ### `LEDs`
 //! * P0.17 -> LED1
 //! * P0.18 -> LED2
@@ -117,7 +119,7 @@ pub unsafe fn reset_handler() {
 
     // GPIOs
     let gpio_pins = static_init!(
-        [&'static nrf5x::gpio::GPIOPin; 15],
+        [&'static nrf5x::gpio::GPIOPin; 14],
         [
             &nrf5x::gpio::PORT[3], // Bottom right header on DK board
             &nrf5x::gpio::PORT[4],
@@ -132,8 +134,7 @@ pub unsafe fn reset_handler() {
             &nrf5x::gpio::PORT[2],
             &nrf5x::gpio::PORT[25],
             &nrf5x::gpio::PORT[24],
-            &nrf5x::gpio::PORT[23],
-            &nrf5x::gpio::PORT[22], // -----
+            &nrf5x::gpio::PORT[23], // -----
         ]
     );
 
@@ -190,6 +191,8 @@ pub unsafe fn reset_handler() {
         LED3_PIN,
         led_pins,
         button_pins,
+        &nrf5x::gpio::PORT[22],
+        Some("dimmer"),
         &mut APP_MEMORY,
         &mut PROCESSES,
         FAULT_RESPONSE,
@@ -10,8 +10,19 @@ extern crate nrf52;
 extern crate nrf5x;
 
 use capsules::virtual_alarm::VirtualMuxAlarm;
+use kernel::common::dynamic_deferred_call;
 use nrf5x::rtc::Rtc;
 
+// How long the kernel main loop may go without making progress before the
+// watchdog resets the board.
+const WATCHDOG_TIMEOUT_MS: usize = 5000;
+
+type SoftwarePwmPin = capsules::software_pwm::SoftwarePwmPin<
+    'static,
+    nrf5x::gpio::GPIOPin,
+    VirtualMuxAlarm<'static, Rtc>,
+>;
+
 /// Supported drivers by the platform
 pub struct Platform {
     ble_radio: &'static capsules::ble_advertising_driver::BLE<
@@ -20,12 +31,21 @@ pub struct Platform {
         VirtualMuxAlarm<'static, Rtc>,
     >,
     button: &'static capsules::button::Button<'static, nrf5x::gpio::GPIOPin>,
+    crc: &'static capsules::crc::Crc<'static, capsules::software_crc::SoftwareCrc<'static>>,
     console: &'static capsules::console::Console<'static, nrf52::uart::Uarte>,
+    date_time: &'static capsules::date_time::DateTimeDriver<
+        'static,
+        capsules::software_date_time::SoftwareDateTime<'static, VirtualMuxAlarm<'static, Rtc>>,
+    >,
     gpio: &'static capsules::gpio::GPIO<'static, nrf5x::gpio::GPIOPin>,
+    pwm: &'static capsules::pwm::Pwm<'static, SoftwarePwmPin>,
     led: &'static capsules::led::LED<'static, nrf5x::gpio::GPIOPin>,
     rng: &'static capsules::rng::SimpleRng<'static, nrf5x::trng::Trng<'static>>,
     temp: &'static capsules::temperature::TemperatureSensor<'static>,
     ipc: kernel::ipc::IPC,
+    ipc_messages: kernel::ipc::MessageIPC,
+    process_info: capsules::process_info::ProcessInfo,
+    crash_log: capsules::crash_log::CrashLog,
     alarm: &'static capsules::alarm::AlarmDriver<
         'static,
         capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
@@ -40,13 +60,19 @@ impl kernel::Platform for Platform {
         match driver_num {
             capsules::console::DRIVER_NUM => f(Some(self.console)),
             capsules::gpio::DRIVER_NUM => f(Some(self.gpio)),
+            capsules::pwm::DRIVER_NUM => f(Some(self.pwm)),
             capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
             capsules::led::DRIVER_NUM => f(Some(self.led)),
             capsules::button::DRIVER_NUM => f(Some(self.button)),
             capsules::rng::DRIVER_NUM => f(Some(self.rng)),
             capsules::ble_advertising_driver::DRIVER_NUM => f(Some(self.ble_radio)),
             capsules::temperature::DRIVER_NUM => f(Some(self.temp)),
+            capsules::crc::DRIVER_NUM => f(Some(self.crc)),
+            capsules::date_time::DRIVER_NUM => f(Some(self.date_time)),
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MESSAGE_DRIVER_NUM => f(Some(&self.ipc_messages)),
+            capsules::process_info::DRIVER_NUM => f(Some(&self.process_info)),
+            capsules::crash_log::DRIVER_NUM => f(Some(&self.crash_log)),
             _ => f(None),
         }
     }
@@ -61,6 +87,8 @@ pub unsafe fn setup_board(
     debug_pin3_index: usize,
     led_pins: &'static mut [(&'static nrf5x::gpio::GPIOPin, capsules::led::ActivationMode)],
     button_pins: &'static mut [(&'static nrf5x::gpio::GPIOPin, capsules::button::GpioMode)],
+    pwm_pin: &'static nrf5x::gpio::GPIOPin,
+    pwm_owner: Option<&'static str>,
     app_memory: &mut [u8],
     process_pointers: &'static mut [core::option::Option<
         &'static mut kernel::procs::Process<'static>,
@@ -85,7 +113,7 @@ pub unsafe fn setup_board(
 
     let gpio = static_init!(
         capsules::gpio::GPIO<'static, nrf5x::gpio::GPIOPin>,
-        capsules::gpio::GPIO::new(gpio_pins)
+        capsules::gpio::GPIO::new(gpio_pins, kernel::Grant::create())
     );
     for pin in gpio_pins.iter() {
         pin.set_client(gpio);
@@ -194,6 +222,60 @@ pub unsafe fn setup_board(
     );
     nrf5x::trng::TRNG.set_client(rng);
 
+    // The nRF52 has no CRC unit, so compute CRCs in software
+    let software_crc = static_init!(
+        capsules::software_crc::SoftwareCrc<'static>,
+        capsules::software_crc::SoftwareCrc::new()
+    );
+    software_crc.initialize_deferred_call(
+        dynamic_deferred_call::register(software_crc).expect("no deferred call for software_crc"),
+    );
+    let crc = static_init!(
+        capsules::crc::Crc<'static, capsules::software_crc::SoftwareCrc<'static>>,
+        capsules::crc::Crc::new(software_crc, kernel::Grant::create())
+    );
+    software_crc.set_client(crc);
+
+    // PWM for userspace, toggled in software on a header pin
+    let pwm_alarm = static_init!(
+        capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+        capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
+    );
+    let software_pwm_pin = static_init!(
+        SoftwarePwmPin,
+        capsules::software_pwm::SoftwarePwmPin::new(pwm_pin, pwm_alarm)
+    );
+    pwm_alarm.set_client(software_pwm_pin);
+    let pwm_pins = static_init!([&'static SoftwarePwmPin; 1], [software_pwm_pin]);
+    let pwm = static_init!(
+        capsules::pwm::Pwm<'static, SoftwarePwmPin>,
+        capsules::pwm::Pwm::new(pwm_pins, kernel::Grant::create())
+    );
+    let pwm_owners = static_init!([Option<&'static str>; 1], [pwm_owner]);
+    pwm.set_owners(pwm_owners);
+
+    // The RTC keeps no calendar, so keep the date and time in software
+    let date_time_alarm = static_init!(
+        capsules::virtual_alarm::VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+        capsules::virtual_alarm::VirtualMuxAlarm::new(mux_alarm)
+    );
+    let software_date_time = static_init!(
+        capsules::software_date_time::SoftwareDateTime<'static, VirtualMuxAlarm<'static, Rtc>>,
+        capsules::software_date_time::SoftwareDateTime::new(date_time_alarm)
+    );
+    let date_time_setters = static_init!([&'static str; 1], ["clock_sync"]);
+    let date_time = static_init!(
+        capsules::date_time::DateTimeDriver<
+            'static,
+            capsules::software_date_time::SoftwareDateTime<'static, VirtualMuxAlarm<'static, Rtc>>,
+        >,
+        capsules::date_time::DateTimeDriver::new(
+            software_date_time,
+            date_time_setters,
+            kernel::Grant::create()
+        )
+    );
+
     // Start all of the clocks. Low power operation will require a better
     // approach than this.
     nrf52::clock::CLOCK.low_stop();
@@ -208,18 +290,27 @@ pub unsafe fn setup_board(
 
     let platform = Platform {
         button: button,
+        crc: crc,
+        date_time: date_time,
         ble_radio: ble_radio,
         console: console,
         led: led,
         gpio: gpio,
+        pwm: pwm,
         rng: rng,
         temp: temp,
         alarm: alarm,
         ipc: kernel::ipc::IPC::new(),
+        ipc_messages: kernel::ipc::MessageIPC::new(),
+        process_info: capsules::process_info::ProcessInfo::new(kernel::Grant::create()),
+        crash_log: capsules::crash_log::CrashLog::new(kernel::Grant::create()),
     };
 
     let mut chip = nrf52::chip::NRF52::new();
 
+    // Report any crashes from earlier boots
+    platform.crash_log.report();
+
     debug!("Initialization complete. Entering main loop\r");
     debug!("{}", &nrf52::ficr::FICR_INSTANCE);
 
@@ -234,5 +325,12 @@ pub unsafe fn setup_board(
         app_fault_response,
     );
 
-    kernel::main(&platform, &mut chip, process_pointers, Some(&platform.ipc));
+    kernel::hil::watchdog::Watchdog::start(&nrf5x::wdt::WDT, WATCHDOG_TIMEOUT_MS);
+    kernel::main(
+        &platform,
+        &mut chip,
+        process_pointers,
+        Some(&platform.ipc),
+        Some(&nrf5x::wdt::WDT),
+    );
 }
@@ -0,0 +1,470 @@
+//! Provides userspace with access to AES-128 in CTR and CCM* modes.
+//!
+//! Requests from any number of applications are queued and performed one at a
+//! time on a single AES engine, in the order they were made. CTR requests are
+//! issued to the engine directly, while CCM* requests go through an
+//! `AES128CCM` implementation layered on the same engine. Because the engine
+//! has only one client, this driver is that client and forwards completions to
+//! the CCM* layer while a CCM* request is in flight.
+//!
+//! Setup
+//! -----
+//!
+//! ```rust
+//! let ccm = static_init!(
+//!     capsules::aes_ccm::AES128CCM<'static, Engine>,
+//!     capsules::aes_ccm::AES128CCM::new(engine, &mut capsules::aes::CCM_CRYPT_BUF)
+//! );
+//! let aes = static_init!(
+//!     capsules::aes::AesDriver<'static, Engine, capsules::aes_ccm::AES128CCM<'static, Engine>>,
+//!     capsules::aes::AesDriver::new(
+//!         engine,
+//!         ccm,
+//!         &mut capsules::aes::CTR_BUF,
+//!         &mut capsules::aes::CCM_BUF,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! hil::symmetric_encryption::AES128::set_client(engine, aes);
+//! hil::symmetric_encryption::AES128CCM::set_client(ccm, aes);
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! An application shares a 16 byte key, a counter (CTR) or nonce (CCM*), the
+//! payload and, for CCM*, any additional authenticated data, then issues a
+//! command. The result is written back into the payload buffer and the
+//! callback reports whether the request succeeded and, for CCM* decryption,
+//! whether the authentication tag was valid.
+//!
+//! ```c
+//! allow(AES_DRIVER, 0, key, 16);
+//! allow(AES_DRIVER, 4, nonce, 13);
+//! allow(AES_DRIVER, 2, aad, aad_len);
+//! allow(AES_DRIVER, 1, payload, payload_len + mic_len);
+//! subscribe(AES_DRIVER, 0, callback, NULL);
+//! command(AES_DRIVER, 5, payload_len, mic_len);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::TakeCell;
+use kernel::hil::symmetric_encryption::{self, AES128, AES128CCM, AES128Ctr, AES128_BLOCK_SIZE,
+                                        AES128_KEY_SIZE, CCM_NONCE_LENGTH};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x40000;
+
+/// Kernel buffer for CTR requests. Payloads are limited to this length.
+pub static mut CTR_BUF: [u8; 128] = [0; 128];
+
+/// Kernel buffer for CCM* requests, holding the additional authenticated
+/// data, the payload and the tag.
+pub static mut CCM_BUF: [u8; 128] = [0; 128];
+
+/// Scratch buffer to hand to `AES128CCM::new()`. It must be able to hold the
+/// encoded authentication and payload fields of any request that fits in
+/// `CCM_BUF`.
+pub static mut CCM_CRYPT_BUF: [u8; 3 * 16 + 128] = [0; 3 * 16 + 128];
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Request {
+    Ctr {
+        encrypting: bool,
+        len: usize,
+    },
+    Ccm {
+        encrypting: bool,
+        m_len: usize,
+        mic_len: usize,
+    },
+}
+
+impl Request {
+    /// The command number that issued this request, reported back in the
+    /// callback.
+    fn command_num(&self) -> usize {
+        match *self {
+            Request::Ctr { encrypting, .. } => if encrypting {
+                2
+            } else {
+                3
+            },
+            Request::Ccm { encrypting, .. } => if encrypting {
+                5
+            } else {
+                6
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    key: Option<AppSlice<Shared, u8>>,
+    data: Option<AppSlice<Shared, u8>>,
+    aad: Option<AppSlice<Shared, u8>>,
+    iv: Option<AppSlice<Shared, u8>>,
+
+    // if Some, the application is waiting for this request to be served
+    waiting: Option<Request>,
+    // when the request was made, relative to other applications'
+    seq: usize,
+}
+
+pub struct AesDriver<'a, A: AES128<'a> + AES128Ctr + 'a, C: AES128CCM<'a> + 'a> {
+    aes: &'a A,
+    ccm: &'a C,
+    ctr_buf: TakeCell<'a, [u8]>,
+    ccm_buf: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+    serving: Cell<Option<(AppId, Request)>>,
+    seq: Cell<usize>,
+    aad_len: Cell<usize>,
+}
+
+impl<'a, A, C> AesDriver<'a, A, C>
+where
+    A: AES128<'a> + AES128Ctr + 'a,
+    C: AES128CCM<'a> + symmetric_encryption::Client<'a> + 'a,
+{
+    pub fn new(
+        aes: &'a A,
+        ccm: &'a C,
+        ctr_buf: &'a mut [u8],
+        ccm_buf: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> AesDriver<'a, A, C> {
+        AesDriver {
+            aes: aes,
+            ccm: ccm,
+            ctr_buf: TakeCell::new(ctr_buf),
+            ccm_buf: TakeCell::new(ccm_buf),
+            apps: apps,
+            serving: Cell::new(None),
+            seq: Cell::new(0),
+            aad_len: Cell::new(0),
+        }
+    }
+
+    /// Start the oldest waiting request, if the engine is free.
+    fn serve_waiting_apps(&self) {
+        while self.serving.get().is_none() {
+            let mut next: Option<(usize, AppId)> = None;
+            for app in self.apps.iter() {
+                app.enter(|app, _| {
+                    if app.waiting.is_some() && next.map_or(true, |(seq, _)| app.seq < seq) {
+                        next = Some((app.seq, app.appid()));
+                    }
+                });
+            }
+            let appid = match next {
+                Some((_, appid)) => appid,
+                None => return,
+            };
+
+            let entered = self.apps.enter(appid, |app, _| {
+                if let Some(request) = app.waiting {
+                    let res = match request {
+                        Request::Ctr { encrypting, len } => {
+                            self.start_ctr(appid, app, encrypting, len)
+                        }
+                        Request::Ccm {
+                            encrypting,
+                            m_len,
+                            mic_len,
+                        } => self.start_ccm(appid, app, encrypting, m_len, mic_len),
+                    };
+                    if res != ReturnCode::SUCCESS {
+                        // The app's request failed
+                        app.waiting = None;
+                        if let Some(mut callback) = app.callback {
+                            callback.schedule(From::from(res), request.command_num(), 0);
+                        }
+                    }
+                }
+            });
+            if entered.is_err() {
+                return;
+            }
+        }
+    }
+
+    fn start_ctr(&self, appid: AppId, app: &mut App, encrypting: bool, len: usize) -> ReturnCode {
+        let (key, iv, data) = match (&app.key, &app.iv, &app.data) {
+            (&Some(ref key), &Some(ref iv), &Some(ref data)) => (key, iv, data),
+            _ => return ReturnCode::EINVAL,
+        };
+        if key.len() != AES128_KEY_SIZE || iv.len() != AES128_BLOCK_SIZE {
+            return ReturnCode::EINVAL;
+        }
+        let len = if len == 0 { data.len() } else { len };
+        if len > data.len() {
+            return ReturnCode::EINVAL;
+        }
+
+        let buf = match self.ctr_buf.take() {
+            Some(buf) => buf,
+            None => return ReturnCode::EBUSY,
+        };
+        // The engine only processes whole blocks, so pad the payload
+        let padded_len = (len + AES128_BLOCK_SIZE - 1) / AES128_BLOCK_SIZE * AES128_BLOCK_SIZE;
+        if padded_len > buf.len() {
+            self.ctr_buf.replace(buf);
+            return ReturnCode::ESIZE;
+        }
+        buf[..len].copy_from_slice(&data.as_ref()[..len]);
+        buf[len..padded_len].iter_mut().for_each(|b| *b = 0);
+
+        self.aes.enable();
+        self.aes.set_mode_aes128ctr(encrypting);
+        self.aes.set_key(key.as_ref());
+        self.aes.set_iv(iv.as_ref());
+        self.aes.start_message();
+        match self.aes.crypt(None, buf, 0, padded_len) {
+            None => {
+                let request = Request::Ctr {
+                    encrypting: encrypting,
+                    len: len,
+                };
+                self.serving.set(Some((appid, request)));
+                ReturnCode::SUCCESS
+            }
+            Some((res, _, buf)) => {
+                self.ctr_buf.replace(buf);
+                res
+            }
+        }
+    }
+
+    fn start_ccm(
+        &self,
+        appid: AppId,
+        app: &mut App,
+        encrypting: bool,
+        m_len: usize,
+        mic_len: usize,
+    ) -> ReturnCode {
+        let (key, nonce, data) = match (&app.key, &app.iv, &app.data) {
+            (&Some(ref key), &Some(ref nonce), &Some(ref data)) => (key, nonce, data),
+            _ => return ReturnCode::EINVAL,
+        };
+        if key.len() != AES128_KEY_SIZE || nonce.len() != CCM_NONCE_LENGTH {
+            return ReturnCode::EINVAL;
+        }
+        match mic_len {
+            0 | 4 | 6 | 8 | 10 | 12 | 14 | 16 => {}
+            _ => return ReturnCode::EINVAL,
+        }
+        if m_len + mic_len > data.len() {
+            return ReturnCode::EINVAL;
+        }
+        let aad_len = app.aad.as_ref().map_or(0, |aad| aad.len());
+
+        let buf = match self.ccm_buf.take() {
+            Some(buf) => buf,
+            None => return ReturnCode::EBUSY,
+        };
+        if aad_len + m_len + mic_len > buf.len() {
+            self.ccm_buf.replace(buf);
+            return ReturnCode::ESIZE;
+        }
+        if let Some(ref aad) = app.aad {
+            buf[..aad_len].copy_from_slice(aad.as_ref());
+        }
+        // When decrypting, the tag follows the ciphertext
+        let copy_len = if encrypting { m_len } else { m_len + mic_len };
+        buf[aad_len..aad_len + copy_len].copy_from_slice(&data.as_ref()[..copy_len]);
+
+        self.ccm.set_key(key.as_ref());
+        self.ccm.set_nonce(nonce.as_ref());
+        match self.ccm
+            .crypt(buf, 0, aad_len, m_len, mic_len, true, encrypting)
+        {
+            (ReturnCode::SUCCESS, _) => {
+                let request = Request::Ccm {
+                    encrypting: encrypting,
+                    m_len: m_len,
+                    mic_len: mic_len,
+                };
+                self.serving.set(Some((appid, request)));
+                self.aad_len.set(aad_len);
+                ReturnCode::SUCCESS
+            }
+            (res, buf) => {
+                buf.map(|buf| self.ccm_buf.replace(buf));
+                res
+            }
+        }
+    }
+
+    /// Deliver the outcome of the request in progress to its app. The caller
+    /// must then put back the kernel buffer and serve the next request.
+    fn finish_request(&self, res: ReturnCode, tag_is_valid: bool, result: &[u8]) {
+        if let Some((appid, request)) = self.serving.take() {
+            self.apps
+                .enter(appid, |app, _| {
+                    app.waiting = None;
+                    if res == ReturnCode::SUCCESS {
+                        if let Some(ref mut data) = app.data {
+                            let len = cmp::min(result.len(), data.len());
+                            data.as_mut()[..len].copy_from_slice(&result[..len]);
+                        }
+                    }
+                    if let Some(mut callback) = app.callback {
+                        callback.schedule(
+                            From::from(res),
+                            request.command_num(),
+                            tag_is_valid as usize,
+                        );
+                    }
+                })
+                .unwrap_or(());
+        }
+    }
+}
+
+impl<'a, A, C> Driver for AesDriver<'a, A, C>
+where
+    A: AES128<'a> + AES128Ctr + 'a,
+    C: AES128CCM<'a> + symmetric_encryption::Client<'a> + 'a,
+{
+    /// Share buffers with the driver.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The 16 byte key.
+    /// - `1`: The payload. Results are written back into this buffer.
+    /// - `2`: Additional authenticated data for CCM* requests.
+    /// - `4`: The 16 byte initial counter for CTR requests, or the 13 byte
+    ///        nonce for CCM* requests.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                match allow_num {
+                    0 => app.key = slice,
+                    1 => app.data = slice,
+                    2 => app.aad = slice,
+                    4 => app.iv = slice,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Subscribe to request completions.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Called with `(status, command_num, tag_is_valid)` when a
+    ///        request completes. `tag_is_valid` is only meaningful for
+    ///        command `6`.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Queue a request.
+    ///
+    /// Each application may have only one request outstanding; further
+    /// requests return `EBUSY` until its callback has been scheduled.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `2`: CTR encrypt the first `data` bytes of the payload, or all of
+    ///        it if `data` is zero.
+    /// - `3`: CTR decrypt, with the same arguments as `2`.
+    /// - `5`: CCM* encrypt and authenticate `data` bytes of payload,
+    ///        appending a `data2` byte tag after it.
+    /// - `6`: CCM* decrypt `data` bytes of payload and verify the `data2`
+    ///        byte tag that follows it.
+    fn command(&self, command_num: usize, data: usize, data2: usize, appid: AppId) -> ReturnCode {
+        let request = match command_num {
+            0 => return ReturnCode::SUCCESS,
+            2 | 3 => Request::Ctr {
+                encrypting: command_num == 2,
+                len: data,
+            },
+            5 | 6 => Request::Ccm {
+                encrypting: command_num == 5,
+                m_len: data,
+                mic_len: data2,
+            },
+            _ => return ReturnCode::ENOSUPPORT,
+        };
+
+        let res = self.apps
+            .enter(appid, |app, _| {
+                if app.waiting.is_some() {
+                    ReturnCode::EBUSY
+                } else if app.callback.is_none() {
+                    ReturnCode::EINVAL
+                } else {
+                    app.waiting = Some(request);
+                    app.seq = self.seq.get();
+                    self.seq.set(self.seq.get().wrapping_add(1));
+                    ReturnCode::SUCCESS
+                }
+            })
+            .unwrap_or_else(|err| err.into());
+        if res == ReturnCode::SUCCESS {
+            self.serve_waiting_apps();
+        }
+        res
+    }
+}
+
+impl<'a, A, C> symmetric_encryption::Client<'a> for AesDriver<'a, A, C>
+where
+    A: AES128<'a> + AES128Ctr + 'a,
+    C: AES128CCM<'a> + symmetric_encryption::Client<'a> + 'a,
+{
+    fn crypt_done(&'a self, source: Option<&'a mut [u8]>, dest: &'a mut [u8]) {
+        match self.serving.get() {
+            Some((_, Request::Ctr { len, .. })) => {
+                self.finish_request(ReturnCode::SUCCESS, false, &dest[..len]);
+                self.ctr_buf.replace(dest);
+                self.serve_waiting_apps();
+            }
+            _ => self.ccm.crypt_done(source, dest),
+        }
+    }
+}
+
+impl<'a, A, C> symmetric_encryption::CCMClient for AesDriver<'a, A, C>
+where
+    A: AES128<'a> + AES128Ctr + 'a,
+    C: AES128CCM<'a> + symmetric_encryption::Client<'a> + 'a,
+{
+    fn crypt_done(&self, buf: &'static mut [u8], res: ReturnCode, tag_is_valid: bool) {
+        if let Some((_, Request::Ccm { encrypting, m_len, mic_len })) = self.serving.get() {
+            let aad_len = self.aad_len.get();
+            let out_len = if encrypting { m_len + mic_len } else { m_len };
+            self.finish_request(res, tag_is_valid, &buf[aad_len..aad_len + out_len]);
+        }
+        self.ccm_buf.replace(buf);
+        self.serve_waiting_apps();
+    }
+}
@@ -1,81 +1,173 @@
 //! Provides userspace applications with a alarm API.
+//!
+//! Each process may have up to `MAX_ALARMS_PER_APP` alarms outstanding at
+//! once. Besides the basic alarm set with command `4`, which a process moves
+//! around as it needs, alarms are identified by an id returned when they are
+//! set, and are either one-shot or periodic. Periodic alarms are rearmed by
+//! the kernel a period after their previous deadline, not after they were
+//! serviced, so they do not drift.
+//!
+//! Deadlines are kept on the 64-bit clock of the underlying alarm, so they
+//! are not confused by wraps of the counter.
 
-use core::cell::Cell;
-use kernel::hil::time::{self, Alarm, Frequency};
+use core::cmp;
+use kernel::hil::time::{self, Alarm, Alarm64, Frequency};
 use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
 
 /// Syscall driver number.
 pub const DRIVER_NUM: usize = 0x00000000;
 
+/// Number of alarms each process may have outstanding at once, including
+/// the basic alarm.
+pub const MAX_ALARMS_PER_APP: usize = 4;
+
+/// Id of the basic alarm set with command `4`.
+const BASIC_ALARM_ID: usize = 0;
+
+/// Largest alarm id, so that ids are positive when returned to userspace.
+const MAX_ALARM_ID: usize = 0x7fff_ffff;
+
+/// Shortest period of a periodic alarm, in milliseconds, so that a process
+/// cannot keep the kernel rearming alarms and queueing callbacks.
+const MIN_PERIOD_MS: u32 = 1;
+
 #[derive(Copy, Clone, Debug)]
-enum Expiration {
-    Disabled,
-    Abs(u32),
+struct Expiration {
+    id: usize,
+    /// Deadline on the 64-bit clock
+    when: u64,
+    /// Zero for one-shot alarms
+    period: u64,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Default)]
 pub struct AlarmData {
-    expiration: Expiration,
+    alarms: [Option<Expiration>; MAX_ALARMS_PER_APP],
+    last_id: usize,
     callback: Option<Callback>,
 }
 
-impl Default for AlarmData {
-    fn default() -> AlarmData {
-        AlarmData {
-            expiration: Expiration::Disabled,
-            callback: None,
+impl AlarmData {
+    /// Returns an id that is not in use, other than the basic alarm's.
+    fn new_id(&mut self) -> usize {
+        loop {
+            self.last_id = if self.last_id >= MAX_ALARM_ID {
+                1
+            } else {
+                self.last_id + 1
+            };
+            let id = self.last_id;
+            if !self.alarms
+                .iter()
+                .any(|alarm| alarm.map_or(false, |alarm| alarm.id == id))
+            {
+                return id;
+            }
+        }
+    }
+
+    fn get(&self, id: usize) -> Option<Expiration> {
+        self.alarms
+            .iter()
+            .filter_map(|alarm| *alarm)
+            .find(|alarm| alarm.id == id)
+    }
+
+    /// Remove an alarm. Returns whether there was one.
+    fn remove(&mut self, id: usize) -> bool {
+        match self.alarms
+            .iter_mut()
+            .find(|alarm| alarm.map_or(false, |alarm| alarm.id == id))
+        {
+            Some(slot) => {
+                *slot = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add an alarm in a free slot, if any.
+    fn insert(&mut self, expiration: Expiration) -> ReturnCode {
+        match self.alarms.iter_mut().find(|alarm| alarm.is_none()) {
+            Some(slot) => {
+                *slot = Some(expiration);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOMEM,
         }
     }
 }
 
-pub struct AlarmDriver<'a, A: Alarm + 'a> {
+pub struct AlarmDriver<'a, A: Alarm + Alarm64 + 'a> {
     alarm: &'a A,
-    num_armed: Cell<usize>,
     app_alarm: Grant<AlarmData>,
-    prev: Cell<u32>,
 }
 
-impl<'a, A: Alarm> AlarmDriver<'a, A> {
+impl<'a, A: Alarm + Alarm64> AlarmDriver<'a, A> {
     pub const fn new(alarm: &'a A, grant: Grant<AlarmData>) -> AlarmDriver<'a, A> {
         AlarmDriver {
             alarm: alarm,
-            num_armed: Cell::new(0),
             app_alarm: grant,
-            prev: Cell::new(0),
         }
     }
 
-    fn reset_active_alarm(&self, now: u32) -> Option<u32> {
-        self.prev.set(now);
-        let mut next_alarm = u32::max_value();
-        let mut next_dist = u32::max_value();
+    /// The shortest period, in tics, of a periodic alarm.
+    fn min_period(&self) -> usize {
+        cmp::max(<A::Frequency>::frequency() * MIN_PERIOD_MS / 1000, 1) as usize
+    }
+
+    /// The counter value at time `when`, as seen by processes.
+    fn tics(&self, when: u64) -> usize {
+        (when as u32 & self.alarm.max_tics()) as usize
+    }
+
+    /// The most recent time, no later than now, at which the counter read
+    /// `tics`.
+    fn reference(&self, now: u64, tics: u32) -> u64 {
+        let ago = (now as u32).wrapping_sub(tics) & self.alarm.max_tics();
+        now.saturating_sub(ago as u64)
+    }
+
+    /// The time at which the counter next reads `tics`, or last did if that
+    /// was less than half a wrap ago.
+    fn deadline(&self, now: u64, tics: u32) -> u64 {
+        let wrap = self.alarm.max_tics() as u64 + 1;
+        let ahead = (tics.wrapping_sub(now as u32) & self.alarm.max_tics()) as u64;
+        if ahead < wrap / 2 {
+            now + ahead
+        } else {
+            (now + ahead).saturating_sub(wrap)
+        }
+    }
+
+    /// Set the underlying alarm for the soonest deadline of any process.
+    fn reset_active_alarm(&self) {
+        let mut next: Option<u64> = None;
         for alarm in self.app_alarm.iter() {
-            alarm.enter(|alarm, _| match alarm.expiration {
-                Expiration::Abs(exp) => {
-                    let t_dist = exp.wrapping_sub(now);
-                    if next_dist > t_dist {
-                        next_alarm = exp;
-                        next_dist = t_dist;
+            alarm.enter(|alarm, _| {
+                for expiration in alarm.alarms.iter().filter_map(|alarm| *alarm) {
+                    if next.map_or(true, |next| expiration.when < next) {
+                        next = Some(expiration.when);
                     }
                 }
-                Expiration::Disabled => {}
             });
         }
-        if next_alarm != u32::max_value() {
-            self.alarm.set_alarm(next_alarm);
-            Some(next_alarm)
-        } else {
-            None
+        match next {
+            Some(when) => self.alarm.set_alarm64(when, 0),
+            None => self.alarm.disable(),
         }
     }
 }
 
-impl<'a, A: Alarm> Driver for AlarmDriver<'a, A> {
+impl<'a, A: Alarm + Alarm64> Driver for AlarmDriver<'a, A> {
     /// Subscribe to alarm expiration
     ///
     /// ### `_subscribe_num`
     ///
-    /// - `0`: Subscribe to alarm expiration
+    /// - `0`: Subscribe to alarm expiration. The callback receives the clock
+    ///        value when the alarm was serviced, the clock value of its
+    ///        deadline and the alarm's id.
     fn subscribe(
         &self,
         _subscribe_num: usize,
@@ -94,100 +186,134 @@ impl<'a, A: Alarm> Driver for AlarmDriver<'a, A> {
     ///
     /// ### `command_num`
     ///
-    /// - `0`: Driver check.
+    /// - `0`: Driver check. Returns the number of alarms a process may have.
     /// - `1`: Return the clock frequency in Hz.
     /// - `2`: Read the the current clock value
-    /// - `3`: Stop the alarm if it is outstanding
-    /// - `4`: Set an alarm to fire at a given clock value `time`.
-    fn command(&self, cmd_type: usize, data: usize, _: usize, caller_id: AppId) -> ReturnCode {
+    /// - `3`: Stop the basic alarm if it is set for clock value `data`.
+    /// - `4`: Set the basic alarm to fire at clock value `data`, replacing any
+    ///        previous one.
+    /// - `5`: Set a one-shot alarm to fire `arg2` tics after clock value
+    ///        `data`. Returns the alarm's id.
+    /// - `6`: Set a periodic alarm to fire every `arg2` tics, starting
+    ///        `arg2` tics after clock value `data`. Returns the alarm's id,
+    ///        or `EINVAL` if `arg2` is shorter than a millisecond.
+    /// - `7`: Cancel the alarm with id `data`.
+    ///
+    /// Commands `4`, `5` and `6` return `ENOMEM` if the process already has
+    /// as many alarms as it may.
+    fn command(&self, cmd_type: usize, data: usize, arg2: usize, caller_id: AppId) -> ReturnCode {
         // Returns the error code to return to the user and whether we need to
-        // reset which is the next active alarm. We only _don't_ reset if we're
-        // disabling the underlying alarm anyway, if the underlying alarm is
-        // currently disabled and we're enabling the first alarm, or on an error
-        // (i.e. no change to the alarms).
-        self.app_alarm
+        // reset which is the next active alarm.
+        let (return_code, reset) = self.app_alarm
             .enter(caller_id, |td, _alloc| {
-                let now = self.alarm.now();
-                let (return_code, reset) = match cmd_type {
-                    0 /* check if present */ => (ReturnCode::SuccessWithValue { value: 1 }, false),
+                let now = self.alarm.now64();
+                match cmd_type {
+                    0 /* check if present */ => {
+                        (ReturnCode::SuccessWithValue { value: MAX_ALARMS_PER_APP }, false)
+                    },
                     1 /* Get clock frequency */ => {
                         let freq = <A::Frequency>::frequency() as usize;
                         (ReturnCode::SuccessWithValue { value: freq }, false)
                     },
                     2 /* capture time */ => {
-                        (ReturnCode::SuccessWithValue { value: now as usize },
+                        (ReturnCode::SuccessWithValue { value: self.tics(now) },
                          false)
                     },
                     3 /* Stop */ => {
-                        let alarm_id = data as u32;
-                        match td.expiration {
-                            Expiration::Disabled => {
+                        match td.get(BASIC_ALARM_ID) {
+                            None => {
                                 // Request to stop when already stopped
                                 (ReturnCode::EALREADY, false)
                             },
-                            Expiration::Abs(exp) if exp != alarm_id => {
+                            Some(alarm) if self.tics(alarm.when) != data => {
                                 // Request to stop invalid alarm id
                                 (ReturnCode::EINVAL, false)
                             },
                             _ => {
-                                td.expiration = Expiration::Disabled;
-                                let new_num_armed = self.num_armed.get() - 1;
-                                self.num_armed.set(new_num_armed);
+                                td.remove(BASIC_ALARM_ID);
                                 (ReturnCode::SUCCESS, true)
                             }
                         }
                     },
                     4 /* Set absolute expiration */ => {
-                        let time = data;
-                        // if previously unarmed, but now will become armed
-                        if let Expiration::Disabled = td.expiration {
-                            self.num_armed.set(self.num_armed.get() + 1);
+                        td.remove(BASIC_ALARM_ID);
+                        let expiration = Expiration {
+                            id: BASIC_ALARM_ID,
+                            when: self.deadline(now, data as u32),
+                            period: 0,
+                        };
+                        match td.insert(expiration) {
+                            ReturnCode::SUCCESS => {
+                                (ReturnCode::SuccessWithValue { value: data }, true)
+                            }
+                            err => (err, false),
+                        }
+                    },
+                    5 /* Set one-shot alarm */ | 6 /* Set periodic alarm */ => {
+                        if cmd_type == 6 && arg2 < self.min_period() {
+                            return (ReturnCode::EINVAL, false);
+                        }
+                        let id = td.new_id();
+                        let expiration = Expiration {
+                            id: id,
+                            when: self.reference(now, data as u32) + arg2 as u64,
+                            period: if cmd_type == 6 { arg2 as u64 } else { 0 },
+                        };
+                        match td.insert(expiration) {
+                            ReturnCode::SUCCESS => {
+                                (ReturnCode::SuccessWithValue { value: id }, true)
+                            }
+                            err => (err, false),
+                        }
+                    },
+                    7 /* Cancel by id */ => {
+                        if td.remove(data) {
+                            (ReturnCode::SUCCESS, true)
+                        } else {
+                            (ReturnCode::EINVAL, false)
                         }
-                        td.expiration = Expiration::Abs(time as u32);
-                        (ReturnCode::SuccessWithValue { value: time }, true)
                     },
                     _ => (ReturnCode::ENOSUPPORT, false)
-                };
-                if reset {
-                    self.reset_active_alarm(now);
                 }
-                return_code
             })
-            .unwrap_or_else(|err| err.into())
+            .unwrap_or_else(|err| (err.into(), false));
+        if reset {
+            self.reset_active_alarm();
+        }
+        return_code
     }
 }
 
-fn has_expired(alarm: u32, now: u32, prev: u32) -> bool {
-    now.wrapping_sub(prev) >= alarm.wrapping_sub(prev)
-}
-
-impl<'a, A: Alarm> time::Client for AlarmDriver<'a, A> {
+impl<'a, A: Alarm + Alarm64> time::Client for AlarmDriver<'a, A> {
     fn fired(&self) {
-        let now = self.alarm.now();
+        let now = self.alarm.now64();
         self.app_alarm.each(|alarm| {
-            if let Expiration::Abs(exp) = alarm.expiration {
-                let expired = has_expired(exp, now, self.prev.get());
-                if expired {
-                    alarm.expiration = Expiration::Disabled;
-                    self.num_armed.set(self.num_armed.get() - 1);
-                    alarm
-                        .callback
-                        .map(|mut cb| cb.schedule(now as usize, exp as usize, 0));
+            let callback = alarm.callback;
+            for slot in alarm.alarms.iter_mut() {
+                let expiration = match *slot {
+                    Some(expiration) if expiration.when <= now => expiration,
+                    _ => continue,
+                };
+
+                if expiration.period == 0 {
+                    *slot = None;
+                } else {
+                    // Rearm relative to the deadline, skipping any periods
+                    // that were missed entirely.
+                    let missed = (now - expiration.when) / expiration.period;
+                    *slot = Some(Expiration {
+                        when: expiration.when + (missed + 1) * expiration.period,
+                        ..expiration
+                    });
                 }
+
+                callback.map(|mut cb| {
+                    cb.schedule(self.tics(now), self.tics(expiration.when), expiration.id)
+                });
             }
         });
 
-        // If there are armed alarms left, reset the underlying alarm to the
-        // nearest interval.  Otherwise, disable the underlying alarm.
-        if self.num_armed.get() == 0 {
-            self.alarm.disable();
-        } else if let Some(next_alarm) = self.reset_active_alarm(now) {
-            let new_now = self.alarm.now();
-            if has_expired(next_alarm, new_now, now) {
-                self.fired();
-            }
-        } else {
-            self.alarm.disable();
-        }
+        // Set the underlying alarm for the nearest deadline left, if any.
+        self.reset_active_alarm();
     }
 }
@@ -80,7 +80,7 @@ impl<'a> AppFlash<'a> {
                 if flash_address < app_flash_start || flash_address >= app_flash_end
                     || flash_address + flash_length >= app_flash_end
                 {
-                    return ReturnCode::EINVAL;
+                    return ReturnCode::EPERM;
                 }
 
                 if self.current_app.get().is_none() {
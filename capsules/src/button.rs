@@ -72,7 +72,7 @@ pub enum GpioMode {
 
 /// Values that are passed to userspace to identify if the button is pressed
 /// or not.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum ButtonState {
     NotPressed = 0,
     Pressed = 1,
@@ -33,6 +33,29 @@
 //! When the buffer has been written successfully, the buffer is released from
 //! the driver. Successive writes must call `allow` each time a buffer is to be
 //! written.
+//!
+//! Reading
+//! -------
+//!
+//! Several processes may have a read pending at once. Each received byte goes
+//! to the process holding the input focus, if any, and otherwise to the
+//! process whose read has been pending the longest. A process takes the focus
+//! with command `5`, for example an interactive shell, so that other processes
+//! only get input while it does not want it. The focus is released with
+//! command `6`, or when the process exits.
+//!
+//! By default reads return raw bytes. With command `4` a process can ask for
+//! its input to be echoed, and for line editing: backspace and delete erase
+//! the last byte, CR, LF and CR LF all end the line as a single `\n`, and the
+//! read completes at the end of the line.
+//!
+//! ```c
+//! // Echo and line editing
+//! command(CONSOLE_DRIVER_NUM, 4, 0x3, 0);
+//! allow(CONSOLE_DRIVER_NUM, 2, line, sizeof(line));
+//! subscribe(CONSOLE_DRIVER_NUM, 2, line_callback);
+//! command(CONSOLE_DRIVER_NUM, 2, sizeof(line), 0);
+//! ```
 
 use core::cell::Cell;
 use core::cmp;
@@ -43,6 +66,17 @@ use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
 /// Syscall driver number.
 pub const DRIVER_NUM: usize = 0x00000001;
 
+/// Receive mode flag: echo received bytes.
+pub const MODE_ECHO: usize = 0x1;
+/// Receive mode flag: edit and complete reads by line.
+pub const MODE_LINE: usize = 0x2;
+
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+/// Bytes of echo waiting for the UART. Echo beyond this is dropped.
+const ECHO_LEN: usize = 16;
+
 pub struct App {
     write_callback: Option<Callback>,
     write_buffer: Option<AppSlice<Shared, u8>>,
@@ -53,6 +87,14 @@ pub struct App {
     read_callback: Option<Callback>,
     read_buffer: Option<AppSlice<Shared, u8>>,
     read_len: usize,
+    /// Bytes received so far by the pending read
+    read_pos: usize,
+    pending_read: bool,
+    /// When the pending read started, relative to other processes' reads
+    read_seq: usize,
+    mode: usize,
+    /// Whether the last byte was a CR, in line mode
+    after_cr: bool,
 }
 
 impl Default for App {
@@ -67,6 +109,11 @@ impl Default for App {
             read_callback: None,
             read_buffer: None,
             read_len: 0,
+            read_pos: 0,
+            pending_read: false,
+            read_seq: 0,
+            mode: 0,
+            after_cr: false,
         }
     }
 }
@@ -79,8 +126,12 @@ pub struct Console<'a, U: UART + 'a> {
     apps: Grant<App>,
     tx_in_progress: Cell<Option<AppId>>,
     tx_buffer: TakeCell<'static, [u8]>,
-    rx_in_progress: Cell<Option<AppId>>,
+    rx_in_progress: Cell<bool>,
     rx_buffer: TakeCell<'static, [u8]>,
+    read_seq: Cell<usize>,
+    focus: Cell<Option<AppId>>,
+    echo: Cell<[u8; ECHO_LEN]>,
+    echo_len: Cell<usize>,
     baud_rate: u32,
 }
 
@@ -97,8 +148,12 @@ impl<'a, U: UART> Console<'a, U> {
             apps: grant,
             tx_in_progress: Cell::new(None),
             tx_buffer: TakeCell::new(tx_buffer),
-            rx_in_progress: Cell::new(None),
+            rx_in_progress: Cell::new(false),
             rx_buffer: TakeCell::new(rx_buffer),
+            read_seq: Cell::new(0),
+            focus: Cell::new(None),
+            echo: Cell::new([0; ECHO_LEN]),
+            echo_len: Cell::new(0),
             baud_rate: baud_rate,
         }
     }
@@ -143,7 +198,7 @@ impl<'a, U: UART> Console<'a, U> {
     /// Internal helper function for sending data for an existing transaction.
     /// Cannot fail. If can't send now, it will schedule for sending later.
     fn send(&self, app_id: AppId, app: &mut App, slice: AppSlice<Shared, u8>) {
-        if self.tx_in_progress.get().is_none() {
+        if self.tx_idle() {
             self.tx_in_progress.set(Some(app_id));
             self.tx_buffer.take().map(|buffer| {
                 let mut transaction_len = app.write_remaining;
@@ -175,30 +230,57 @@ impl<'a, U: UART> Console<'a, U> {
         }
     }
 
+    /// Whether the UART is free to transmit, neither for a process nor for
+    /// echo.
+    fn tx_idle(&self) -> bool {
+        self.tx_in_progress.get().is_none() && self.tx_buffer.is_some()
+    }
+
+    /// Queue bytes to echo and send them if the UART is free. Otherwise they
+    /// are sent before the next write of a process starts.
+    fn echo(&self, bytes: &[u8]) {
+        let mut echo = self.echo.get();
+        let len = self.echo_len.get();
+        let count = cmp::min(bytes.len(), ECHO_LEN - len);
+        echo[len..len + count].copy_from_slice(&bytes[..count]);
+        self.echo.set(echo);
+        self.echo_len.set(len + count);
+        self.send_echo();
+    }
+
+    /// Send queued echo if the UART is free. Returns whether it was sent.
+    fn send_echo(&self) -> bool {
+        let len = self.echo_len.get();
+        if len == 0 || !self.tx_idle() {
+            return false;
+        }
+        self.tx_buffer.take().map_or(false, |buffer| {
+            let len = cmp::min(len, buffer.len());
+            buffer[..len].copy_from_slice(&self.echo.get()[..len]);
+            self.echo_len.set(0);
+            self.uart.transmit(buffer, len);
+            true
+        })
+    }
+
     /// Internal helper function for starting a receive operation
-    fn receive_new(&self, app_id: AppId, app: &mut App, len: usize) -> ReturnCode {
-        if self.rx_buffer.is_none() {
-            // For now, we tolerate only one concurrent receive operation on this console.
-            // Competing apps will have to retry until success.
-            return ReturnCode::EBUSY;
+    fn receive_new(&self, app: &mut App, len: usize) -> ReturnCode {
+        if app.pending_read {
+            return ReturnCode::EALREADY;
         }
 
         match app.read_buffer {
             Some(ref slice) => {
                 let read_len = cmp::min(len, slice.len());
-                if read_len > self.rx_buffer.map_or(0, |buf| buf.len()) {
-                    // For simplicity, impose a small maximum receive length
-                    // instead of doing incremental reads
-                    ReturnCode::EINVAL
-                } else {
-                    // Note: We have ensured above that rx_buffer is present
-                    app.read_len = read_len;
-                    self.rx_buffer.take().map(|buffer| {
-                        self.rx_in_progress.set(Some(app_id));
-                        self.uart.receive(buffer, app.read_len);
-                    });
-                    ReturnCode::SUCCESS
+                if read_len == 0 {
+                    return ReturnCode::EINVAL;
                 }
+                app.read_len = read_len;
+                app.read_pos = 0;
+                app.pending_read = true;
+                app.read_seq = self.read_seq.get();
+                self.read_seq.set(self.read_seq.get().wrapping_add(1));
+                ReturnCode::SUCCESS
             }
             None => {
                 // Must supply read buffer before performing receive operation
@@ -206,6 +288,127 @@ impl<'a, U: UART> Console<'a, U> {
             }
         }
     }
+
+    /// The process the next received byte goes to: the one holding the
+    /// focus, or the one whose read has been pending the longest.
+    fn reader(&self) -> Option<AppId> {
+        if let Some(focus) = self.focus.get() {
+            match self.apps.enter(focus, |app, _| app.pending_read) {
+                Ok(true) => return Some(focus),
+                Ok(false) => return None,
+                // The process has exited
+                Err(_) => self.focus.set(None),
+            }
+        }
+
+        let mut oldest: Option<(usize, AppId)> = None;
+        for app in self.apps.iter() {
+            app.enter(|app, _| {
+                if app.pending_read && oldest.map_or(true, |(seq, _)| app.read_seq < seq) {
+                    oldest = Some((app.read_seq, app.appid()));
+                }
+            });
+        }
+        oldest.map(|(_, appid)| appid)
+    }
+
+    /// Receive the next byte, if some process wants it.
+    fn receive_next(&self) {
+        if self.rx_in_progress.get() || self.reader().is_none() {
+            return;
+        }
+        self.rx_buffer.take().map(|buffer| {
+            self.rx_in_progress.set(true);
+            self.uart.receive(buffer, 1);
+        });
+    }
+
+    /// Pass a received byte to the reading process, through its line
+    /// discipline.
+    fn receive_byte(&self, byte: u8) {
+        let appid = match self.reader() {
+            Some(appid) => appid,
+            None => return,
+        };
+        self.apps
+            .enter(appid, |app, _| {
+                let echo = app.mode & MODE_ECHO != 0;
+                if app.mode & MODE_LINE == 0 {
+                    self.store_byte(app, byte);
+                    if echo {
+                        self.echo(&[byte]);
+                    }
+                    return;
+                }
+
+                // Normalize CR, LF and CR LF to a single newline
+                let after_cr = app.after_cr;
+                app.after_cr = byte == b'\r';
+                match byte {
+                    b'\n' if after_cr => {}
+                    b'\r' | b'\n' => {
+                        if echo {
+                            self.echo(b"\r\n");
+                        }
+                        app.read_len = app.read_pos + 1;
+                        self.store_byte(app, b'\n');
+                    }
+                    BACKSPACE | DELETE => {
+                        if app.read_pos > 0 {
+                            app.read_pos -= 1;
+                            if echo {
+                                self.echo(&[BACKSPACE, b' ', BACKSPACE]);
+                            }
+                        }
+                    }
+                    _ => {
+                        if echo {
+                            self.echo(&[byte]);
+                        }
+                        self.store_byte(app, byte);
+                    }
+                }
+            })
+            .unwrap_or_default();
+    }
+
+    /// Store a byte in the process's buffer, completing the read if it is
+    /// full.
+    fn store_byte(&self, app: &mut App, byte: u8) {
+        let pos = app.read_pos;
+        let stored = app.read_buffer.as_mut().map_or(false, |buffer| {
+            if pos < buffer.len() {
+                buffer.as_mut()[pos] = byte;
+                true
+            } else {
+                false
+            }
+        });
+        if !stored {
+            // The buffer was withdrawn or shrunk
+            self.complete_read(app, ReturnCode::ESIZE, false);
+            return;
+        }
+
+        app.read_pos += 1;
+        if app.read_pos == app.read_len {
+            let line = app.mode & MODE_LINE != 0 && byte == b'\n';
+            self.complete_read(app, ReturnCode::SUCCESS, line);
+        }
+    }
+
+    /// Signal the end of a read with the bytes received so far. The third
+    /// callback argument is 1 if the read ended with a line.
+    fn complete_read(&self, app: &mut App, result: ReturnCode, line: bool) {
+        // Release the buffer, as for writes
+        app.read_buffer = None;
+        app.pending_read = false;
+        app.after_cr = false;
+        let len = app.read_pos;
+        app.read_callback.map(|mut cb| {
+            cb.schedule(From::from(result), len, line as usize);
+        });
+    }
 }
 
 impl<'a, U: UART> Driver for Console<'a, U> {
@@ -230,6 +433,10 @@ impl<'a, U: UART> Driver for Console<'a, U> {
                 .unwrap_or_else(|err| err.into()),
             2 => self.apps
                 .enter(appid, |app, _| {
+                    if app.pending_read {
+                        // The read in progress keeps its buffer until it ends
+                        return ReturnCode::EBUSY;
+                    }
                     app.read_buffer = slice;
                     ReturnCode::SUCCESS
                 })
@@ -275,8 +482,13 @@ impl<'a, U: UART> Driver for Console<'a, U> {
     ///        passed in `arg1`
     /// - `2`: Receives into a buffer passed via `allow`, up to the length
     ///        passed in `arg1`
-    /// - `3`: Cancel any in progress receives and return (via callback)
+    /// - `3`: Cancel the process's receive and return (via callback)
     ///        what has been received so far.
+    /// - `4`: Set the receive mode to the flags in `arg1`: `MODE_ECHO`
+    ///        and `MODE_LINE`.
+    /// - `5`: Take the input focus. Returns `EBUSY` if another process
+    ///        has it.
+    /// - `6`: Release the input focus.
     fn command(&self, cmd_num: usize, arg1: usize, _: usize, appid: AppId) -> ReturnCode {
         match cmd_num {
             0 /* check if present */ => ReturnCode::SUCCESS,
@@ -288,13 +500,50 @@ impl<'a, U: UART> Driver for Console<'a, U> {
             },
             2 /* getnstr */ => {
                 let len = arg1;
-                self.apps.enter(appid, |app, _| {
-                    self.receive_new(appid, app, len)
-                }).unwrap_or_else(|err| err.into())
+                let result = self.apps.enter(appid, |app, _| {
+                    self.receive_new(app, len)
+                }).unwrap_or_else(|err| err.into());
+                self.receive_next();
+                result
             },
             3 /* abort rx */ => {
-                self.uart.abort_receive();
-                ReturnCode::SUCCESS
+                self.apps.enter(appid, |app, _| {
+                    if app.pending_read {
+                        self.complete_read(app, ReturnCode::SUCCESS, false);
+                    }
+                    ReturnCode::SUCCESS
+                }).unwrap_or_else(|err| err.into())
+            }
+            4 /* set receive mode */ => {
+                if arg1 & !(MODE_ECHO | MODE_LINE) != 0 {
+                    return ReturnCode::EINVAL;
+                }
+                self.apps.enter(appid, |app, _| {
+                    app.mode = arg1;
+                    app.after_cr = false;
+                    ReturnCode::SUCCESS
+                }).unwrap_or_else(|err| err.into())
+            }
+            5 /* take focus */ => {
+                match self.focus.get() {
+                    Some(focus) if focus != appid && self.apps.enter(focus, |_, _| ()).is_ok() => {
+                        ReturnCode::EBUSY
+                    }
+                    _ => {
+                        self.focus.set(Some(appid));
+                        ReturnCode::SUCCESS
+                    }
+                }
+            }
+            6 /* release focus */ => {
+                if self.focus.get() == Some(appid) {
+                    self.focus.set(None);
+                    // Input can go to other readers again
+                    self.receive_next();
+                    ReturnCode::SUCCESS
+                } else {
+                    ReturnCode::EALREADY
+                }
             }
             _ => ReturnCode::ENOSUPPORT
         }
@@ -334,9 +583,9 @@ impl<'a, U: UART> Client for Console<'a, U> {
             })
         });
 
-        // If we are not printing more from the current AppSlice,
-        // see if any other applications have pending messages.
-        if self.tx_in_progress.get().is_none() {
+        // If we are not printing more from the current AppSlice, send any
+        // echo, then see if any other applications have pending messages.
+        if self.tx_in_progress.get().is_none() && !self.send_echo() {
             for cntr in self.apps.iter() {
                 let started_tx = cntr.enter(|app, _| {
                     if app.pending_write {
@@ -367,48 +616,26 @@ impl<'a, U: UART> Client for Console<'a, U> {
     }
 
     fn receive_complete(&self, buffer: &'static mut [u8], rx_len: usize, error: uart::Error) {
+        let byte = buffer[0];
         self.rx_buffer.replace(buffer);
-        self.rx_in_progress.get().map(|appid| {
-            self.rx_in_progress.set(None);
+        self.rx_in_progress.set(false);
 
-            self.apps
-                .enter(appid, |app, _| {
-                    app.read_callback.map(|mut cb| {
-                        let (result, len) = match error {
-                            uart::Error::CommandComplete => {
-                                // Copy the data into the application buffer, if it exists
-                                match app.read_buffer.take() {
-                                    Some(mut app_buffer) => {
-                                        // We used UART::receive(),
-                                        // so we received the requested length
-                                        self.rx_buffer.map(|buffer| {
-                                            // Copy our driver's buffer into the app's buffer
-                                            for (i, c) in app_buffer.as_mut()[0..rx_len]
-                                                .iter_mut()
-                                                .enumerate()
-                                            {
-                                                *c = buffer[i]
-                                            }
-                                        });
-                                        (ReturnCode::SUCCESS, rx_len)
-                                    }
-                                    None => (ReturnCode::EINVAL, 0),
-                                }
-                            }
-                            _ => {
-                                // Some UART error occurred
-                                (ReturnCode::FAIL, 0)
-                            }
-                        };
-
-                        // Schedule the app's callback
-                        cb.schedule(From::from(result), len, 0);
-                    });
+        match error {
+            uart::Error::CommandComplete => {
+                if rx_len == 1 {
+                    self.receive_byte(byte);
+                }
+            }
+            _ => {
+                // Some UART error occurred, fail the read it was for
+                self.reader().map(|appid| {
+                    self.apps
+                        .enter(appid, |app, _| self.complete_read(app, ReturnCode::FAIL, false))
+                        .unwrap_or_default();
+                });
+            }
+        }
 
-                    // If the enter() above fails because the app has disappeared,
-                    // we simply drop the received data.
-                })
-                .unwrap_or_default();
-        });
+        self.receive_next();
     }
 }
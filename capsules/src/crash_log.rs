@@ -0,0 +1,167 @@
+//! Reports and clears the crash records the kernel keeps across reboots.
+//!
+//! On boot, `report()` prints any records left by earlier panics on the
+//! debug console. Applications can also read the records and clear them.
+//!
+//! Records are copied into the application's buffer in the layout of
+//! `kernel::crash_log::CrashRecord`, all words little endian:
+//!
+//! ```text
+//!   0: magic           4: sequence        8: pc             12: lr
+//!  16: cfsr           20: hfsr           24: mmfar          28: bfar
+//!  32: line           36: app index      40: app name [16]  56: file [32]
+//!  88: version [16]  104: checksum
+//! ```
+//!
+//! Setup
+//! -----
+//!
+//! ```rust
+//! let crash_log = static_init!(
+//!     capsules::crash_log::CrashLog,
+//!     capsules::crash_log::CrashLog::new(kernel::Grant::create())
+//! );
+//! crash_log.report();
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```c
+//! allow(CRASH_LOG_DRIVER, 0, record, 108);
+//! int count = command(CRASH_LOG_DRIVER, 1, 0, 0);
+//! command(CRASH_LOG_DRIVER, 2, 0, 0);  // read the oldest record
+//! command(CRASH_LOG_DRIVER, 3, 0, 0);  // clear all records
+//! ```
+
+use kernel::crash_log;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x10003;
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct CrashLog {
+    apps: Grant<App>,
+}
+
+impl CrashLog {
+    pub fn new(apps: Grant<App>) -> CrashLog {
+        CrashLog { apps: apps }
+    }
+
+    /// Print the stored crash records on the debug console, oldest first.
+    /// The records are kept until cleared.
+    pub fn report(&self) {
+        let count = crash_log::count();
+        if count == 0 {
+            return;
+        }
+
+        debug!("{} crash record(s) from earlier boots:", count);
+        for n in 0..count {
+            crash_log::get(n).map(|record| {
+                debug!(
+                    "#{}: kernel {} panicked at {}:{}",
+                    record.sequence,
+                    record.version(),
+                    record.file(),
+                    record.line
+                );
+                if record.app_index != crash_log::NO_APP {
+                    debug!(
+                        "    app {} ({}) faulted",
+                        record.app_index,
+                        record.app_name()
+                    );
+                }
+                debug!(
+                    "    pc {:#010x} lr {:#010x} cfsr {:#010x} hfsr {:#010x} \
+                     mmfar {:#010x} bfar {:#010x}",
+                    record.pc,
+                    record.lr,
+                    record.cfsr,
+                    record.hfsr,
+                    record.mmfar,
+                    record.bfar
+                );
+            });
+        }
+    }
+}
+
+impl Driver for CrashLog {
+    /// Share a buffer receiving crash records.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The buffer filled by command `2`. It must hold at least
+    ///        `kernel::crash_log::RECORD_LEN` bytes.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(&self, _: usize, _: Option<Callback>, _: AppId) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Read or clear crash records.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Return the number of stored records.
+    /// - `2`: Copy record `data`, counting from the oldest, into the buffer
+    ///        and return its length. Returns `EINVAL` if there is no such
+    ///        record and `ESIZE` if the buffer is too short.
+    /// - `3`: Erase all records.
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: crash_log::count(),
+            },
+            2 => self.apps
+                .enter(appid, |app, _| {
+                    let record = match crash_log::get(data) {
+                        Some(record) => record,
+                        None => return ReturnCode::EINVAL,
+                    };
+                    match app.buffer {
+                        Some(ref mut buffer) => {
+                            let bytes = record.as_bytes();
+                            if buffer.len() < bytes.len() {
+                                return ReturnCode::ESIZE;
+                            }
+                            buffer.as_mut()[..bytes.len()].copy_from_slice(bytes);
+                            ReturnCode::SuccessWithValue { value: bytes.len() }
+                        }
+                        None => ReturnCode::EINVAL,
+                    }
+                })
+                .unwrap_or_else(|err| err.into()),
+            3 => {
+                crash_log::clear();
+                ReturnCode::SUCCESS
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
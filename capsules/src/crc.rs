@@ -17,8 +17,10 @@
 //!
 //! ## CRC Algorithms
 //!
-//! The capsule supports two general purpose CRC algorithms, as well as a few
-//! hardware specific algorithms implemented on the Atmel SAM4L.
+//! The capsule supports four general purpose CRC algorithms, as well as a few
+//! hardware specific algorithms implemented on the Atmel SAM4L. Not every CRC
+//! unit supports every algorithm; unsupported requests complete with
+//! `ENOSUPPORT`.
 //!
 //! In the values used to identify polynomials below, more-significant bits
 //! correspond to higher-order terms, and the most significant bit is omitted
@@ -64,6 +66,25 @@
 //! This algorithm uses the same polynomial as `CRC-32C`, but does no post-
 //! processing on the output value.  It can be performed purely in hardware on
 //! the SAM4L.
+//!
+//! ### CRC-16-CCITT
+//!
+//! __Polynomial__: `0x1021`
+//!
+//! Starts from `0xFFFF`, consumes each input byte from most-significant bit
+//! to least-significant and does no post-processing on the output value.
+//!
+//! ### CRC-8
+//!
+//! __Polynomial__: `0x07`
+//!
+//! Starts from `0x00`, consumes each input byte from most-significant bit to
+//! least-significant and does no post-processing on the output value.
+//!
+//! ## Chips without a CRC unit
+//!
+//! `capsules::software_crc::SoftwareCrc` implements every algorithm above in
+//! software and can be used in place of a hardware unit on any board.
 
 use core::cell::Cell;
 use kernel::hil;
@@ -281,6 +302,14 @@ impl<'a, C: hil::crc::CRC> Driver for Crc<'a, C> {
     ///   * `4: SAM4L-32C`  This algorithm uses the same polynomial as
     ///   `CRC-32C`, but does no post-processing on the output value.  It
     ///   can be performed purely in hardware on the SAM4L.
+    ///
+    ///   * `5: CRC-16-CCITT`  This algorithm uses polynomial 0x1021 with
+    ///   initial value 0xFFFF, consumes input from most-significant bit
+    ///   first and does no post-processing on the output value.
+    ///
+    ///   * `6: CRC-8`  This algorithm uses polynomial 0x07 with initial
+    ///   value 0x00, consumes input from most-significant bit first and
+    ///   does no post-processing on the output value.
     fn command(&self, command_num: usize, algorithm: usize, _: usize, appid: AppId) -> ReturnCode {
         match command_num {
             // This driver is present
@@ -346,6 +375,8 @@ fn alg_from_user_int(i: usize) -> Option<hil::crc::CrcAlg> {
         2 => Some(CrcAlg::Sam4L16),
         3 => Some(CrcAlg::Sam4L32),
         4 => Some(CrcAlg::Sam4L32C),
+        5 => Some(CrcAlg::Crc16CCITT),
+        6 => Some(CrcAlg::Crc8),
         _ => None,
     }
 }
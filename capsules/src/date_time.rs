@@ -0,0 +1,188 @@
+//! Provides userspace with wall-clock date and time.
+//!
+//! Processes can read the time, for example to timestamp samples. Only the
+//! processes the board names, by package name, may set it, for example one
+//! that gets the time from the network or the console. Reads copy the time
+//! into a buffer shared by the process, so that the seconds and the calendar
+//! fields agree. The buffer is laid out as follows, all little endian:
+//!
+//! ```text
+//!  0: seconds since the Unix epoch (u64)
+//!  8: year (u16)
+//! 10: month, 1 to 12    11: day, 1 to 31
+//! 12: hour              13: minute
+//! 14: second            15: day of the week, 0 for Sunday
+//! ```
+//!
+//! Setup
+//! -----
+//!
+//! ```rust
+//! let date_time_setters = static_init!([&'static str; 1], ["clock_sync"]);
+//! let date_time = static_init!(
+//!     capsules::date_time::DateTimeDriver<
+//!         'static,
+//!         SoftwareDateTime<'static, VirtualMuxAlarm<'static, Rtc>>,
+//!     >,
+//!     capsules::date_time::DateTimeDriver::new(
+//!         software_date_time,
+//!         date_time_setters,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```c
+//! allow(DATE_TIME_DRIVER, 0, buffer, 16);
+//! command(DATE_TIME_DRIVER, 1, 0, 0);  // read the time into the buffer
+//! command(DATE_TIME_DRIVER, 2, seconds & 0xffffffff, seconds >> 32);
+//! command(DATE_TIME_DRIVER, 3, year << 16 | month << 8 | day,
+//!         hour << 16 | minute << 8 | second);
+//! ```
+
+use kernel::hil::date_time::{self, Date, DateTime};
+use kernel::procs;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x00007;
+
+/// Length of the time copied into the buffer.
+pub const TIME_LEN: usize = 16;
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct DateTimeDriver<'a, D: DateTime + 'a> {
+    date_time: &'a D,
+    /// Package names of the processes that may set the time
+    setters: &'a [&'a str],
+    apps: Grant<App>,
+}
+
+impl<'a, D: DateTime> DateTimeDriver<'a, D> {
+    pub fn new(
+        date_time: &'a D,
+        setters: &'a [&'a str],
+        apps: Grant<App>,
+    ) -> DateTimeDriver<'a, D> {
+        DateTimeDriver {
+            date_time: date_time,
+            setters: setters,
+            apps: apps,
+        }
+    }
+
+    fn may_set(&self, appid: AppId) -> bool {
+        procs::process_name(appid.idx())
+            .map_or(false, |name| self.setters.iter().any(|&setter| setter == name))
+    }
+
+    fn copy_time(&self, buffer: &mut [u8]) -> ReturnCode {
+        let seconds = match self.date_time.get_seconds() {
+            Some(seconds) => seconds,
+            None => return ReturnCode::EOFF,
+        };
+        if buffer.len() < TIME_LEN {
+            return ReturnCode::ESIZE;
+        }
+
+        let date = date_time::date_from_seconds(seconds);
+        for i in 0..8 {
+            buffer[i] = (seconds >> (8 * i)) as u8;
+        }
+        buffer[8] = date.year as u8;
+        buffer[9] = (date.year >> 8) as u8;
+        buffer[10] = date.month;
+        buffer[11] = date.day;
+        buffer[12] = date.hour;
+        buffer[13] = date.minute;
+        buffer[14] = date.second;
+        buffer[15] = date.day_of_week;
+        ReturnCode::SuccessWithValue { value: TIME_LEN }
+    }
+}
+
+impl<'a, D: DateTime> Driver for DateTimeDriver<'a, D> {
+    /// Share a buffer receiving the time.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The buffer filled by command `1`. It must hold at least
+    ///        `TIME_LEN` bytes.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(&self, _: usize, _: Option<Callback>, _: AppId) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Read or set the time.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Copy the current time into the buffer and return its length.
+    ///        Returns `EOFF` if the time has not been set and `ESIZE` if the
+    ///        buffer is too short.
+    /// - `2`: Set the time to `data + (arg2 << 32)` seconds since the Unix
+    ///        epoch. Returns `EINVAL` for times past the year 9999.
+    /// - `3`: Set the date to year `data >> 16`, month `(data >> 8) & 0xff`
+    ///        and day `data & 0xff`, and the time of day to hour `arg2 >> 16`,
+    ///        minute `(arg2 >> 8) & 0xff` and second `arg2 & 0xff`, in UTC.
+    ///        Returns `EINVAL` if this is not a valid date between 1970 and
+    ///        9999.
+    ///
+    /// Commands `2` and `3` return `EPERM` if the board does not allow the
+    /// process to set the time.
+    fn command(&self, command_num: usize, data: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        if (command_num == 2 || command_num == 3) && !self.may_set(appid) {
+            return ReturnCode::EPERM;
+        }
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self.apps
+                .enter(appid, |app, _| match app.buffer {
+                    Some(ref mut buffer) => self.copy_time(buffer.as_mut()),
+                    None => ReturnCode::EINVAL,
+                })
+                .unwrap_or_else(|err| err.into()),
+            2 => {
+                let seconds = (data as u32) as u64 | ((arg2 as u32) as u64) << 32;
+                if seconds > date_time::MAX_SECONDS {
+                    return ReturnCode::EINVAL;
+                }
+                self.date_time.set_seconds(seconds)
+            }
+            3 => self.date_time.set_date(Date {
+                year: (data >> 16) as u16,
+                month: (data >> 8) as u8,
+                day: data as u8,
+                hour: (arg2 >> 16) as u8,
+                minute: (arg2 >> 8) as u8,
+                second: arg2 as u8,
+                day_of_week: 0,
+            }),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,516 @@
+//! Provides userspace with debounced button events.
+//!
+//! This is a drop-in replacement for [`button`](../button/index.html) that
+//! filters contact bounce in the kernel and recognizes long presses and
+//! double clicks, so that processes do not each need their own timers. All
+//! buttons share one alarm, usually a `VirtualMuxAlarm`.
+//!
+//! After an edge, a button must stay in its new state for the debounce time
+//! before a press or release is reported. A press held for the long-press
+//! time is also reported as a long press. A second short press that starts
+//! within the double-click time of the first one's release is also reported
+//! as a double click. The times are set per button by the board, and a time
+//! of zero disables that event.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let button_pins = static_init!(
+//!     [capsules::debounced_button::ButtonPin<'static, sam4l::gpio::GPIOPin>; 1],
+//!     [capsules::debounced_button::ButtonPin::new(
+//!         &sam4l::gpio::PA[16],
+//!         capsules::button::GpioMode::LowWhenPressed
+//!     )]
+//! );
+//! button_pins[0].set_long_press_ms(2000);
+//! let button_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let button = static_init!(
+//!     capsules::debounced_button::DebouncedButton<
+//!         'static,
+//!         sam4l::gpio::GPIOPin,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::debounced_button::DebouncedButton::new(
+//!         button_pins,
+//!         button_alarm,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! for btn in button_pins.iter() {
+//!     btn.pin().set_client(button);
+//! }
+//! button_alarm.set_client(button);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! The interface is that of `button`, with the addition of command `4`.
+//!
+//! ### Command
+//!
+//! #### `command_num`
+//!
+//! - `0`: Driver check and get number of buttons on the board.
+//! - `1`: Enable events for a given button.
+//! - `2`: Disable events for a button.
+//! - `3`: Read the current, debounced, state of the button.
+//! - `4`: Choose which events are delivered, as a bit mask of `ButtonEvent`
+//!   values. Press and release events are delivered by default.
+//!
+//! ### Subscribe
+//!
+//! #### `subscribe_num`
+//!
+//! - `0`: Set callback for button events. The callback is called with the
+//!   index of the button, the pressed (1) or not pressed (0) state of the
+//!   button, and the `ButtonEvent`.
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::hil::gpio::{self, InterruptMode};
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+pub use button::{ButtonState, GpioMode, SubscribeMap};
+
+/// Syscall driver number, the same as the undebounced button driver.
+pub const DRIVER_NUM: usize = ::button::DRIVER_NUM;
+
+pub const DEFAULT_DEBOUNCE_MS: u32 = 20;
+pub const DEFAULT_LONG_PRESS_MS: u32 = 1000;
+pub const DEFAULT_DOUBLE_CLICK_MS: u32 = 300;
+
+/// Events passed to userspace as the third callback argument.
+#[derive(Clone, Copy)]
+pub enum ButtonEvent {
+    Press = 0,
+    Release = 1,
+    LongPress = 2,
+    DoubleClick = 3,
+}
+
+const ALL_EVENTS: usize = 0xf;
+const DEFAULT_EVENTS: usize = 1 << ButtonEvent::Press as usize | 1 << ButtonEvent::Release as usize;
+
+pub struct App {
+    callback: Option<Callback>,
+    subscribed: SubscribeMap,
+    /// Bit mask of the events to deliver
+    events: usize,
+}
+
+impl Default for App {
+    fn default() -> App {
+        App {
+            callback: None,
+            subscribed: 0,
+            events: DEFAULT_EVENTS,
+        }
+    }
+}
+
+/// A button and its debouncing state.
+pub struct ButtonPin<'a, G: gpio::Pin + 'a> {
+    pin: &'a G,
+    mode: GpioMode,
+    debounce_ms: Cell<u32>,
+    long_press_ms: Cell<u32>,
+    double_click_ms: Cell<u32>,
+    enabled: Cell<bool>,
+    /// The debounced state
+    state: Cell<ButtonState>,
+    /// Time of the last edge, while waiting for the pin to settle
+    settle: Cell<Option<u32>>,
+    /// Time of the last press or release, while waiting for a long press or
+    /// the end of the double-click time
+    hold: Cell<Option<u32>>,
+    long_press: Cell<bool>,
+    /// Released after a short press, within the double-click time
+    first_click: Cell<bool>,
+    /// Pressed again within the double-click time
+    second_click: Cell<bool>,
+}
+
+impl<'a, G: gpio::Pin> ButtonPin<'a, G> {
+    pub fn new(pin: &'a G, mode: GpioMode) -> ButtonPin<'a, G> {
+        ButtonPin {
+            pin: pin,
+            mode: mode,
+            debounce_ms: Cell::new(DEFAULT_DEBOUNCE_MS),
+            long_press_ms: Cell::new(DEFAULT_LONG_PRESS_MS),
+            double_click_ms: Cell::new(DEFAULT_DOUBLE_CLICK_MS),
+            enabled: Cell::new(false),
+            state: Cell::new(ButtonState::NotPressed),
+            settle: Cell::new(None),
+            hold: Cell::new(None),
+            long_press: Cell::new(false),
+            first_click: Cell::new(false),
+            second_click: Cell::new(false),
+        }
+    }
+
+    pub fn pin(&self) -> &'a G {
+        self.pin
+    }
+
+    /// How long the button must stay pressed or released to be reported.
+    pub fn set_debounce_ms(&self, ms: u32) {
+        self.debounce_ms.set(ms);
+    }
+
+    /// How long the button must be held for a long press, or 0 for none.
+    pub fn set_long_press_ms(&self, ms: u32) {
+        self.long_press_ms.set(ms);
+    }
+
+    /// The most time between the release of a short press and the next
+    /// press for a double click, or 0 for none.
+    pub fn set_double_click_ms(&self, ms: u32) {
+        self.double_click_ms.set(ms);
+    }
+
+    fn read(&self) -> ButtonState {
+        match (self.mode, self.pin.read()) {
+            (GpioMode::LowWhenPressed, false) | (GpioMode::HighWhenPressed, true) => {
+                ButtonState::Pressed
+            }
+            _ => ButtonState::NotPressed,
+        }
+    }
+
+    fn hold_ms(&self) -> u32 {
+        match self.state.get() {
+            ButtonState::Pressed => self.long_press_ms.get(),
+            ButtonState::NotPressed => self.double_click_ms.get(),
+        }
+    }
+
+    /// Start waiting for a long press or a second click, if enabled.
+    fn start_hold(&self, now: u32) {
+        self.hold
+            .set(if self.hold_ms() > 0 { Some(now) } else { None });
+    }
+}
+
+pub struct DebouncedButton<'a, G: gpio::Pin + 'a, A: Alarm + 'a> {
+    pins: &'a [ButtonPin<'a, G>],
+    alarm: &'a A,
+    apps: Grant<App>,
+}
+
+impl<'a, G: gpio::Pin + gpio::PinCtl, A: Alarm> DebouncedButton<'a, G, A> {
+    pub fn new(
+        pins: &'a [ButtonPin<'a, G>],
+        alarm: &'a A,
+        grant: Grant<App>,
+    ) -> DebouncedButton<'a, G, A> {
+        for button in pins.iter() {
+            button.pin.make_input();
+        }
+
+        DebouncedButton {
+            pins: pins,
+            alarm: alarm,
+            apps: grant,
+        }
+    }
+
+    fn tics(&self, ms: u32) -> u32 {
+        (ms as u64 * A::Frequency::frequency() as u64 / 1000) as u32
+    }
+
+    /// The (reference, dt) pairs of a button's pending timeouts.
+    fn timeouts(&self, button: &ButtonPin<'a, G>) -> [Option<(u32, u32)>; 2] {
+        [
+            button
+                .settle
+                .get()
+                .map(|reference| (reference, self.tics(button.debounce_ms.get()))),
+            button
+                .hold
+                .get()
+                .map(|reference| (reference, self.tics(button.hold_ms()))),
+        ]
+    }
+
+    /// Set the alarm for the earliest timeout of any button.
+    fn set_next_alarm(&self) {
+        let now = self.alarm.now();
+        let mut next: Option<u32> = None;
+        for button in self.pins.iter() {
+            for &(reference, dt) in self.timeouts(button).iter().flat_map(|t| t) {
+                let remaining = if self.alarm.has_expired(reference, dt, now) {
+                    0
+                } else {
+                    dt - self.alarm.elapsed(reference, now)
+                };
+                next = Some(next.map_or(remaining, |next| cmp::min(next, remaining)));
+            }
+        }
+
+        match next {
+            Some(dt) => self.alarm.set_alarm_from(now, dt),
+            None => self.alarm.disable(),
+        }
+    }
+
+    fn enable(&self, pin_num: usize) {
+        let button = &self.pins[pin_num];
+        if !button.enabled.get() {
+            button.enabled.set(true);
+            button.state.set(button.read());
+            button.pin.enable_interrupt(pin_num, InterruptMode::EitherEdge);
+        }
+    }
+
+    fn disable(&self, pin_num: usize) {
+        let button = &self.pins[pin_num];
+        button.pin.disable_interrupt();
+        button.enabled.set(false);
+        button.settle.set(None);
+        button.hold.set(None);
+        button.long_press.set(false);
+        button.first_click.set(false);
+        button.second_click.set(false);
+    }
+
+    /// Whether any process is listening to a button.
+    fn has_listeners(&self, pin_num: usize) -> bool {
+        let listeners = Cell::new(0);
+        self.apps.each(|app| {
+            app.callback.map(|_| {
+                if app.subscribed & (1 << pin_num) != 0 {
+                    listeners.set(listeners.get() + 1);
+                }
+            });
+        });
+        listeners.get() > 0
+    }
+
+    fn notify(&self, pin_num: usize, event: ButtonEvent) {
+        let state = match event {
+            ButtonEvent::Press | ButtonEvent::LongPress => ButtonState::Pressed,
+            ButtonEvent::Release | ButtonEvent::DoubleClick => ButtonState::NotPressed,
+        };
+
+        self.apps.each(|app| {
+            if app.subscribed & (1 << pin_num) != 0 && app.events & (1 << event as usize) != 0 {
+                app.callback.map(|mut callback| {
+                    callback.schedule(pin_num, state as usize, event as usize);
+                });
+            }
+        });
+    }
+
+    /// Handle a debounced press or release.
+    fn changed(&self, pin_num: usize, now: u32) {
+        let button = &self.pins[pin_num];
+        match button.state.get() {
+            ButtonState::NotPressed => {
+                button.state.set(ButtonState::Pressed);
+                button.long_press.set(false);
+                button.second_click.set(button.first_click.get());
+                button.first_click.set(false);
+                button.start_hold(now);
+                self.notify(pin_num, ButtonEvent::Press);
+            }
+            ButtonState::Pressed => {
+                button.state.set(ButtonState::NotPressed);
+                button.hold.set(None);
+                self.notify(pin_num, ButtonEvent::Release);
+                if button.long_press.get() {
+                    button.second_click.set(false);
+                } else if button.second_click.get() {
+                    button.second_click.set(false);
+                    self.notify(pin_num, ButtonEvent::DoubleClick);
+                } else {
+                    button.start_hold(now);
+                    button.first_click.set(button.hold.get().is_some());
+                }
+            }
+        }
+    }
+}
+
+impl<'a, G: gpio::Pin + gpio::PinCtl, A: Alarm> Driver for DebouncedButton<'a, G, A> {
+    /// Set callbacks.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Set callback for button events. The callback is called with
+    ///   the index of the button, its pressed/not pressed state and the
+    ///   `ButtonEvent`.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self.apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+
+            // default
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Configure events and read state for buttons.
+    ///
+    /// `data` is the index of the button in the button array as passed to
+    /// `DebouncedButton::new()`.
+    ///
+    /// Commands `1` to `3` return `EINVAL` if an invalid button number is
+    /// passed in.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check and get number of buttons on the board.
+    /// - `1`: Enable events for a given button.
+    /// - `2`: Disable events for a button.
+    /// - `3`: Read the current, debounced, state of the button.
+    /// - `4`: Set the mask of events to deliver to `data`. Returns `EINVAL`
+    ///   for unknown events.
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        let pins = self.pins;
+        match command_num {
+            // return button count
+            0 => ReturnCode::SuccessWithValue {
+                value: pins.len() as usize,
+            },
+
+            // enable events for a button
+            1 => {
+                if data < pins.len() {
+                    self.apps
+                        .enter(appid, |app, _| {
+                            app.subscribed |= 1 << data;
+                            self.enable(data);
+                            ReturnCode::SUCCESS
+                        })
+                        .unwrap_or_else(|err| err.into())
+                } else {
+                    ReturnCode::EINVAL /* impossible button */
+                }
+            }
+
+            // disable events for a button
+            2 => {
+                if data >= pins.len() {
+                    ReturnCode::EINVAL /* impossible button */
+                } else {
+                    let res = self.apps
+                        .enter(appid, |app, _| {
+                            app.subscribed &= !(1 << data);
+                            ReturnCode::SUCCESS
+                        })
+                        .unwrap_or_else(|err| err.into());
+
+                    if !self.has_listeners(data) {
+                        self.disable(data);
+                        self.set_next_alarm();
+                    }
+
+                    res
+                }
+            }
+
+            // read input
+            3 => {
+                if data >= pins.len() {
+                    ReturnCode::EINVAL /* impossible button */
+                } else {
+                    let button = &pins[data];
+                    let button_state = if button.enabled.get() {
+                        button.state.get()
+                    } else {
+                        button.read()
+                    };
+                    ReturnCode::SuccessWithValue {
+                        value: button_state as usize,
+                    }
+                }
+            }
+
+            // choose events
+            4 => {
+                if data & !ALL_EVENTS != 0 {
+                    ReturnCode::EINVAL
+                } else {
+                    self.apps
+                        .enter(appid, |app, _| {
+                            app.events = data;
+                            ReturnCode::SUCCESS
+                        })
+                        .unwrap_or_else(|err| err.into())
+                }
+            }
+
+            // default
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a, G: gpio::Pin + gpio::PinCtl, A: Alarm> gpio::Client for DebouncedButton<'a, G, A> {
+    fn fired(&self, pin_num: usize) {
+        if pin_num >= self.pins.len() {
+            return;
+        }
+
+        // It's possible we got an interrupt for a process that has since died
+        // (and didn't unregister the interrupt). Lazily disable interrupts for
+        // this button if so.
+        if !self.has_listeners(pin_num) {
+            self.disable(pin_num);
+        } else {
+            // Every edge restarts the time the pin must settle
+            self.pins[pin_num].settle.set(Some(self.alarm.now()));
+        }
+        self.set_next_alarm();
+    }
+}
+
+impl<'a, G: gpio::Pin + gpio::PinCtl, A: Alarm> time::Client for DebouncedButton<'a, G, A> {
+    fn fired(&self) {
+        let now = self.alarm.now();
+        for (pin_num, button) in self.pins.iter().enumerate() {
+            let [settle, hold] = self.timeouts(button);
+
+            if let Some((reference, dt)) = settle {
+                if self.alarm.has_expired(reference, dt, now) {
+                    button.settle.set(None);
+                    if button.read() != button.state.get() {
+                        self.changed(pin_num, now);
+                        // The pending timeout was for the previous state
+                        continue;
+                    }
+                }
+            }
+
+            if let Some((reference, dt)) = hold {
+                if self.alarm.has_expired(reference, dt, now) {
+                    button.hold.set(None);
+                    match button.state.get() {
+                        ButtonState::Pressed => {
+                            button.long_press.set(true);
+                            self.notify(pin_num, ButtonEvent::LongPress);
+                        }
+                        ButtonState::NotPressed => button.first_click.set(false),
+                    }
+                }
+            }
+        }
+
+        self.set_next_alarm();
+    }
+}
@@ -0,0 +1,337 @@
+//! Provides userspace with access to SHA-256 and HMAC-SHA256.
+//!
+//! Requests from any number of applications are queued and served one at a
+//! time. The message is copied from the application's buffer into a small
+//! kernel buffer a chunk at a time, so messages may be of any length.
+//!
+//! Setup
+//! -----
+//!
+//! ```rust
+//! let digest = static_init!(
+//!     capsules::digest::DigestDriver<'static, Engine>,
+//!     capsules::digest::DigestDriver::new(
+//!         engine,
+//!         &mut capsules::digest::DATA_BUF,
+//!         &mut capsules::digest::DEST_BUF,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! hil::digest::Digest::set_client(engine, digest);
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```c
+//! allow(DIGEST_DRIVER, 0, key, key_len);     // HMAC only
+//! allow(DIGEST_DRIVER, 1, message, message_len);
+//! allow(DIGEST_DRIVER, 2, digest, 32);
+//! subscribe(DIGEST_DRIVER, 0, callback, NULL);
+//! command(DIGEST_DRIVER, 2, message_len, 0);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::TakeCell;
+use kernel::hil::digest::{self, Digest, HMACSha256, Sha256, SHA256_DIGEST_LEN};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x40003;
+
+/// Kernel buffer the message is copied through, one chunk at a time.
+pub static mut DATA_BUF: [u8; 64] = [0; 64];
+
+/// Kernel buffer receiving the digest.
+pub static mut DEST_BUF: [u8; SHA256_DIGEST_LEN] = [0; SHA256_DIGEST_LEN];
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Algorithm {
+    Sha256,
+    HmacSha256,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    key: Option<AppSlice<Shared, u8>>,
+    data: Option<AppSlice<Shared, u8>>,
+    dest: Option<AppSlice<Shared, u8>>,
+
+    // if Some, the application is waiting for a digest of the first `len`
+    // bytes of `data` using the given algorithm
+    waiting: Option<(Algorithm, usize)>,
+}
+
+pub struct DigestDriver<'a, D: Digest<'a> + Sha256 + HMACSha256 + 'a> {
+    engine: &'a D,
+    data_buf: TakeCell<'static, [u8]>,
+    dest_buf: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+    serving_app: Cell<Option<AppId>>,
+    // Bytes of the message added so far, and its total length
+    offset: Cell<usize>,
+    len: Cell<usize>,
+}
+
+impl<'a, D: Digest<'a> + Sha256 + HMACSha256> DigestDriver<'a, D> {
+    pub fn new(
+        engine: &'a D,
+        data_buf: &'static mut [u8],
+        dest_buf: &'static mut [u8],
+        apps: Grant<App>,
+    ) -> DigestDriver<'a, D> {
+        DigestDriver {
+            engine: engine,
+            data_buf: TakeCell::new(data_buf),
+            dest_buf: TakeCell::new(dest_buf),
+            apps: apps,
+            serving_app: Cell::new(None),
+            offset: Cell::new(0),
+            len: Cell::new(0),
+        }
+    }
+
+    fn serve_waiting_apps(&self) {
+        if self.serving_app.get().is_some() {
+            // A computation is in progress
+            return;
+        }
+
+        for app in self.apps.iter() {
+            let started = app.enter(|app, _| {
+                if let Some((alg, len)) = app.waiting {
+                    let res = self.start(app, alg, len);
+                    if res == ReturnCode::SUCCESS {
+                        self.serving_app.set(Some(app.appid()));
+                        true
+                    } else {
+                        // The app's request failed
+                        app.waiting = None;
+                        if let Some(mut callback) = app.callback {
+                            callback.schedule(From::from(res), 0, 0);
+                        }
+                        false
+                    }
+                } else {
+                    false
+                }
+            });
+            if started {
+                break;
+            }
+        }
+    }
+
+    fn start(&self, app: &mut App, alg: Algorithm, len: usize) -> ReturnCode {
+        let data_len = app.data.as_ref().map_or(0, |data| data.len());
+        let len = if len == 0 { data_len } else { len };
+        if len > data_len || app.dest.as_ref().map_or(0, |dest| dest.len()) < SHA256_DIGEST_LEN
+        {
+            return ReturnCode::EINVAL;
+        }
+
+        let res = match alg {
+            Algorithm::Sha256 => self.engine.set_mode_sha256(),
+            Algorithm::HmacSha256 => match app.key {
+                Some(ref key) => self.engine.set_mode_hmacsha256(key.as_ref()),
+                None => ReturnCode::EINVAL,
+            },
+        };
+        if res != ReturnCode::SUCCESS {
+            return res;
+        }
+        self.engine.clear_data();
+
+        self.offset.set(0);
+        self.len.set(len);
+        self.add_next_chunk(app)
+    }
+
+    /// Feed the next chunk of the message to the engine, or finish the
+    /// message if all of it has been added.
+    fn add_next_chunk(&self, app: &mut App) -> ReturnCode {
+        let offset = self.offset.get();
+        let remaining = self.len.get() - offset;
+
+        if remaining == 0 {
+            return self.dest_buf.take().map_or(ReturnCode::EBUSY, |dest_buf| {
+                match self.engine.run(dest_buf) {
+                    (ReturnCode::SUCCESS, _) => ReturnCode::SUCCESS,
+                    (res, dest_buf) => {
+                        dest_buf.map(|buf| self.dest_buf.replace(buf));
+                        res
+                    }
+                }
+            });
+        }
+
+        let data = match app.data {
+            Some(ref data) => data,
+            None => return ReturnCode::EINVAL,
+        };
+        self.data_buf.take().map_or(ReturnCode::EBUSY, |data_buf| {
+            let n = cmp::min(remaining, data_buf.len());
+            if data.len() < offset + n {
+                // The app shared a shorter message since the request started
+                self.data_buf.replace(data_buf);
+                return ReturnCode::ESIZE;
+            }
+            data_buf[..n].copy_from_slice(&data.as_ref()[offset..offset + n]);
+            match self.engine.add_data(data_buf, n) {
+                (ReturnCode::SUCCESS, _) => {
+                    self.offset.set(offset + n);
+                    ReturnCode::SUCCESS
+                }
+                (res, data_buf) => {
+                    data_buf.map(|buf| self.data_buf.replace(buf));
+                    res
+                }
+            }
+        })
+    }
+
+    /// Report the outcome of the request being served, copying the digest
+    /// into the app's buffer on success, and move on to the next request.
+    fn finish(&self, res: ReturnCode) {
+        if let Some(appid) = self.serving_app.take() {
+            let _ = self.apps.enter(appid, |app, _| {
+                let dest_len = app.dest.as_ref().map_or(0, |dest| dest.len());
+                let res = if res == ReturnCode::SUCCESS && dest_len < SHA256_DIGEST_LEN {
+                    // The app shared a shorter buffer since the request started
+                    ReturnCode::ESIZE
+                } else {
+                    res
+                };
+                if res == ReturnCode::SUCCESS {
+                    self.dest_buf.map(|dest_buf| {
+                        app.dest.as_mut().map(|dest| {
+                            dest.as_mut()[..SHA256_DIGEST_LEN]
+                                .copy_from_slice(&dest_buf[..SHA256_DIGEST_LEN]);
+                        });
+                    });
+                }
+                app.waiting = None;
+                if let Some(mut callback) = app.callback {
+                    callback.schedule(From::from(res), 0, 0);
+                }
+            });
+        }
+        self.serve_waiting_apps();
+    }
+}
+
+impl<'a, D: Digest<'a> + Sha256 + HMACSha256> Driver for DigestDriver<'a, D> {
+    /// Share buffers with the driver.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The HMAC key.
+    /// - `1`: The message to compute a digest of.
+    /// - `2`: A buffer of at least 32 bytes receiving the digest.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                match allow_num {
+                    0 => app.key = slice,
+                    1 => app.data = slice,
+                    2 => app.dest = slice,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Subscribe to request completions.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Called with the status of the request once the digest has
+    ///        been written to the buffer shared with `allow_num` 2.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Queue a request.
+    ///
+    /// Each application may have only one request outstanding; further
+    /// requests return `EBUSY` until its callback has been scheduled.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Compute the SHA-256 digest of the first `data` bytes of the
+    ///        message, or all of it if `data` is zero.
+    /// - `2`: Compute the HMAC-SHA256 tag of the message, with the same
+    ///        arguments as `1`, using the shared key.
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        let alg = match command_num {
+            0 => return ReturnCode::SUCCESS,
+            1 => Algorithm::Sha256,
+            2 => Algorithm::HmacSha256,
+            _ => return ReturnCode::ENOSUPPORT,
+        };
+
+        let res = self.apps
+            .enter(appid, |app, _| {
+                if app.waiting.is_some() {
+                    // Each app may make only one request at a time
+                    ReturnCode::EBUSY
+                } else if app.callback.is_none() {
+                    ReturnCode::EINVAL
+                } else {
+                    app.waiting = Some((alg, data));
+                    ReturnCode::SUCCESS
+                }
+            })
+            .unwrap_or_else(|err| err.into());
+        if res == ReturnCode::SUCCESS {
+            self.serve_waiting_apps();
+        }
+        res
+    }
+}
+
+impl<'a, D: Digest<'a> + Sha256 + HMACSha256> digest::Client for DigestDriver<'a, D> {
+    fn add_data_done(&self, result: ReturnCode, data: &'static mut [u8]) {
+        self.data_buf.replace(data);
+
+        let res = if result != ReturnCode::SUCCESS {
+            result
+        } else {
+            self.serving_app.get().map_or(ReturnCode::FAIL, |appid| {
+                self.apps
+                    .enter(appid, |app, _| self.add_next_chunk(app))
+                    .unwrap_or_else(|err| err.into())
+            })
+        };
+        if res != ReturnCode::SUCCESS {
+            self.finish(res);
+        }
+    }
+
+    fn hash_done(&self, result: ReturnCode, digest: &'static mut [u8]) {
+        self.dest_buf.replace(digest);
+        self.finish(result);
+    }
+}
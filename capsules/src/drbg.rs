@@ -0,0 +1,381 @@
+//! Kernel entropy pool based on a deterministic random bit generator.
+//!
+//! Hardware random number generators are often slow and their raw output is
+//! not always suitable as a cryptographic source. `CtrDrbg` implements the
+//! AES-128 CTR_DRBG of NIST SP 800-90A (without a derivation function) on top
+//! of any `hil::symmetric_encryption::AES128Ctr` implementation. It seeds
+//! itself with 256 bits read from a `hil::rng::RNG` entropy source and reseeds
+//! from that source every `RESEED_INTERVAL` generate requests.
+//!
+//! Each generate request runs the block cipher in CTR mode once over
+//! `buffer.len()` bytes: all but the last two blocks are handed out as random
+//! numbers, and the last two blocks become the next key and counter (the
+//! CTR_DRBG "update" step). Unconsumed output is erased rather than kept
+//! around for later requests.
+//!
+//! Consumers use a `VirtualDrbg`, which implements `hil::rng::RNG`, so the pool
+//! can be shared by kernel capsules (e.g. for BLE address generation or
+//! network nonces) and the userspace `capsules::rng::SimpleRng` driver.
+//!
+//! The pool must be the only client of the AES engine it is given. If the
+//! engine refuses a request, the pool starts over from a fresh seed, from a
+//! deferred call that must be registered at board initialization, so that
+//! waiting consumers are still served.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let drbg = static_init!(
+//!     capsules::drbg::CtrDrbg<'static, sam4l::trng::Trng, sam4l::aes::Aes>,
+//!     capsules::drbg::CtrDrbg::new(
+//!         &sam4l::trng::TRNG,
+//!         &sam4l::aes::AES,
+//!         &mut capsules::drbg::INPUT_BUF,
+//!         &mut capsules::drbg::OUTPUT_BUF
+//!     )
+//! );
+//! drbg.initialize_deferred_call(
+//!     dynamic_deferred_call::register(drbg).expect("no deferred call for drbg"),
+//! );
+//! sam4l::trng::TRNG.set_client(drbg);
+//! kernel::hil::symmetric_encryption::AES128::set_client(&sam4l::aes::AES, drbg);
+//!
+//! let virtual_drbg = static_init!(
+//!     capsules::drbg::VirtualDrbg<'static, sam4l::trng::Trng, sam4l::aes::Aes>,
+//!     capsules::drbg::VirtualDrbg::new(drbg)
+//! );
+//! let rng = static_init!(
+//!     capsules::rng::SimpleRng<'static, capsules::drbg::VirtualDrbg<'static, sam4l::trng::Trng, sam4l::aes::Aes>>,
+//!     capsules::rng::SimpleRng::new(virtual_drbg, kernel::Grant::create())
+//! );
+//! virtual_drbg.set_client(rng);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::common::dynamic_deferred_call::{DeferredCallClient, DeferredCallHandle};
+use kernel::common::{List, ListLink, ListNode};
+use kernel::hil::rng;
+use kernel::hil::symmetric_encryption::{self, AES128, AES128Ctr, AES128_BLOCK_SIZE,
+                                        AES128_KEY_SIZE};
+use kernel::ReturnCode;
+
+/// Bytes of entropy used to (re)seed the generator: one key and one counter.
+pub const SEED_LEN: usize = AES128_KEY_SIZE + AES128_BLOCK_SIZE;
+
+/// Number of generate requests served between reseeds from the entropy source.
+pub const RESEED_INTERVAL: usize = 1024;
+
+/// Size of the cipher buffers: four blocks of output plus the update blocks.
+pub const BUF_LEN: usize = 4 * AES128_BLOCK_SIZE + SEED_LEN;
+
+/// Cipher input, which stays all zeros.
+pub static mut INPUT_BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+/// Cipher output, erased after every use.
+pub static mut OUTPUT_BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum State {
+    Unseeded,
+    Seeding,
+    Updating,
+    Idle,
+    Generating,
+}
+
+/// Iterates over the 32-bit words of the generator output, remembering how
+/// far consumers have read.
+struct OutputIter<'b> {
+    buf: &'b [u8],
+    idx: usize,
+}
+
+impl<'b> Iterator for OutputIter<'b> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.idx + 4 > self.buf.len() {
+            None
+        } else {
+            let b = &self.buf[self.idx..self.idx + 4];
+            self.idx += 4;
+            Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+        }
+    }
+}
+
+/// Encode a counter value as a big-endian block.
+fn counter_block(counter: u128) -> [u8; AES128_BLOCK_SIZE] {
+    let mut block = [0; AES128_BLOCK_SIZE];
+    for (i, b) in block.iter_mut().enumerate() {
+        *b = (counter >> (8 * (AES128_BLOCK_SIZE - 1 - i))) as u8;
+    }
+    block
+}
+
+pub struct CtrDrbg<'a, R: rng::RNG + 'a, A: AES128<'a> + AES128Ctr + 'a> {
+    entropy: &'a R,
+    aes: &'a A,
+    input: TakeCell<'a, [u8]>,
+    output: TakeCell<'a, [u8]>,
+    state: Cell<State>,
+    key: Cell<[u8; AES128_KEY_SIZE]>,
+    counter: Cell<u128>,
+    seed: Cell<[u8; SEED_LEN]>,
+    seed_len: Cell<usize>,
+    generated: Cell<usize>,
+    users: List<'a, VirtualDrbg<'a, R, A>>,
+    deferred_call: Cell<Option<DeferredCallHandle>>,
+}
+
+impl<'a, R: rng::RNG, A: AES128<'a> + AES128Ctr> CtrDrbg<'a, R, A> {
+    /// `input` and `output` must have the same length, which must be a
+    /// multiple of `AES128_BLOCK_SIZE` larger than `SEED_LEN`.
+    pub fn new(
+        entropy: &'a R,
+        aes: &'a A,
+        input: &'a mut [u8],
+        output: &'a mut [u8],
+    ) -> CtrDrbg<'a, R, A> {
+        CtrDrbg {
+            entropy: entropy,
+            aes: aes,
+            input: TakeCell::new(input),
+            output: TakeCell::new(output),
+            state: Cell::new(State::Unseeded),
+            key: Cell::new(Default::default()),
+            counter: Cell::new(0),
+            seed: Cell::new([0; SEED_LEN]),
+            seed_len: Cell::new(0),
+            generated: Cell::new(0),
+            users: List::new(),
+            deferred_call: Cell::new(None),
+        }
+    }
+
+    /// Set the deferred call used to retry after the AES engine refuses a
+    /// request.
+    pub fn initialize_deferred_call(&self, handle: DeferredCallHandle) {
+        self.deferred_call.set(Some(handle));
+    }
+
+    /// Start over from a fresh seed, after the AES engine refused a request.
+    /// This is done from a deferred call rather than waiting for the next
+    /// request, as consumers may already be waiting.
+    fn retry(&self) {
+        self.seed.set([0; SEED_LEN]);
+        self.state.set(State::Unseeded);
+        self.deferred_call.get().map(|handle| handle.set());
+    }
+
+    /// Start work for pending consumers if the generator is idle.
+    fn service(&self) {
+        match self.state.get() {
+            State::Unseeded | State::Idle => {}
+            _ => return,
+        }
+        if !self.users.iter().any(|user| user.requested.get()) {
+            return;
+        }
+
+        if self.state.get() == State::Unseeded || self.generated.get() >= RESEED_INTERVAL {
+            self.state.set(State::Seeding);
+            self.seed_len.set(0);
+            self.entropy.get();
+        } else if self.run_cipher(false) == ReturnCode::SUCCESS {
+            self.state.set(State::Generating);
+            self.generated.set(self.generated.get() + 1);
+        } else {
+            self.retry();
+        }
+    }
+
+    /// Run the cipher in CTR mode over the whole buffer. When generating, the
+    /// keystream starts from the current counter plus one. For a bare update
+    /// only the last two blocks are used, so the keystream is shifted to make
+    /// those the blocks for the current counter plus one and plus two.
+    fn run_cipher(&self, update_only: bool) -> ReturnCode {
+        let (input, output) = match (self.input.take(), self.output.take()) {
+            (Some(input), Some(output)) => (input, output),
+            (input, output) => {
+                self.input.put(input);
+                self.output.put(output);
+                return ReturnCode::EBUSY;
+            }
+        };
+
+        let len = output.len();
+        let mut first = self.counter.get().wrapping_add(1);
+        if update_only {
+            let skipped = (len - SEED_LEN) / AES128_BLOCK_SIZE;
+            first = first.wrapping_sub(skipped as u128);
+        }
+
+        self.aes.enable();
+        self.aes.set_mode_aes128ctr(true);
+        self.aes.set_key(&self.key.get());
+        self.aes.set_iv(&counter_block(first));
+        self.aes.start_message();
+        match self.aes.crypt(Some(input), output, 0, len) {
+            None => ReturnCode::SUCCESS,
+            Some((res, input, output)) => {
+                self.input.put(input);
+                self.output.replace(output);
+                self.aes.disable();
+                res
+            }
+        }
+    }
+
+    /// Load the next key and counter from the end of the cipher output,
+    /// mixed with `seed`, and erase them from the buffer.
+    fn update(&self, output: &mut [u8], seed: &[u8; SEED_LEN]) {
+        let start = output.len() - SEED_LEN;
+        let mut key = [0; AES128_KEY_SIZE];
+        let mut counter: u128 = 0;
+        for (i, b) in output[start..].iter_mut().enumerate() {
+            let v = *b ^ seed[i];
+            if i < AES128_KEY_SIZE {
+                key[i] = v;
+            } else {
+                counter = counter << 8 | v as u128;
+            }
+            *b = 0;
+        }
+        self.key.set(key);
+        self.counter.set(counter);
+    }
+
+    /// Hand the fresh output to waiting consumers, in list order.
+    fn deliver(&self, output: &[u8]) {
+        let mut iter = OutputIter {
+            buf: output,
+            idx: 0,
+        };
+        for user in self.users.iter().filter(|user| user.requested.get()) {
+            if iter.idx >= iter.buf.len() {
+                break;
+            }
+            let done = user.client
+                .get()
+                .map_or(rng::Continue::Done, |client| {
+                    client.randomness_available(&mut iter)
+                });
+            if done == rng::Continue::Done {
+                user.requested.set(false);
+            }
+        }
+    }
+}
+
+impl<'a, R: rng::RNG, A: AES128<'a> + AES128Ctr> rng::Client for CtrDrbg<'a, R, A> {
+    fn randomness_available(&self, randomness: &mut Iterator<Item = u32>) -> rng::Continue {
+        if self.state.get() != State::Seeding {
+            return rng::Continue::Done;
+        }
+
+        let mut seed = self.seed.get();
+        let mut len = self.seed_len.get();
+        while len < SEED_LEN {
+            match randomness.next() {
+                Some(word) => {
+                    for i in 0..4 {
+                        seed[len + i] = (word >> (i * 8)) as u8;
+                    }
+                    len += 4;
+                }
+                None => break,
+            }
+        }
+        self.seed.set(seed);
+        self.seed_len.set(len);
+
+        if len < SEED_LEN {
+            return rng::Continue::More;
+        }
+
+        if self.run_cipher(true) == ReturnCode::SUCCESS {
+            self.state.set(State::Updating);
+        } else {
+            self.retry();
+        }
+        rng::Continue::Done
+    }
+}
+
+impl<'a, R: rng::RNG, A: AES128<'a> + AES128Ctr> symmetric_encryption::Client<'a>
+    for CtrDrbg<'a, R, A>
+{
+    fn crypt_done(&self, source: Option<&'a mut [u8]>, output: &'a mut [u8]) {
+        self.aes.disable();
+        self.input.put(source);
+
+        match self.state.get() {
+            State::Updating => {
+                self.update(output, &self.seed.get());
+                self.seed.set([0; SEED_LEN]);
+                self.generated.set(0);
+            }
+            State::Generating => {
+                self.update(output, &[0; SEED_LEN]);
+                let len = output.len() - SEED_LEN;
+                self.deliver(&output[..len]);
+            }
+            _ => {}
+        }
+
+        // Never keep generator output around
+        for b in output.iter_mut() {
+            *b = 0;
+        }
+        self.output.replace(output);
+        self.state.set(State::Idle);
+        self.service();
+    }
+}
+
+impl<'a, R: rng::RNG, A: AES128<'a> + AES128Ctr> DeferredCallClient for CtrDrbg<'a, R, A> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        self.service();
+    }
+}
+
+/// A consumer of the entropy pool.
+pub struct VirtualDrbg<'a, R: rng::RNG + 'a, A: AES128<'a> + AES128Ctr + 'a> {
+    drbg: &'a CtrDrbg<'a, R, A>,
+    requested: Cell<bool>,
+    next: ListLink<'a, VirtualDrbg<'a, R, A>>,
+    client: Cell<Option<&'a rng::Client>>,
+}
+
+impl<'a, R: rng::RNG, A: AES128<'a> + AES128Ctr> ListNode<'a, VirtualDrbg<'a, R, A>>
+    for VirtualDrbg<'a, R, A>
+{
+    fn next(&self) -> &'a ListLink<VirtualDrbg<'a, R, A>> {
+        &self.next
+    }
+}
+
+impl<'a, R: rng::RNG, A: AES128<'a> + AES128Ctr> VirtualDrbg<'a, R, A> {
+    pub fn new(drbg: &'a CtrDrbg<'a, R, A>) -> VirtualDrbg<'a, R, A> {
+        VirtualDrbg {
+            drbg: drbg,
+            requested: Cell::new(false),
+            next: ListLink::empty(),
+            client: Cell::new(None),
+        }
+    }
+
+    pub fn set_client(&'a self, client: &'a rng::Client) {
+        self.drbg.users.push_tail(self);
+        self.client.set(Some(client));
+    }
+}
+
+impl<'a, R: rng::RNG, A: AES128<'a> + AES128Ctr> rng::RNG for VirtualDrbg<'a, R, A> {
+    fn get(&self) {
+        self.requested.set(true);
+        self.drbg.service();
+    }
+}
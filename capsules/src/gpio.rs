@@ -20,12 +20,23 @@
 //!      &sam4l::gpio::PB[12]]);
 //! let gpio = static_init!(
 //!     capsules::gpio::GPIO<'static, sam4l::gpio::GPIOPin>,
-//!     capsules::gpio::GPIO::new(gpio_pins));
+//!     capsules::gpio::GPIO::new(gpio_pins, kernel::Grant::create()));
 //! for pin in gpio_pins.iter() {
 //!     pin.set_client(gpio);
 //! }
 //! ```
 //!
+//! Pins can be assigned to processes by package name. Only the owner of a pin
+//! may configure or drive it, or enable interrupts on it; other processes get
+//! `EPERM`. Pins without an owner may be used by any process.
+//!
+//! ```rust
+//! let gpio_owners = static_init!(
+//!     [Option<&'static str>; 4],
+//!     [Some("sensor_app"), Some("sensor_app"), None, None]);
+//! gpio.set_owners(gpio_owners);
+//! ```
+//!
 //! Syscall Interface
 //! -----------------
 //!
@@ -38,28 +49,171 @@
 //! Commands control and query GPIO information, namely how many GPIOs are
 //! present, the GPIO direction and state, and whether they should interrupt.
 //!
+//! Several pins can be read or written at once, so that a process can update
+//! a group of pins without other processes running in between.
+//!
 //! ### Subscribes
 //!
 //! The GPIO interface provides only one callback, which is used for pins that
-//! have had interrupts enabled.
+//! the process has enabled interrupts on.
 
 /// Syscall driver number.
 pub const DRIVER_NUM: usize = 0x00000004;
 
+/// Number of pins read and written by the port commands. Values must fit in a
+/// positive return code.
+const PORT_PINS: usize = 31;
+
 use core::cell::Cell;
+use core::cmp;
 use kernel::hil::gpio::{Client, InputMode, InterruptMode, Pin, PinCtl};
-use kernel::{AppId, Callback, Driver, ReturnCode};
+use kernel::procs;
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    /// Pins the process has enabled interrupts on
+    interrupts: usize,
+}
 
 pub struct GPIO<'a, G: Pin + 'a> {
     pins: &'a [&'a G],
-    callback: Cell<Option<Callback>>,
+    /// Package name of the process that owns each pin, if any
+    owners: Cell<Option<&'a [Option<&'a str>]>>,
+    apps: Grant<App>,
 }
 
 impl<'a, G: Pin + PinCtl> GPIO<'a, G> {
-    pub fn new(pins: &'a [&'a G]) -> GPIO<'a, G> {
+    /// Panics if given more than 32 pins, as each process keeps a bitmask of
+    /// the pins it has interrupts enabled on.
+    pub fn new(pins: &'a [&'a G], grant: Grant<App>) -> GPIO<'a, G> {
+        assert!(pins.len() <= 32);
         GPIO {
             pins: pins,
-            callback: Cell::new(None),
+            owners: Cell::new(None),
+            apps: grant,
+        }
+    }
+
+    /// Assign pins to processes. `owners[i]` is the package name of the
+    /// process that owns pin `i`, or `None` if any process may use it.
+    pub fn set_owners(&self, owners: &'a [Option<&'a str>]) {
+        self.owners.set(Some(owners));
+    }
+
+    /// Check that `pin` exists and that the process may configure and drive
+    /// it.
+    fn check_access(&self, pin: usize, appid: AppId) -> ReturnCode {
+        if pin >= self.pins.len() {
+            return ReturnCode::EINVAL; /* impossible pin */
+        }
+        let owner = self.owners
+            .get()
+            .and_then(|owners| owners.get(pin).cloned())
+            .unwrap_or(None);
+        match owner {
+            Some(name) if procs::process_name(appid.idx()) != Some(name) => ReturnCode::EPERM,
+            _ => ReturnCode::SUCCESS,
+        }
+    }
+
+    /// The pins covered by the port commands.
+    fn port_mask(&self) -> usize {
+        (1 << cmp::min(self.pins.len(), PORT_PINS)) - 1
+    }
+
+    /// Whether any process has interrupts enabled on `pin`.
+    fn has_listeners(&self, pin: usize) -> bool {
+        let listeners = Cell::new(false);
+        self.apps.each(|app| {
+            if app.interrupts & (1 << pin) != 0 {
+                listeners.set(true);
+            }
+        });
+        listeners.get()
+    }
+
+    /// Commands on a single pin the process may use.
+    fn command_pin(
+        &self,
+        command_num: usize,
+        pin: usize,
+        data2: usize,
+        appid: AppId,
+    ) -> ReturnCode {
+        let pins = self.pins.as_ref();
+        match command_num {
+            // enable output
+            1 => {
+                pins[pin].make_output();
+                ReturnCode::SUCCESS
+            }
+
+            // set pin
+            2 => {
+                pins[pin].set();
+                ReturnCode::SUCCESS
+            }
+
+            // clear pin
+            3 => {
+                pins[pin].clear();
+                ReturnCode::SUCCESS
+            }
+
+            // toggle pin
+            4 => {
+                pins[pin].toggle();
+                ReturnCode::SUCCESS
+            }
+
+            // enable and configure input
+            5 => {
+                let pin_config = data2;
+                self.configure_input_pin(pin, pin_config)
+            }
+
+            // configure interrupts on pin
+            // (no affect or reliance on registered callback)
+            7 => {
+                let irq_config = data2;
+                self.apps
+                    .enter(appid, |app, _| {
+                        let result = self.configure_interrupt(pin, irq_config);
+                        if result == ReturnCode::SUCCESS {
+                            app.interrupts |= 1 << pin;
+                        }
+                        result
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+
+            // disable interrupts on pin, also disables pin once no process
+            // has interrupts enabled on it
+            // (no affect or reliance on registered callback)
+            8 => {
+                let result = self.apps
+                    .enter(appid, |app, _| {
+                        app.interrupts &= !(1 << pin);
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or_else(|err| err.into());
+                if !self.has_listeners(pin) {
+                    pins[pin].disable_interrupt();
+                    pins[pin].disable();
+                }
+                result
+            }
+
+            // disable pin
+            9 => {
+                pins[pin].disable();
+                ReturnCode::SUCCESS
+            }
+
+            // default
+            _ => ReturnCode::ENOSUPPORT,
         }
     }
 
@@ -106,16 +260,26 @@ impl<'a, G: Pin + PinCtl> GPIO<'a, G> {
     }
 }
 
-impl<'a, G: Pin> Client for GPIO<'a, G> {
+impl<'a, G: Pin + PinCtl> Client for GPIO<'a, G> {
     fn fired(&self, pin_num: usize) {
         // read the value of the pin
         let pins = self.pins.as_ref();
         let pin_state = pins[pin_num].read();
 
-        // schedule callback with the pin number and value
-        self.callback
-            .get()
-            .map(|mut cb| cb.schedule(pin_num, pin_state as usize, 0));
+        // schedule callback with the pin number and value for the processes
+        // that enabled the interrupt
+        self.apps.each(|app| {
+            if app.interrupts & (1 << pin_num) != 0 {
+                app.callback
+                    .map(|mut cb| cb.schedule(pin_num, pin_state as usize, 0));
+            }
+        });
+
+        // The processes that enabled it may have since died. Lazily disable
+        // the interrupt if so.
+        if !self.has_listeners(pin_num) {
+            pins[pin_num].disable_interrupt();
+        }
     }
 }
 
@@ -124,21 +288,24 @@ impl<'a, G: Pin + PinCtl> Driver for GPIO<'a, G> {
     ///
     /// ### `subscribe_num`
     ///
-    /// - `0`: Subscribe to interrupts from all pins with interrupts enabled.
-    ///        The callback signature is `fn(pin_num: usize, pin_state: bool)`
+    /// - `0`: Subscribe to interrupts from all pins the process enabled
+    ///        interrupts on. The callback signature is
+    ///        `fn(pin_num: usize, pin_state: bool)`
     fn subscribe(
         &self,
         subscribe_num: usize,
         callback: Option<Callback>,
-        _app_id: AppId,
+        app_id: AppId,
     ) -> ReturnCode {
         match subscribe_num {
             // subscribe to all pin interrupts (no affect or reliance on
             // individual pins being configured as interrupts)
-            0 => {
-                self.callback.set(callback);
-                ReturnCode::SUCCESS
-            }
+            0 => self.apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
 
             // default
             _ => ReturnCode::ENOSUPPORT,
@@ -175,7 +342,13 @@ impl<'a, G: Pin + PinCtl> Driver for GPIO<'a, G> {
     /// - `7`: Configure interrupt on `pin` with `irq_config` in 0x00XX00000
     /// - `8`: Disable interrupt on `pin`.
     /// - `9`: Disable `pin`.
-    fn command(&self, command_num: usize, data1: usize, data2: usize, _: AppId) -> ReturnCode {
+    /// - `10`: Read the values of the first 31 pins as a bit mask.
+    /// - `11`: Set the pins in the mask `data1` to the values of the same bits
+    ///         in `data2`.
+    ///
+    /// Commands other than reads return `EPERM` if the pin is assigned to
+    /// another process. Command `11` changes no pin if it returns an error.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, appid: AppId) -> ReturnCode {
         let pins = self.pins.as_ref();
         let pin = data1;
         match command_num {
@@ -184,56 +357,6 @@ impl<'a, G: Pin + PinCtl> Driver for GPIO<'a, G> {
                 value: pins.len() as usize,
             },
 
-            // enable output
-            1 => {
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    pins[pin].make_output();
-                    ReturnCode::SUCCESS
-                }
-            }
-
-            // set pin
-            2 => {
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    pins[pin].set();
-                    ReturnCode::SUCCESS
-                }
-            }
-
-            // clear pin
-            3 => {
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    pins[pin].clear();
-                    ReturnCode::SUCCESS
-                }
-            }
-
-            // toggle pin
-            4 => {
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    pins[pin].toggle();
-                    ReturnCode::SUCCESS
-                }
-            }
-
-            // enable and configure input
-            5 => {
-                let pin_config = data2;
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    self.configure_input_pin(pin, pin_config)
-                }
-            }
-
             // read input
             6 => {
                 if pin >= pins.len() {
@@ -246,37 +369,47 @@ impl<'a, G: Pin + PinCtl> Driver for GPIO<'a, G> {
                 }
             }
 
-            // configure interrupts on pin
-            // (no affect or reliance on registered callback)
-            7 => {
-                let irq_config = data2;
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    self.configure_interrupt(pin, irq_config)
+            // read port
+            10 => {
+                let mut value = 0;
+                for (index, pin) in pins.iter().enumerate() {
+                    if self.port_mask() & (1 << index) != 0 && pin.read() {
+                        value |= 1 << index;
+                    }
                 }
+                ReturnCode::SuccessWithValue { value: value }
             }
 
-            // disable interrupts on pin, also disables pin
-            // (no affect or reliance on registered callback)
-            8 => {
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    pins[pin].disable_interrupt();
-                    pins[pin].disable();
-                    ReturnCode::SUCCESS
+            // write port
+            11 => {
+                let mask = data1;
+                if mask & !self.port_mask() != 0 {
+                    return ReturnCode::EINVAL; /* impossible pin */
                 }
+                let masked = || (0..pins.len()).filter(move |&index| mask & (1 << index) != 0);
+                for index in masked() {
+                    let result = self.check_access(index, appid);
+                    if result != ReturnCode::SUCCESS {
+                        return result;
+                    }
+                }
+                for index in masked() {
+                    if data2 & (1 << index) != 0 {
+                        pins[index].set();
+                    } else {
+                        pins[index].clear();
+                    }
+                }
+                ReturnCode::SUCCESS
             }
 
-            // disable pin
-            9 => {
-                if pin >= pins.len() {
-                    ReturnCode::EINVAL /* impossible pin */
-                } else {
-                    pins[pin].disable();
-                    ReturnCode::SUCCESS
+            // commands on a pin the process must have access to
+            1...5 | 7...9 => {
+                let result = self.check_access(pin, appid);
+                if result != ReturnCode::SUCCESS {
+                    return result;
                 }
+                self.command_pin(command_num, pin, data2, appid)
             }
 
             // default
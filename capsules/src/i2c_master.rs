@@ -0,0 +1,393 @@
+//! Provides userspace with transactions on an I2C bus.
+//!
+//! Processes may only talk to the device addresses the board allows them, by
+//! package name. The bus is shared with the kernel's own I2C devices through
+//! a `MuxI2C`. Each process may queue up to `MAX_QUEUED_TRANSACTIONS`
+//! transactions, and transactions run in the order they were requested,
+//! whichever process requested them.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let i2c_master_device = static_init!(
+//!     capsules::virtual_i2c::I2CDevice,
+//!     capsules::virtual_i2c::I2CDevice::new(sensors_i2c, 0)
+//! );
+//! let i2c_master_permissions = static_init!(
+//!     [(&'static str, u8); 2],
+//!     [("thermostat", 0x48), ("thermostat", 0x49)]
+//! );
+//! let i2c_master = static_init!(
+//!     capsules::i2c_master::I2CMasterDriver<'static>,
+//!     capsules::i2c_master::I2CMasterDriver::new(
+//!         i2c_master_device,
+//!         i2c_master_permissions,
+//!         &mut capsules::i2c_master::BUFFER,
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! i2c_master_device.set_client(i2c_master);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! ### Command
+//!
+//! The address of the device is passed in the low 8 bits of `data1`, and the
+//! offset in the shared buffer the transaction writes from and reads into in
+//! the bits above. Queued transactions should use separate parts of the
+//! buffer, as a transaction may start before the process has handled the
+//! callback of the previous one. Lengths are passed in `data2` and are
+//! limited by the shared buffer and by the kernel's buffer, usually
+//! `BUFFER_LEN`. Commands return `ESIZE` for longer transactions.
+//!
+//! #### `command_num`
+//!
+//! - `0`: Driver check.
+//! - `1`: Write `data2` bytes from the buffer.
+//! - `2`: Read `data2` bytes into the buffer.
+//! - `3`: Write `data2 & 0xff` bytes from the buffer, then read
+//!   `data2 >> 8` bytes into the buffer at the same offset.
+//!
+//! Commands return `EPERM` if the process may not use the address, and
+//! `ENOMEM` if it already has `MAX_QUEUED_TRANSACTIONS` transactions
+//! outstanding.
+//!
+//! ### Allow
+//!
+//! - `1`: The buffer written from and read into.
+//!
+//! ### Subscribe
+//!
+//! - `1`: Callback when a transaction ends, once for each transaction in the
+//!   order they were requested. The first argument is the result:
+//!   `SUCCESS`, `ENOACK` if the device did not acknowledge its address or the
+//!   data, `EBUSY` if another master won arbitration of the bus, or `FAIL`.
+//!   The second is the number of bytes read. The third tells errors apart:
+//!   one of the `TransactionError` values.
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::common::cells::TakeCell;
+use kernel::hil::i2c::{self, I2CDevice};
+use kernel::procs;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+use virtual_i2c;
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x00020003;
+
+/// Length of the default kernel buffer, the longest write or read.
+pub const BUFFER_LEN: usize = 64;
+
+pub static mut BUFFER: [u8; BUFFER_LEN] = [0; BUFFER_LEN];
+
+/// Number of transactions each process may have outstanding at once.
+pub const MAX_QUEUED_TRANSACTIONS: usize = 4;
+
+/// How a transaction ended, passed to userspace as the third callback
+/// argument.
+#[derive(Clone, Copy)]
+pub enum TransactionError {
+    None = 0,
+    AddressNak = 1,
+    DataNak = 2,
+    ArbitrationLost = 3,
+    Overrun = 4,
+}
+
+impl From<i2c::Error> for TransactionError {
+    fn from(error: i2c::Error) -> TransactionError {
+        match error {
+            i2c::Error::AddressNak => TransactionError::AddressNak,
+            i2c::Error::DataNak => TransactionError::DataNak,
+            i2c::Error::ArbitrationLost => TransactionError::ArbitrationLost,
+            i2c::Error::Overrun => TransactionError::Overrun,
+            i2c::Error::CommandComplete => TransactionError::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Transaction {
+    addr: u8,
+    /// Where in the process's buffer to write from and read into
+    offset: usize,
+    write_len: u8,
+    read_len: u8,
+    /// When the transaction was requested, relative to other processes'
+    seq: usize,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+    transactions: [Option<Transaction>; MAX_QUEUED_TRANSACTIONS],
+}
+
+pub struct I2CMasterDriver<'a> {
+    device: &'a virtual_i2c::I2CDevice<'a>,
+    /// Package names of processes and the addresses they may use
+    permissions: &'a [(&'a str, u8)],
+    buffer: TakeCell<'static, [u8]>,
+    /// The longest write or read, limited by `buffer`
+    max_len: usize,
+    /// The process whose transaction is on the bus, and the transaction's
+    /// slot in its queue
+    inflight: Cell<Option<(AppId, usize)>>,
+    seq: Cell<usize>,
+    apps: Grant<App>,
+}
+
+impl<'a> I2CMasterDriver<'a> {
+    pub fn new(
+        device: &'a virtual_i2c::I2CDevice<'a>,
+        permissions: &'a [(&'a str, u8)],
+        buffer: &'static mut [u8],
+        grant: Grant<App>,
+    ) -> I2CMasterDriver<'a> {
+        I2CMasterDriver {
+            device: device,
+            permissions: permissions,
+            max_len: cmp::min(buffer.len(), 255),
+            buffer: TakeCell::new(buffer),
+            inflight: Cell::new(None),
+            seq: Cell::new(0),
+            apps: grant,
+        }
+    }
+
+    fn is_permitted(&self, appid: AppId, addr: usize) -> bool {
+        procs::process_name(appid.idx()).map_or(false, |name| {
+            self.permissions
+                .iter()
+                .any(|&(owner, allowed)| owner == name && allowed as usize == addr)
+        })
+    }
+
+    fn request(
+        &self,
+        appid: AppId,
+        addr: usize,
+        offset: usize,
+        write_len: usize,
+        read_len: usize,
+    ) -> ReturnCode {
+        if addr > 0x7f {
+            return ReturnCode::EINVAL;
+        }
+        if !self.is_permitted(appid, addr) {
+            return ReturnCode::EPERM;
+        }
+        if write_len > self.max_len || read_len > self.max_len {
+            return ReturnCode::ESIZE;
+        }
+
+        let result = self.apps
+            .enter(appid, |app, _| {
+                let len = app.buffer.as_ref().map_or(0, |buffer| buffer.len());
+                if len == 0 || offset >= len || cmp::max(write_len, read_len) > len - offset {
+                    return ReturnCode::EINVAL;
+                }
+                let slot = match app.transactions.iter_mut().find(|slot| slot.is_none()) {
+                    Some(slot) => slot,
+                    None => return ReturnCode::ENOMEM,
+                };
+                *slot = Some(Transaction {
+                    addr: addr as u8,
+                    offset: offset,
+                    write_len: write_len as u8,
+                    read_len: read_len as u8,
+                    seq: self.seq.get(),
+                });
+                self.seq.set(self.seq.get().wrapping_add(1));
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+
+        if result == ReturnCode::SUCCESS {
+            self.do_next_transaction();
+        }
+        result
+    }
+
+    /// Start the oldest outstanding transaction, if the bus is free.
+    fn do_next_transaction(&self) {
+        while self.inflight.get().is_none() && self.buffer.is_some() {
+            let mut next: Option<(usize, AppId, usize)> = None;
+            for app in self.apps.iter() {
+                app.enter(|app, _| {
+                    for (slot, transaction) in app.transactions.iter().enumerate() {
+                        if let Some(transaction) = *transaction {
+                            if next.map_or(true, |(seq, _, _)| transaction.seq < seq) {
+                                next = Some((transaction.seq, app.appid(), slot));
+                            }
+                        }
+                    }
+                });
+            }
+            let (appid, slot) = match next {
+                Some((_, appid, slot)) => (appid, slot),
+                None => return,
+            };
+
+            let _ = self.apps.enter(appid, |app, _| {
+                let transaction = match app.transactions[slot] {
+                    Some(transaction) => transaction,
+                    None => return,
+                };
+                let offset = transaction.offset;
+                let write_len = transaction.write_len as usize;
+                let read_len = transaction.read_len as usize;
+                let valid = app.buffer.as_ref().map_or(false, |slice| {
+                    slice.len() >= offset + cmp::max(write_len, read_len)
+                });
+                if !valid {
+                    // The buffer changed since the request
+                    app.transactions[slot] = None;
+                    app.callback.map(|mut cb| {
+                        cb.schedule(From::from(ReturnCode::EINVAL), 0, 0);
+                    });
+                    return;
+                }
+
+                let slice = &app.buffer;
+                self.buffer.take().map(|kernel_buffer| {
+                    slice.as_ref().map(|slice| {
+                        kernel_buffer[..write_len]
+                            .copy_from_slice(&slice.as_ref()[offset..offset + write_len]);
+                    });
+                    self.inflight.set(Some((appid, slot)));
+                    self.device.set_address(transaction.addr);
+                    self.device.enable();
+                    match (write_len, read_len) {
+                        (_, 0) => self.device.write(kernel_buffer, write_len as u8),
+                        (0, _) => self.device.read(kernel_buffer, read_len as u8),
+                        _ => self.device
+                            .write_read(kernel_buffer, write_len as u8, read_len as u8),
+                    }
+                });
+            });
+        }
+    }
+}
+
+impl<'a> i2c::I2CClient for I2CMasterDriver<'a> {
+    fn command_complete(&self, buffer: &'static mut [u8], error: i2c::Error) {
+        self.device.disable();
+        let result = ReturnCode::from(error);
+        self.inflight.take().map(|(appid, slot)| {
+            let _ = self.apps.enter(appid, |app, _| {
+                let (offset, read_len) = match app.transactions[slot].take() {
+                    Some(transaction) if result == ReturnCode::SUCCESS => {
+                        (transaction.offset, transaction.read_len as usize)
+                    }
+                    _ => (0, 0),
+                };
+                let read_len = app.buffer.as_mut().map_or(0, |app_buffer| {
+                    let len = cmp::min(read_len, app_buffer.len().saturating_sub(offset));
+                    app_buffer.as_mut()[offset..offset + len].copy_from_slice(&buffer[..len]);
+                    len
+                });
+                app.callback.map(|mut cb| {
+                    cb.schedule(
+                        From::from(result),
+                        read_len,
+                        TransactionError::from(error) as usize,
+                    );
+                });
+            });
+        });
+        self.buffer.replace(buffer);
+        self.do_next_transaction();
+    }
+}
+
+impl<'a> Driver for I2CMasterDriver<'a> {
+    /// Setup shared buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `1`: The buffer written from and read into.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            1 => self.apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Setup callbacks.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `1`: Callback when a transaction ends.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            1 => self.apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Start transactions.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Write `data2` bytes to the device at address `data1 & 0xff`.
+    /// - `2`: Read `data2` bytes from the device at address `data1 & 0xff`.
+    /// - `3`: Write `data2 & 0xff` bytes to the device at address
+    ///        `data1 & 0xff`, then read `data2 >> 8` bytes.
+    ///
+    /// The transaction uses the buffer from offset `data1 >> 8`. It is queued
+    /// behind earlier ones, and `ENOMEM` is returned if the process already
+    /// has `MAX_QUEUED_TRANSACTIONS` outstanding.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => {
+                if data2 == 0 {
+                    return ReturnCode::EINVAL;
+                }
+                self.request(appid, data1 & 0xff, data1 >> 8, data2, 0)
+            }
+            2 => {
+                if data2 == 0 {
+                    return ReturnCode::EINVAL;
+                }
+                self.request(appid, data1 & 0xff, data1 >> 8, 0, data2)
+            }
+            3 => {
+                let write_len = data2 & 0xff;
+                let read_len = data2 >> 8;
+                if write_len == 0 || read_len == 0 {
+                    return ReturnCode::EINVAL;
+                }
+                self.request(appid, data1 & 0xff, data1 >> 8, write_len, read_len)
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
@@ -0,0 +1,655 @@
+//! Provides userspace with LEDs that blink and run patterns in the kernel.
+//!
+//! This is a drop-in replacement for [`led`](../led/index.html) that also
+//! lets a process hand the kernel a blink, or a sequence of steps across
+//! several LEDs, so that it does not need to wake up for every change. LEDs
+//! with a PWM-capable output can also be dimmed. All LEDs share one alarm,
+//! usually a `VirtualMuxAlarm`.
+//!
+//! A blinking LED, and the LEDs used by a running program, belong to the
+//! process that started it until it ends. Other processes get `EBUSY` when
+//! they try to control them.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let led_pins = static_init!(
+//!     [capsules::led_pattern::LedPin<'static, sam4l::gpio::GPIOPin>; 2],
+//!     [
+//!         capsules::led_pattern::LedPin::new(
+//!             &sam4l::gpio::PA[13],
+//!             capsules::led::ActivationMode::ActiveLow
+//!         ),
+//!         capsules::led_pattern::LedPin::new(
+//!             &sam4l::gpio::PA[15],
+//!             capsules::led::ActivationMode::ActiveLow
+//!         ),
+//!     ]
+//! );
+//! // Dim the first LED with a software PWM on the same pin
+//! led_pins[0].set_pwm(pwm_pin);
+//! let led_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let led = static_init!(
+//!     capsules::led_pattern::LedPattern<
+//!         'static,
+//!         sam4l::gpio::GPIOPin,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::led_pattern::LedPattern::new(led_pins, led_alarm, kernel::Grant::create())
+//! );
+//! led_alarm.set_client(led);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! The interface is that of `led`, with the addition of commands `4` to `7`.
+//!
+//! ### Command
+//!
+//! #### `command_num`
+//!
+//! - `0`: Return the number of LEDs on this platform.
+//! - `1`: Turn the LED `data` on.
+//! - `2`: Turn the LED `data` off.
+//! - `3`: Toggle the LED `data`.
+//! - `4`: Blink an LED. The low byte of `data` is the index of the LED and
+//!   the rest is the number of blinks, or 0 to blink until stopped. The low
+//!   half of `arg2` is the time on and the high half the period, in ms.
+//! - `5`: Run the program shared with `allow` `0`, `data` times or until
+//!   stopped if 0.
+//! - `6`: Stop the process's program and turn its LEDs off.
+//! - `7`: Set the brightness of the LED `data` when on to `arg2`, from 0 to
+//!   `MAX_BRIGHTNESS`. LEDs without PWM are on at any non-zero brightness.
+//!
+//! Commands `1` to `4` on a blinking LED stop the blink.
+//!
+//! ### Allow
+//!
+//! - `0`: The program, a sequence of 4-byte steps. Each step is a mask of
+//!   the LEDs to turn on (LEDs 0 to 7), their brightness, and the duration
+//!   of the step in ms as a 16-bit little-endian value. The program's other
+//!   LEDs are off during the step.
+//!
+//! ### Subscribe
+//!
+//! - `0`: Callback when a finite blink or program ends. It is called with
+//!   `0` and the index of the LED for a blink, or `1` for a program.
+
+use core::cell::Cell;
+use core::cmp;
+use kernel::hil;
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+pub use led::ActivationMode;
+
+/// Syscall driver number, the same as the plain LED driver.
+pub const DRIVER_NUM: usize = ::led::DRIVER_NUM;
+
+/// The brightness of an LED that is fully on.
+pub const MAX_BRIGHTNESS: usize = 0xff;
+
+/// Frequency of the PWM signal for dimmed LEDs.
+const PWM_FREQUENCY_HZ: usize = 200;
+
+const STEP_LEN: usize = 4;
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    program: Option<AppSlice<Shared, u8>>,
+}
+
+#[derive(Clone, Copy)]
+struct Blink {
+    app: AppId,
+    on_tics: u32,
+    off_tics: u32,
+    /// Blinks left, or 0 to blink until stopped
+    remaining: usize,
+    lit: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Program {
+    app: AppId,
+    /// The LEDs the program uses
+    leds: usize,
+    step: usize,
+    /// Runs left, or 0 to run until stopped
+    remaining: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Step {
+    leds: usize,
+    brightness: usize,
+    duration_ms: u32,
+}
+
+/// An LED and its blink state.
+pub struct LedPin<'a, G: hil::gpio::Pin + 'a> {
+    pin: &'a G,
+    mode: ActivationMode,
+    pwm: Cell<Option<&'a hil::pwm::PwmPin>>,
+    /// Brightness when turned on by a command
+    brightness: Cell<usize>,
+    /// Brightness it is set to now
+    level: Cell<usize>,
+    blink: Cell<Option<Blink>>,
+    /// Time of the last blink edge and the time until the next
+    timeout: Cell<Option<(u32, u32)>>,
+}
+
+impl<'a, G: hil::gpio::Pin> LedPin<'a, G> {
+    pub fn new(pin: &'a G, mode: ActivationMode) -> LedPin<'a, G> {
+        LedPin {
+            pin: pin,
+            mode: mode,
+            pwm: Cell::new(None),
+            brightness: Cell::new(MAX_BRIGHTNESS),
+            level: Cell::new(0),
+            blink: Cell::new(None),
+            timeout: Cell::new(None),
+        }
+    }
+
+    /// Dim the LED with a PWM output that drives the same LED, instead of
+    /// the GPIO pin.
+    pub fn set_pwm(&self, pwm: &'a hil::pwm::PwmPin) {
+        self.pwm.set(Some(pwm));
+    }
+
+    fn init(&self) {
+        self.pin.make_output();
+        self.set(0);
+    }
+
+    fn set(&self, level: usize) {
+        self.level.set(level);
+        match self.pwm.get() {
+            Some(pwm) => {
+                let max = pwm.get_maximum_duty_cycle();
+                let duty = level * max / MAX_BRIGHTNESS;
+                let duty = match self.mode {
+                    ActivationMode::ActiveHigh => duty,
+                    ActivationMode::ActiveLow => max - duty,
+                };
+                let frequency = cmp::min(PWM_FREQUENCY_HZ, pwm.get_maximum_frequency_hz());
+                pwm.start(frequency, duty);
+            }
+            None => match (self.mode, level > 0) {
+                (ActivationMode::ActiveHigh, true) | (ActivationMode::ActiveLow, false) => {
+                    self.pin.set()
+                }
+                _ => self.pin.clear(),
+            },
+        }
+    }
+
+    fn on(&self) {
+        self.set(self.brightness.get());
+    }
+
+    fn off(&self) {
+        self.set(0);
+    }
+}
+
+/// Holds the LEDs and the blinks and program running on them, and
+/// implements a `Driver` interface to control them.
+pub struct LedPattern<'a, G: hil::gpio::Pin + 'a, A: Alarm + 'a> {
+    leds: &'a [LedPin<'a, G>],
+    alarm: &'a A,
+    apps: Grant<App>,
+    program: Cell<Option<Program>>,
+    /// Time the current step started and its duration
+    program_timeout: Cell<Option<(u32, u32)>>,
+}
+
+impl<'a, G: hil::gpio::Pin + hil::gpio::PinCtl, A: Alarm> LedPattern<'a, G, A> {
+    pub fn new(
+        leds: &'a [LedPin<'a, G>],
+        alarm: &'a A,
+        grant: Grant<App>,
+    ) -> LedPattern<'a, G, A> {
+        // Make all pins output and off
+        for led in leds.iter() {
+            led.init();
+        }
+
+        LedPattern {
+            leds: leds,
+            alarm: alarm,
+            apps: grant,
+            program: Cell::new(None),
+            program_timeout: Cell::new(None),
+        }
+    }
+
+    fn tics(&self, ms: u32) -> u32 {
+        (ms as u64 * A::Frequency::frequency() as u64 / 1000) as u32
+    }
+
+    /// Whether `app` is another process that still exists.
+    fn is_other(&self, owner: AppId, app: AppId) -> bool {
+        owner != app && self.apps.enter(owner, |_, _| ()).is_ok()
+    }
+
+    /// The LEDs another process's program is using.
+    fn busy_leds(&self, appid: AppId) -> usize {
+        match self.program.get() {
+            Some(program) if self.is_other(program.app, appid) => program.leds,
+            _ => 0,
+        }
+    }
+
+    /// Stop any blink on an LED and return it to direct control, unless it
+    /// belongs to another process.
+    fn claim(&self, index: usize, appid: AppId) -> ReturnCode {
+        let led = &self.leds[index];
+        if self.busy_leds(appid) & (1 << index) != 0 {
+            return ReturnCode::EBUSY;
+        }
+        if let Some(blink) = led.blink.get() {
+            if self.is_other(blink.app, appid) {
+                return ReturnCode::EBUSY;
+            }
+            led.blink.set(None);
+            led.timeout.set(None);
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn schedule(&self, appid: AppId, arg1: usize, arg2: usize) {
+        let _ = self.apps.enter(appid, |app, _| {
+            app.callback.map(|mut cb| cb.schedule(arg1, arg2, 0));
+        });
+    }
+
+    /// Set the alarm for the next blink edge or program step.
+    fn set_next_alarm(&self) {
+        let now = self.alarm.now();
+        let mut next: Option<u32> = None;
+        let timeouts = self.leds
+            .iter()
+            .map(|led| led.timeout.get())
+            .chain(Some(self.program_timeout.get()));
+        for (reference, dt) in timeouts.flat_map(|t| t) {
+            let remaining = if self.alarm.has_expired(reference, dt, now) {
+                0
+            } else {
+                dt - self.alarm.elapsed(reference, now)
+            };
+            next = Some(next.map_or(remaining, |next| cmp::min(next, remaining)));
+        }
+
+        match next {
+            Some(dt) => self.alarm.set_alarm_from(now, dt),
+            None => self.alarm.disable(),
+        }
+    }
+
+    fn blink(
+        &self,
+        index: usize,
+        count: usize,
+        on_ms: u32,
+        period_ms: u32,
+        appid: AppId,
+    ) -> ReturnCode {
+        if on_ms == 0 || on_ms >= period_ms {
+            return ReturnCode::EINVAL;
+        }
+        let result = self.claim(index, appid);
+        if result != ReturnCode::SUCCESS {
+            return result;
+        }
+
+        let led = &self.leds[index];
+        let on_tics = self.tics(on_ms);
+        led.blink.set(Some(Blink {
+            app: appid,
+            on_tics: on_tics,
+            off_tics: self.tics(period_ms) - on_tics,
+            remaining: count,
+            lit: true,
+        }));
+        led.on();
+        led.timeout.set(Some((self.alarm.now(), on_tics)));
+        self.set_next_alarm();
+        ReturnCode::SUCCESS
+    }
+
+    /// Move a blinking LED to its next edge, `reference` being the time of
+    /// the edge that is due.
+    fn blink_edge(&self, index: usize, reference: u32) {
+        let led = &self.leds[index];
+        let mut blink = match led.blink.get() {
+            Some(blink) => blink,
+            None => return,
+        };
+
+        if blink.lit {
+            blink.lit = false;
+            led.blink.set(Some(blink));
+            led.off();
+            led.timeout.set(Some((reference, blink.off_tics)));
+            return;
+        }
+
+        if blink.remaining > 0 {
+            blink.remaining -= 1;
+            if blink.remaining == 0 {
+                led.blink.set(None);
+                led.timeout.set(None);
+                self.schedule(blink.app, 0, index);
+                return;
+            }
+        }
+        blink.lit = true;
+        led.blink.set(Some(blink));
+        led.on();
+        led.timeout.set(Some((reference, blink.on_tics)));
+    }
+
+    /// Read a step of a process's program. Returns `None` if the process or
+    /// its program is gone.
+    fn step(&self, appid: AppId, index: usize) -> Option<Step> {
+        self.apps
+            .enter(appid, |app, _| {
+                app.program.as_ref().and_then(|program| {
+                    let bytes = program.as_ref();
+                    let step = index * STEP_LEN;
+                    if step + STEP_LEN > bytes.len() {
+                        return None;
+                    }
+                    Some(Step {
+                        leds: bytes[step] as usize,
+                        brightness: bytes[step + 1] as usize,
+                        duration_ms: bytes[step + 2] as u32 | (bytes[step + 3] as u32) << 8,
+                    })
+                })
+            })
+            .unwrap_or(None)
+    }
+
+    fn start_program(&self, count: usize, appid: AppId) -> ReturnCode {
+        // Check the program, and find the LEDs it uses
+        let steps = self.apps
+            .enter(appid, |app, _| {
+                app.program
+                    .as_ref()
+                    .map_or(0, |program| program.len() / STEP_LEN)
+            })
+            .unwrap_or(0);
+        if steps == 0 {
+            return ReturnCode::EINVAL;
+        }
+        let all_leds = (1 << cmp::min(self.leds.len(), 8)) - 1;
+        let mut leds = 0;
+        for index in 0..steps {
+            match self.step(appid, index) {
+                Some(step) if step.duration_ms > 0 && step.leds & !all_leds == 0 => {
+                    leds |= step.leds;
+                }
+                _ => return ReturnCode::EINVAL,
+            }
+        }
+
+        if self.busy_leds(appid) != 0 {
+            return ReturnCode::EBUSY;
+        }
+        for index in 0..self.leds.len() {
+            if leds & (1 << index) != 0 {
+                let result = self.claim(index, appid);
+                if result != ReturnCode::SUCCESS {
+                    return result;
+                }
+            }
+        }
+
+        // A program the process was already running is replaced
+        if let Some(program) = self.program.get() {
+            self.set_leds(program.leds, 0, 0);
+        }
+        self.program.set(Some(Program {
+            app: appid,
+            leds: leds,
+            step: 0,
+            remaining: count,
+        }));
+        self.run_step(self.alarm.now());
+        self.set_next_alarm();
+        ReturnCode::SUCCESS
+    }
+
+    /// Stop the running program, turning its LEDs off.
+    fn stop_program(&self) {
+        if let Some(program) = self.program.get() {
+            self.set_leds(program.leds, 0, 0);
+        }
+        self.program.set(None);
+        self.program_timeout.set(None);
+    }
+
+    /// Set the LEDs in `leds` that are in `on` to `brightness` and the others
+    /// off.
+    fn set_leds(&self, leds: usize, on: usize, brightness: usize) {
+        for (index, led) in self.leds.iter().enumerate() {
+            if leds & (1 << index) != 0 {
+                led.set(if on & (1 << index) != 0 { brightness } else { 0 });
+            }
+        }
+    }
+
+    /// Show the program's current step, which starts at `reference`.
+    fn run_step(&self, reference: u32) {
+        let mut program = match self.program.get() {
+            Some(program) => program,
+            None => return,
+        };
+
+        let step = match self.step(program.app, program.step) {
+            Some(step) => step,
+            None if program.step > 0 => {
+                // The end of the program
+                if program.remaining > 0 {
+                    program.remaining -= 1;
+                    if program.remaining == 0 {
+                        self.stop_program();
+                        self.schedule(program.app, 1, 0);
+                        return;
+                    }
+                }
+                program.step = 0;
+                match self.step(program.app, 0) {
+                    Some(step) => step,
+                    None => {
+                        self.stop_program();
+                        return;
+                    }
+                }
+            }
+            None => {
+                // The process or its program is gone
+                self.stop_program();
+                return;
+            }
+        };
+        if step.duration_ms == 0 {
+            // The process changed its program while it ran
+            self.stop_program();
+            return;
+        }
+
+        self.set_leds(program.leds, step.leds, step.brightness);
+        program.step += 1;
+        self.program.set(Some(program));
+        self.program_timeout
+            .set(Some((reference, self.tics(step.duration_ms))));
+    }
+}
+
+impl<'a, G: hil::gpio::Pin + hil::gpio::PinCtl, A: Alarm> Driver for LedPattern<'a, G, A> {
+    /// Setup shared buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The program run by command `5`.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.program = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Setup callbacks.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Callback when a finite blink or program ends.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Control the LEDs.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Returns the number of LEDs on the board.
+    /// - `1`: Turn the LED at index specified by `data` on.
+    /// - `2`: Turn the LED at index specified by `data` off.
+    /// - `3`: Toggle the LED at index specified by `data` on or off.
+    /// - `4`: Blink the LED at index `data & 0xff`, `data >> 8` times or
+    ///        until stopped if 0, on for `arg2 & 0xffff` ms every
+    ///        `arg2 >> 16` ms.
+    /// - `5`: Run the allowed program `data` times, or until stopped if 0.
+    /// - `6`: Stop the process's program.
+    /// - `7`: Set the brightness of the LED at index `data` to `arg2`.
+    ///
+    /// Commands on an LED return `EINVAL` if the LED index is not valid, and
+    /// `EBUSY` if the LED belongs to another process's blink or program.
+    fn command(&self, command_num: usize, data: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        let leds = self.leds;
+        match command_num {
+            // get number of LEDs
+            0 => ReturnCode::SuccessWithValue {
+                value: leds.len() as usize,
+            },
+
+            // on, off, toggle
+            1...3 => {
+                if data >= leds.len() {
+                    return ReturnCode::EINVAL; /* impossible pin */
+                }
+                let result = self.claim(data, appid);
+                if result == ReturnCode::SUCCESS {
+                    let led = &leds[data];
+                    match command_num {
+                        1 => led.on(),
+                        2 => led.off(),
+                        _ => if led.level.get() > 0 {
+                            led.off()
+                        } else {
+                            led.on()
+                        },
+                    }
+                }
+                result
+            }
+
+            // blink
+            4 => {
+                let index = data & 0xff;
+                if index >= leds.len() {
+                    return ReturnCode::EINVAL; /* impossible pin */
+                }
+                let on_ms = (arg2 & 0xffff) as u32;
+                let period_ms = (arg2 >> 16) as u32;
+                self.blink(index, data >> 8, on_ms, period_ms, appid)
+            }
+
+            // run program
+            5 => self.start_program(data, appid),
+
+            // stop program
+            6 => match self.program.get() {
+                Some(program) if program.app == appid => {
+                    self.stop_program();
+                    self.set_next_alarm();
+                    ReturnCode::SUCCESS
+                }
+                _ => ReturnCode::EALREADY,
+            },
+
+            // brightness
+            7 => {
+                if data >= leds.len() || arg2 > MAX_BRIGHTNESS {
+                    return ReturnCode::EINVAL;
+                }
+                let led = &leds[data];
+                if self.busy_leds(appid) & (1 << data) != 0 {
+                    return ReturnCode::EBUSY;
+                }
+                led.brightness.set(arg2);
+                if led.level.get() > 0 {
+                    led.on();
+                }
+                ReturnCode::SUCCESS
+            }
+
+            // default
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+impl<'a, G: hil::gpio::Pin + hil::gpio::PinCtl, A: Alarm> time::Client for LedPattern<'a, G, A> {
+    fn fired(&self) {
+        let now = self.alarm.now();
+        for (index, led) in self.leds.iter().enumerate() {
+            if let Some((reference, dt)) = led.timeout.get() {
+                if self.alarm.has_expired(reference, dt, now) {
+                    self.blink_edge(index, reference.wrapping_add(dt) & self.alarm.max_tics());
+                }
+            }
+        }
+
+        if let Some((reference, dt)) = self.program_timeout.get() {
+            if self.alarm.has_expired(reference, dt, now) {
+                self.run_step(reference.wrapping_add(dt) & self.alarm.max_tics());
+            }
+        }
+
+        self.set_next_alarm();
+    }
+}
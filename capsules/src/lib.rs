@@ -15,15 +15,22 @@ pub mod app_flash_driver;
 pub mod ble_advertising_driver;
 pub mod button;
 pub mod console;
+pub mod crash_log;
 pub mod crc;
 pub mod dac;
+pub mod date_time;
+pub mod debounced_button;
+pub mod digest;
+pub mod drbg;
 pub mod fm25cl;
 pub mod fxos8700cq;
 pub mod gpio;
 pub mod gpio_async;
+pub mod i2c_master;
 pub mod i2c_master_slave_driver;
 pub mod isl29035;
 pub mod led;
+pub mod led_pattern;
 pub mod lps25hb;
 pub mod ltc294x;
 pub mod max17205;
@@ -33,11 +40,19 @@ pub mod nonvolatile_storage_driver;
 pub mod nonvolatile_to_pages;
 pub mod nrf51822_serialization;
 pub mod pca9544a;
+pub mod process_info;
+pub mod pwm;
 pub mod rf233;
 pub mod rf233_const;
 pub mod rng;
 pub mod sdcard;
+pub mod sensor_sampler;
 pub mod si7021;
+pub mod software_aes;
+pub mod software_crc;
+pub mod software_date_time;
+pub mod software_pwm;
+pub mod software_sha256;
 pub mod spi;
 pub mod tmp006;
 pub mod tsl2561;
@@ -47,9 +62,11 @@ pub mod usbc_client;
 pub mod virtual_alarm;
 pub mod virtual_flash;
 pub mod virtual_i2c;
+pub mod virtual_pwm;
 pub mod virtual_spi;
 #[macro_use]
 pub mod net;
+pub mod aes;
 pub mod aes_ccm;
 pub mod humidity;
 pub mod ieee802154;
@@ -0,0 +1,172 @@
+//! Provides userspace with the kernel's accounting of every process, so that
+//! an application can act like `top`.
+//!
+//! The statistics are copied into a buffer shared by the application as
+//! little endian 32-bit words:
+//!
+//! ```text
+//! 0: CPU time in microseconds, low word
+//! 1: CPU time in microseconds, high word
+//! 2: timeslice expirations
+//! 3: yields
+//! 4: syscalls
+//! 5: dropped callbacks
+//! 6: restarts
+//! 7: subscribe, command and allow calls to drivers not listed below
+//! 8..: (driver number, call count) pairs, for drivers with a non-zero count
+//! ```
+//!
+//! Setup
+//! -----
+//!
+//! ```rust
+//! let process_info = static_init!(
+//!     capsules::process_info::ProcessInfo,
+//!     capsules::process_info::ProcessInfo::new(kernel::Grant::create())
+//! );
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```c
+//! allow(PROCESS_INFO_DRIVER, 0, buffer, sizeof(buffer));
+//! int slots = command(PROCESS_INFO_DRIVER, 1, 0, 0);
+//! int len = command(PROCESS_INFO_DRIVER, 2, process_index, 0);
+//! ```
+
+use kernel::procs;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x10002;
+
+/// Number of words before the per-driver counts.
+const HEADER_WORDS: usize = 8;
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct ProcessInfo {
+    apps: Grant<App>,
+}
+
+impl ProcessInfo {
+    pub fn new(apps: Grant<App>) -> ProcessInfo {
+        ProcessInfo { apps: apps }
+    }
+
+    fn copy_stats(&self, app_idx: usize, buffer: &mut [u8]) -> ReturnCode {
+        let stats = match procs::process_stats(app_idx) {
+            Some(stats) => stats,
+            None => return ReturnCode::ENOAPP,
+        };
+
+        let mut words = [0u32; HEADER_WORDS + 2 * procs::DRIVER_SYSCALL_SLOTS];
+        words[0] = stats.cpu_time_us as u32;
+        words[1] = (stats.cpu_time_us >> 32) as u32;
+        words[2] = stats.timeslice_expirations as u32;
+        words[3] = stats.yields as u32;
+        words[4] = stats.syscalls as u32;
+        words[5] = stats.dropped_callbacks as u32;
+        words[6] = stats.restarts as u32;
+        words[7] = stats.other_driver_syscalls as u32;
+        let mut len = HEADER_WORDS;
+        for &(driver_num, count) in stats.driver_syscalls.iter() {
+            if count > 0 {
+                words[len] = driver_num as u32;
+                words[len + 1] = count as u32;
+                len += 2;
+            }
+        }
+
+        if buffer.len() < 4 * len {
+            return ReturnCode::ESIZE;
+        }
+        for (i, word) in words[..len].iter().enumerate() {
+            for j in 0..4 {
+                buffer[4 * i + j] = (word >> (8 * j)) as u8;
+            }
+        }
+        ReturnCode::SuccessWithValue { value: 4 * len }
+    }
+
+    fn copy_name(&self, app_idx: usize, buffer: &mut [u8]) -> ReturnCode {
+        match procs::process_name(app_idx) {
+            Some(name) => {
+                if buffer.len() < name.len() {
+                    return ReturnCode::ESIZE;
+                }
+                buffer[..name.len()].copy_from_slice(name.as_bytes());
+                ReturnCode::SuccessWithValue { value: name.len() }
+            }
+            None => ReturnCode::ENOAPP,
+        }
+    }
+}
+
+impl Driver for ProcessInfo {
+    /// Share a buffer receiving process information.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: The buffer filled by commands `2` and `3`.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(&self, _: usize, _: Option<Callback>, _: AppId) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Query process information. Processes are identified by their index,
+    /// from zero to the number of process slots.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Return the number of process slots. Slots may be empty.
+    /// - `2`: Copy the statistics of process `data` into the buffer and
+    ///        return the number of bytes written.
+    /// - `3`: Copy the name of process `data` into the buffer and return its
+    ///        length.
+    ///
+    /// Commands `2` and `3` return `ENOAPP` if the slot is empty and `ESIZE`
+    /// if the buffer is too short.
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: procs::number_of_process_slots(),
+            },
+            2 | 3 => self.apps
+                .enter(appid, |app, _| match app.buffer {
+                    Some(ref mut buffer) => {
+                        if command_num == 2 {
+                            self.copy_stats(data, buffer.as_mut())
+                        } else {
+                            self.copy_name(data, buffer.as_mut())
+                        }
+                    }
+                    None => ReturnCode::EINVAL,
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
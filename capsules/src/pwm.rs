@@ -0,0 +1,179 @@
+//! Provides userspace with PWM outputs.
+//!
+//! The board may assign pins to processes by package name, in which case no
+//! other process may use them and gets `EPERM`. Any other pin belongs to the
+//! process that starts a signal on it until that process stops it, so that
+//! processes do not disturb each other's signals. Other processes get `EBUSY`
+//! until then. If the process exits first, the pin keeps its signal until
+//! another process takes it over.
+//!
+//! Setup
+//! -----
+//!
+//! Up to 32 pins, all of the same type:
+//!
+//! ```rust
+//! type PwmPin = SoftwarePwmPin<'static, sam4l::gpio::GPIOPin, VirtualMuxAlarm<'static, Ast>>;
+//!
+//! let pwm_pins = static_init!([&'static PwmPin; 1], [pwm_pin]);
+//! let pwm = static_init!(
+//!     capsules::pwm::Pwm<'static, PwmPin>,
+//!     capsules::pwm::Pwm::new(pwm_pins, kernel::Grant::create())
+//! );
+//!
+//! // Optionally, assign pins to processes
+//! let pwm_owners = static_init!([Option<&'static str>; 1], [Some("dimmer")]);
+//! pwm.set_owners(pwm_owners);
+//! ```
+//!
+//! Usage
+//! -----
+//!
+//! ```c
+//! int max_duty = command(PWM_DRIVER, 4, pin, 0);
+//! // 1 kHz at a quarter duty cycle
+//! command(PWM_DRIVER, 1, pin | (max_duty / 4) << 8, 1000);
+//! command(PWM_DRIVER, 2, pin, 0);
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::pwm::PwmPin;
+use kernel::procs;
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x00008;
+
+#[derive(Default)]
+pub struct App {
+    /// Pins started by the process, one bit per pin
+    pins: u32,
+}
+
+pub struct Pwm<'a, P: PwmPin + 'a> {
+    pins: &'a [&'a P],
+    owners: Cell<Option<&'a [Option<&'a str>]>>,
+    apps: Grant<App>,
+}
+
+impl<'a, P: PwmPin> Pwm<'a, P> {
+    pub fn new(pins: &'a [&'a P], apps: Grant<App>) -> Pwm<'a, P> {
+        assert!(pins.len() <= 32);
+        Pwm {
+            pins: pins,
+            owners: Cell::new(None),
+            apps: apps,
+        }
+    }
+
+    /// Assign pins to processes. `owners[i]` is the package name of the
+    /// process that owns pin `i`, or `None` if any process may use it.
+    pub fn set_owners(&self, owners: &'a [Option<&'a str>]) {
+        self.owners.set(Some(owners));
+    }
+
+    /// Whether the board lets the process use `pin`.
+    fn is_permitted(&self, pin: usize, appid: AppId) -> bool {
+        let owner = self.owners
+            .get()
+            .and_then(|owners| owners.get(pin).cloned())
+            .unwrap_or(None);
+        owner.map_or(true, |name| procs::process_name(appid.idx()) == Some(name))
+    }
+
+    /// The process that started a signal on `pin`, if it still exists.
+    fn owner(&self, pin: usize) -> Option<AppId> {
+        let mut owner = None;
+        for app in self.apps.iter() {
+            app.enter(|app, _| {
+                if app.pins & (1 << pin) != 0 {
+                    owner = Some(app.appid());
+                }
+            });
+        }
+        owner
+    }
+
+    fn start(
+        &self,
+        pin: usize,
+        frequency_hz: usize,
+        duty_cycle: usize,
+        appid: AppId,
+    ) -> ReturnCode {
+        if !self.is_permitted(pin, appid) {
+            return ReturnCode::EPERM;
+        }
+        if self.owner(pin).map_or(false, |owner| owner != appid) {
+            return ReturnCode::EBUSY;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                let res = self.pins[pin].start(frequency_hz, duty_cycle);
+                if res == ReturnCode::SUCCESS {
+                    app.pins |= 1 << pin;
+                }
+                res
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn stop(&self, pin: usize, appid: AppId) -> ReturnCode {
+        if !self.is_permitted(pin, appid) {
+            return ReturnCode::EPERM;
+        }
+        if self.owner(pin).map_or(false, |owner| owner != appid) {
+            return ReturnCode::EBUSY;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                app.pins &= !(1 << pin);
+                self.pins[pin].stop()
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl<'a, P: PwmPin> Driver for Pwm<'a, P> {
+    fn subscribe(&self, _: usize, _: Option<Callback>, _: AppId) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Control the PWM pins.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check. Returns the number of pins.
+    /// - `1`: Start a signal on pin `data & 0xff` with duty cycle `data >> 8`
+    ///        and frequency `arg2` in hertz. Returns `EINVAL` if the frequency
+    ///        or duty cycle is out of range.
+    /// - `2`: Stop the signal on pin `data`, leaving it low.
+    /// - `3`: Return the highest frequency of pin `data`, in hertz.
+    /// - `4`: Return the duty cycle of pin `data` for which it is always
+    ///        high.
+    ///
+    /// All commands but `0` return `EINVAL` if there is no such pin.
+    /// Commands `1` and `2` return `EPERM` if the board assigned the pin to
+    /// another process, and `EBUSY` if another process has a signal on it.
+    fn command(&self, command_num: usize, data: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        let pin = if command_num == 1 { data & 0xff } else { data };
+        if command_num != 0 && pin >= self.pins.len() {
+            return ReturnCode::EINVAL;
+        }
+
+        match command_num {
+            0 => ReturnCode::SuccessWithValue {
+                value: self.pins.len(),
+            },
+            1 => self.start(pin, arg2, data >> 8, appid),
+            2 => self.stop(pin, appid),
+            3 => ReturnCode::SuccessWithValue {
+                value: self.pins[pin].get_maximum_frequency_hz(),
+            },
+            4 => ReturnCode::SuccessWithValue {
+                value: self.pins[pin].get_maximum_duty_cycle(),
+            },
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
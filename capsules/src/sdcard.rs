@@ -154,14 +154,12 @@ enum AlarmState {
     WaitForWriteBusy,
 }
 
-/// Error codes returned if an SD card transaction fails
+/// The operation an error is reported for
 #[derive(Clone, Copy, Debug, PartialEq)]
-enum ErrorCode {
-    CardStateChanged = -1,
-    InitializationFailure = -2,
-    ReadFailure = -3,
-    WriteFailure = -4,
-    TimeoutFailure = -5,
+pub enum Operation {
+    Initialize = 0,
+    Read = 1,
+    Write = 2,
 }
 
 /// SD card types, determined during initialization
@@ -185,7 +183,10 @@ pub trait SDCardClient {
     fn init_done(&self, block_size: u32, total_size: u64);
     fn read_done(&self, data: &'static mut [u8], len: usize);
     fn write_done(&self, buffer: &'static mut [u8]);
-    fn error(&self, error: u32);
+    /// An operation failed. `ECANCEL` if the card was inserted or removed
+    /// during the operation, `ETIMEOUT` if the card stopped responding and
+    /// `FAIL` if it reported an error.
+    fn error(&self, error: ReturnCode, operation: Operation);
 }
 
 /// Functions for initializing and accessing an SD card
@@ -445,7 +446,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::InitializationFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Initialize);
                     });
                 }
             }
@@ -516,7 +517,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::InitializationFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Initialize);
                     });
                 }
             }
@@ -545,7 +546,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::InitializationFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Initialize);
                     });
                 }
             }
@@ -613,7 +614,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::InitializationFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Initialize);
                     });
                 }
             }
@@ -652,7 +653,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::InitializationFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Initialize);
                     });
                 }
             }
@@ -675,7 +676,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::InitializationFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Initialize);
                     });
                 }
             }
@@ -737,7 +738,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::InitializationFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Initialize);
                     });
                 }
             }
@@ -764,7 +765,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::ReadFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Read);
                     });
                 }
             }
@@ -795,7 +796,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::ReadFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Read);
                     });
                 }
             }
@@ -854,7 +855,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::ReadFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Read);
                     });
                 }
             }
@@ -916,7 +917,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::ReadFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Read);
                     });
                 }
             }
@@ -969,7 +970,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                         self.alarm_state.set(AlarmState::Idle);
                         self.alarm_count.set(0);
                         self.client.get().map(move |client| {
-                            client.error(ErrorCode::WriteFailure as u32);
+                            client.error(ReturnCode::FAIL, Operation::Write);
                         });
                     }
                 } else {
@@ -980,7 +981,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::WriteFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Write);
                     });
                 }
             }
@@ -1004,7 +1005,7 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
                     self.alarm_state.set(AlarmState::Idle);
                     self.alarm_count.set(0);
                     self.client.get().map(move |client| {
-                        client.error(ErrorCode::WriteFailure as u32);
+                        client.error(ReturnCode::FAIL, Operation::Write);
                     });
                 }
             }
@@ -1047,17 +1048,45 @@ impl<'a, A: hil::time::Alarm + 'a> SDCard<'a, A> {
         }
     }
 
+    /// the operation in progress, from the state of the card
+    fn operation(&self) -> Operation {
+        match self.state.get() {
+            SpiState::StartReadBlocks { .. }
+            | SpiState::WaitReadBlock
+            | SpiState::ReadBlockComplete
+            | SpiState::WaitReadBlocks { .. }
+            | SpiState::ReceivedBlock { .. }
+            | SpiState::ReadBlocksComplete => Operation::Read,
+
+            SpiState::StartWriteBlocks { .. }
+            | SpiState::WriteBlockResponse
+            | SpiState::WriteBlockBusy
+            | SpiState::WaitWriteBlockBusy => Operation::Write,
+
+            SpiState::Idle => match self.alarm_state.get() {
+                AlarmState::WaitForDataBlock | AlarmState::WaitForDataBlocks { .. } => {
+                    Operation::Read
+                }
+                AlarmState::WaitForWriteBusy => Operation::Write,
+                _ => Operation::Initialize,
+            },
+
+            _ => Operation::Initialize,
+        }
+    }
+
     /// updates SD card state upon timer alarm fired
     fn process_alarm_states(&self) {
         // keep track of how many times the alarm has been called in a row
         let repeats = self.alarm_count.get();
         if repeats > 100 {
             // error, send callback and quit
+            let operation = self.operation();
             self.state.set(SpiState::Idle);
             self.alarm_state.set(AlarmState::Idle);
             self.alarm_count.set(0);
             self.client.get().map(move |client| {
-                client.error(ErrorCode::TimeoutFailure as u32);
+                client.error(ReturnCode::ETIMEOUT, operation);
             });
         } else {
             self.alarm_count.set(repeats + 1);
@@ -1357,10 +1386,11 @@ impl<'a, A: hil::time::Alarm + 'a> hil::gpio::Client for SDCard<'a, A> {
         if self.alarm_state.get() != AlarmState::Idle || self.state.get() != SpiState::Idle {
             // something was running when this occurred. Kill the transaction and
             //  send an error callback
+            let operation = self.operation();
             self.state.set(SpiState::Idle);
             self.alarm_state.set(AlarmState::Idle);
             self.client.get().map(move |client| {
-                client.error(ErrorCode::CardStateChanged as u32);
+                client.error(ReturnCode::ECANCEL, operation);
             });
         }
 
@@ -1485,10 +1515,10 @@ impl<'a, A: hil::time::Alarm + 'a> SDCardClient for SDCardDriver<'a, A> {
         });
     }
 
-    fn error(&self, error: u32) {
+    fn error(&self, error: ReturnCode, operation: Operation) {
         self.app.map(|app| {
             app.callback.map(|mut cb| {
-                cb.schedule(4, error as usize, 0);
+                cb.schedule(4, usize::from(error), operation as usize);
             });
         });
     }
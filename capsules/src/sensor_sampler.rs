@@ -0,0 +1,622 @@
+//! Samples sensors periodically on behalf of processes and delivers the
+//! readings in batches.
+//!
+//! A process asks for a sensor to be read every so many milliseconds and to
+//! be woken up once a given number of readings has been collected in a
+//! buffer it shares with the kernel, instead of waking up for every sample.
+//! When the schedules of several processes fall due together they share a
+//! single read of the sensor.
+//!
+//! The sampler sits between the sensor drivers and the one-shot sensor
+//! capsules: it implements the `hil::sensors` traits itself and forwards
+//! one-shot reads to the drivers, so `TemperatureSensor`, `HumiditySensor`,
+//! `AmbientLight` and `NineDof` keep working on top of it. Reads of all
+//! sensors are done one at a time.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let sensor_sampler_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let sensor_sampler = static_init!(
+//!     capsules::sensor_sampler::SensorSampler<
+//!         'static,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::sensor_sampler::SensorSampler::new(
+//!         sensor_sampler_alarm,
+//!         Some(si7021),
+//!         Some(si7021),
+//!         Some(isl29035),
+//!         Some(fxos8700),
+//!         kernel::Grant::create()
+//!     )
+//! );
+//! sensor_sampler_alarm.set_client(sensor_sampler);
+//! hil::sensors::TemperatureDriver::set_client(si7021, sensor_sampler);
+//! hil::sensors::HumidityDriver::set_client(si7021, sensor_sampler);
+//! hil::sensors::AmbientLight::set_client(isl29035, sensor_sampler);
+//! hil::sensors::NineDof::set_client(fxos8700, sensor_sampler);
+//!
+//! // The one-shot capsules are then built on `sensor_sampler`, e.g.
+//! let temp = static_init!(
+//!     capsules::temperature::TemperatureSensor<'static>,
+//!     capsules::temperature::TemperatureSensor::new(sensor_sampler, kernel::Grant::create())
+//! );
+//! hil::sensors::TemperatureDriver::set_client(sensor_sampler, temp);
+//! ```
+//!
+//! Syscall Interface
+//! -----------------
+//!
+//! Sensors are identified by number: `0` temperature, `1` humidity, `2`
+//! ambient light, `3` accelerometer, `4` magnetometer and `5` gyroscope.
+//! A reading is one 32-bit little-endian word, in the units of the one-shot
+//! driver for that sensor, or three words (x, y, z) for the last three.
+//!
+//! ### Command
+//!
+//! - `0`: Driver check. Returns a bitmask of the sensors present.
+//! - `1`: Sample sensor `data1 & 0xff` every `data2` milliseconds, starting
+//!   now, and deliver a batch of `data1 >> 8` readings. Replaces any
+//!   schedule the process had for that sensor. Returns `ENODEVICE` if the
+//!   sensor is not present and `ESIZE` if the buffer allowed for it cannot
+//!   hold a batch.
+//! - `2`: Stop sampling sensor `data1`, delivering any readings collected.
+//! - `3`: Deliver the readings collected for sensor `data1` now, without
+//!   waiting for the batch to fill.
+//!
+//! Commands `2` and `3` return `EALREADY` if the sensor is not being sampled.
+//!
+//! ### Allow
+//!
+//! - `n`: The buffer readings of sensor `n` are stored in. Each batch starts
+//!   at the beginning of the buffer.
+//!
+//! ### Subscribe
+//!
+//! - `0`: Callback when a batch is delivered. The arguments are the sensor
+//!   and the number of readings in the buffer.
+
+use core::cell::Cell;
+use kernel::hil::sensors;
+use kernel::hil::time::{self, Alarm, Alarm64, Frequency};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+/// Syscall driver number.
+pub const DRIVER_NUM: usize = 0x60005;
+
+/// The number of sensors the sampler knows about.
+pub const NUM_SENSORS: usize = 6;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sensor {
+    Temperature = 0,
+    Humidity = 1,
+    AmbientLight = 2,
+    Accelerometer = 3,
+    Magnetometer = 4,
+    Gyroscope = 5,
+}
+
+const SENSORS: [Sensor; NUM_SENSORS] = [
+    Sensor::Temperature,
+    Sensor::Humidity,
+    Sensor::AmbientLight,
+    Sensor::Accelerometer,
+    Sensor::Magnetometer,
+    Sensor::Gyroscope,
+];
+
+impl Sensor {
+    fn from_id(id: usize) -> Option<Sensor> {
+        SENSORS.get(id).cloned()
+    }
+
+    fn bit(self) -> usize {
+        1 << self as usize
+    }
+
+    /// The size of one reading in the process's buffer, in bytes.
+    fn reading_len(self) -> usize {
+        match self {
+            Sensor::Temperature | Sensor::Humidity | Sensor::AmbientLight => 4,
+            Sensor::Accelerometer | Sensor::Magnetometer | Sensor::Gyroscope => 12,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Schedule {
+    /// In alarm tics
+    period: u64,
+    /// When the next sample is due
+    next: u64,
+    /// Readings per batch
+    batch: usize,
+    /// Readings collected so far
+    count: usize,
+    /// Whether the process is waiting for the next read of the sensor
+    waiting: bool,
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffers: [Option<AppSlice<Shared, u8>>; NUM_SENSORS],
+    schedules: [Option<Schedule>; NUM_SENSORS],
+}
+
+pub struct SensorSampler<'a, A: Alarm + Alarm64 + 'a> {
+    alarm: &'a A,
+    temperature: Option<&'a sensors::TemperatureDriver>,
+    humidity: Option<&'a sensors::HumidityDriver>,
+    ambient_light: Option<&'a sensors::AmbientLight>,
+    ninedof: Option<&'a sensors::NineDof>,
+    temperature_client: Cell<Option<&'static sensors::TemperatureClient>>,
+    humidity_client: Cell<Option<&'static sensors::HumidityClient>>,
+    ambient_light_client: Cell<Option<&'static sensors::AmbientLightClient>>,
+    ninedof_client: Cell<Option<&'static sensors::NineDofClient>>,
+    /// The sensor being read
+    reading: Cell<Option<Sensor>>,
+    /// Bitmask of sensors waiting to be read
+    pending: Cell<usize>,
+    /// Bitmask of sensors whose next reading goes to the one-shot clients
+    forward: Cell<usize>,
+    apps: Grant<App>,
+}
+
+impl<'a, A: Alarm + Alarm64> SensorSampler<'a, A> {
+    pub fn new(
+        alarm: &'a A,
+        temperature: Option<&'a sensors::TemperatureDriver>,
+        humidity: Option<&'a sensors::HumidityDriver>,
+        ambient_light: Option<&'a sensors::AmbientLight>,
+        ninedof: Option<&'a sensors::NineDof>,
+        grant: Grant<App>,
+    ) -> SensorSampler<'a, A> {
+        SensorSampler {
+            alarm: alarm,
+            temperature: temperature,
+            humidity: humidity,
+            ambient_light: ambient_light,
+            ninedof: ninedof,
+            temperature_client: Cell::new(None),
+            humidity_client: Cell::new(None),
+            ambient_light_client: Cell::new(None),
+            ninedof_client: Cell::new(None),
+            reading: Cell::new(None),
+            pending: Cell::new(0),
+            forward: Cell::new(0),
+            apps: grant,
+        }
+    }
+
+    fn is_present(&self, sensor: Sensor) -> bool {
+        match sensor {
+            Sensor::Temperature => self.temperature.is_some(),
+            Sensor::Humidity => self.humidity.is_some(),
+            Sensor::AmbientLight => self.ambient_light.is_some(),
+            Sensor::Accelerometer | Sensor::Magnetometer | Sensor::Gyroscope => {
+                self.ninedof.is_some()
+            }
+        }
+    }
+
+    fn ms_to_tics(&self, ms: usize) -> u64 {
+        ms as u64 * A::Frequency::frequency() as u64 / 1000
+    }
+
+    /// Start a read of `sensor` on its driver.
+    fn read(&self, sensor: Sensor) -> ReturnCode {
+        let result = match sensor {
+            Sensor::Temperature => self.temperature
+                .map_or(ReturnCode::ENODEVICE, |driver| driver.read_temperature()),
+            Sensor::Humidity => self.humidity
+                .map_or(ReturnCode::ENODEVICE, |driver| driver.read_humidity()),
+            Sensor::AmbientLight => self.ambient_light
+                .map_or(ReturnCode::ENODEVICE, |driver| driver.read_light_intensity()),
+            Sensor::Accelerometer => self.ninedof
+                .map_or(ReturnCode::ENODEVICE, |driver| driver.read_accelerometer()),
+            Sensor::Magnetometer => self.ninedof
+                .map_or(ReturnCode::ENODEVICE, |driver| driver.read_magnetometer()),
+            Sensor::Gyroscope => self.ninedof
+                .map_or(ReturnCode::ENODEVICE, |driver| driver.read_gyroscope()),
+        };
+        if result == ReturnCode::SUCCESS {
+            self.reading.set(Some(sensor));
+        }
+        result
+    }
+
+    /// Start reading the next pending sensor, if no read is in progress.
+    /// Processes waiting on a sensor that cannot be read miss that sample,
+    /// and a one-shot read queued for it is dropped.
+    fn read_next(&self) {
+        while self.reading.get().is_none() && self.pending.get() != 0 {
+            let sensor = match SENSORS
+                .iter()
+                .find(|sensor| self.pending.get() & sensor.bit() != 0)
+            {
+                Some(&sensor) => sensor,
+                None => return,
+            };
+            self.pending.set(self.pending.get() & !sensor.bit());
+            if self.read(sensor) != ReturnCode::SUCCESS {
+                self.forward.set(self.forward.get() & !sensor.bit());
+                self.apps.each(|app| {
+                    app.schedules[sensor as usize]
+                        .as_mut()
+                        .map(|schedule| schedule.waiting = false);
+                });
+            }
+        }
+    }
+
+    /// Queue a one-shot read for the clients of the sampler. It is served by
+    /// a read already in progress if there is one.
+    fn read_for_client(&self, sensor: Sensor) -> ReturnCode {
+        if !self.is_present(sensor) {
+            return ReturnCode::ENODEVICE;
+        }
+        if self.reading.get().is_none() {
+            let result = self.read(sensor);
+            if result == ReturnCode::SUCCESS {
+                self.forward.set(self.forward.get() | sensor.bit());
+            }
+            return result;
+        }
+        self.forward.set(self.forward.get() | sensor.bit());
+        if self.reading.get() != Some(sensor) {
+            self.pending.set(self.pending.get() | sensor.bit());
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Set the alarm for the soonest sample due of any process.
+    fn reset_alarm(&self) {
+        let mut next: Option<u64> = None;
+        for app in self.apps.iter() {
+            app.enter(|app, _| {
+                for schedule in app.schedules.iter().filter_map(|schedule| *schedule) {
+                    if next.map_or(true, |next| schedule.next < next) {
+                        next = Some(schedule.next);
+                    }
+                }
+            });
+        }
+        match next {
+            Some(when) => self.alarm.set_alarm64(when, 0),
+            None => self.alarm.disable(),
+        }
+    }
+
+    /// Pass a completed read to the processes waiting on it and to the
+    /// one-shot clients, then start the next read.
+    fn complete(&self, values: [usize; 3]) {
+        let sensor = match self.reading.take() {
+            Some(sensor) => sensor,
+            None => return,
+        };
+
+        if self.forward.get() & sensor.bit() != 0 {
+            self.forward.set(self.forward.get() & !sensor.bit());
+            match sensor {
+                Sensor::Temperature => self.temperature_client
+                    .get()
+                    .map(|client| client.callback(values[0])),
+                Sensor::Humidity => self.humidity_client
+                    .get()
+                    .map(|client| client.callback(values[0])),
+                Sensor::AmbientLight => self.ambient_light_client
+                    .get()
+                    .map(|client| client.callback(values[0])),
+                Sensor::Accelerometer | Sensor::Magnetometer | Sensor::Gyroscope => self
+                    .ninedof_client
+                    .get()
+                    .map(|client| client.callback(values[0], values[1], values[2])),
+            };
+        }
+
+        let len = sensor.reading_len();
+        self.apps.each(|app| {
+            let App {
+                callback,
+                ref mut buffers,
+                ref mut schedules,
+            } = **app;
+            let buffer = &mut buffers[sensor as usize];
+            let schedule = match schedules[sensor as usize].as_mut() {
+                Some(schedule) => schedule,
+                None => return,
+            };
+            if !schedule.waiting {
+                return;
+            }
+            schedule.waiting = false;
+
+            let offset = schedule.count * len;
+            let stored = buffer.as_mut().map_or(false, |buffer| {
+                if buffer.len() < offset + len {
+                    return false;
+                }
+                let words = buffer.as_mut()[offset..offset + len].chunks_mut(4);
+                for (word, &value) in words.zip(values.iter()) {
+                    let value = value as u32;
+                    word[0] = value as u8;
+                    word[1] = (value >> 8) as u8;
+                    word[2] = (value >> 16) as u8;
+                    word[3] = (value >> 24) as u8;
+                }
+                true
+            });
+            if stored {
+                schedule.count += 1;
+            }
+            if schedule.count >= schedule.batch
+                || (!stored && schedule.count > 0)
+            {
+                // The batch is full, or the buffer shrank and cannot hold
+                // another reading
+                callback.map(|mut cb| cb.schedule(sensor as usize, schedule.count, 0));
+                schedule.count = 0;
+            }
+        });
+
+        self.read_next();
+    }
+
+    fn start(&self, appid: AppId, sensor: usize, batch: usize, period_ms: usize) -> ReturnCode {
+        let sensor = match Sensor::from_id(sensor) {
+            Some(sensor) => sensor,
+            None => return ReturnCode::EINVAL,
+        };
+        if !self.is_present(sensor) {
+            return ReturnCode::ENODEVICE;
+        }
+        let period = self.ms_to_tics(period_ms);
+        if batch == 0 || period == 0 {
+            return ReturnCode::EINVAL;
+        }
+
+        let now = self.alarm.now64();
+        let result = self.apps
+            .enter(appid, |app, _| {
+                let len = app.buffers[sensor as usize]
+                    .as_ref()
+                    .map_or(0, |buffer| buffer.len());
+                if len < batch * sensor.reading_len() {
+                    return ReturnCode::ESIZE;
+                }
+                app.schedules[sensor as usize] = Some(Schedule {
+                    period: period,
+                    next: now,
+                    batch: batch,
+                    count: 0,
+                    waiting: false,
+                });
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+
+        if result == ReturnCode::SUCCESS {
+            self.reset_alarm();
+        }
+        result
+    }
+
+    /// Deliver the readings collected for `sensor`, and stop sampling it if
+    /// `stop` is set.
+    fn flush(&self, appid: AppId, sensor: usize, stop: bool) -> ReturnCode {
+        if sensor >= NUM_SENSORS {
+            return ReturnCode::EINVAL;
+        }
+        let result = self.apps
+            .enter(appid, |app, _| {
+                let callback = app.callback;
+                let slot = &mut app.schedules[sensor];
+                let count = match slot.as_mut() {
+                    Some(schedule) => {
+                        let count = schedule.count;
+                        schedule.count = 0;
+                        count
+                    }
+                    None => return ReturnCode::EALREADY,
+                };
+                if stop {
+                    *slot = None;
+                }
+                if count > 0 {
+                    callback.map(|mut cb| cb.schedule(sensor, count, 0));
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+
+        if result == ReturnCode::SUCCESS && stop {
+            self.reset_alarm();
+        }
+        result
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> time::Client for SensorSampler<'a, A> {
+    fn fired(&self) {
+        let now = self.alarm.now64();
+        let reading = self.reading.get();
+        let due = Cell::new(0);
+        self.apps.each(|app| {
+            for (id, slot) in app.schedules.iter_mut().enumerate() {
+                let schedule = match slot.as_mut() {
+                    Some(schedule) => schedule,
+                    None => continue,
+                };
+                if schedule.next > now {
+                    continue;
+                }
+
+                // Reschedule relative to the deadline, skipping any samples
+                // that were missed entirely.
+                let missed = (now - schedule.next) / schedule.period;
+                schedule.next += (missed + 1) * schedule.period;
+                schedule.waiting = true;
+                due.set(due.get() | 1 << id);
+            }
+        });
+
+        // A read of a sensor already in progress serves everyone now due
+        for sensor in SENSORS.iter() {
+            if due.get() & sensor.bit() != 0 && reading != Some(*sensor) {
+                self.pending.set(self.pending.get() | sensor.bit());
+            }
+        }
+        self.read_next();
+        self.reset_alarm();
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::TemperatureClient for SensorSampler<'a, A> {
+    fn callback(&self, value: usize) {
+        self.complete([value, 0, 0]);
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::HumidityClient for SensorSampler<'a, A> {
+    fn callback(&self, value: usize) {
+        self.complete([value, 0, 0]);
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::AmbientLightClient for SensorSampler<'a, A> {
+    fn callback(&self, lux: usize) {
+        self.complete([lux, 0, 0]);
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::NineDofClient for SensorSampler<'a, A> {
+    fn callback(&self, arg1: usize, arg2: usize, arg3: usize) {
+        self.complete([arg1, arg2, arg3]);
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::TemperatureDriver for SensorSampler<'a, A> {
+    fn set_client(&self, client: &'static sensors::TemperatureClient) {
+        self.temperature_client.set(Some(client));
+    }
+
+    fn read_temperature(&self) -> ReturnCode {
+        self.read_for_client(Sensor::Temperature)
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::HumidityDriver for SensorSampler<'a, A> {
+    fn set_client(&self, client: &'static sensors::HumidityClient) {
+        self.humidity_client.set(Some(client));
+    }
+
+    fn read_humidity(&self) -> ReturnCode {
+        self.read_for_client(Sensor::Humidity)
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::AmbientLight for SensorSampler<'a, A> {
+    fn set_client(&self, client: &'static sensors::AmbientLightClient) {
+        self.ambient_light_client.set(Some(client));
+    }
+
+    fn read_light_intensity(&self) -> ReturnCode {
+        self.read_for_client(Sensor::AmbientLight)
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> sensors::NineDof for SensorSampler<'a, A> {
+    fn set_client(&self, client: &'static sensors::NineDofClient) {
+        self.ninedof_client.set(Some(client));
+    }
+
+    fn read_accelerometer(&self) -> ReturnCode {
+        self.read_for_client(Sensor::Accelerometer)
+    }
+
+    fn read_magnetometer(&self) -> ReturnCode {
+        self.read_for_client(Sensor::Magnetometer)
+    }
+
+    fn read_gyroscope(&self) -> ReturnCode {
+        self.read_for_client(Sensor::Gyroscope)
+    }
+}
+
+impl<'a, A: Alarm + Alarm64> Driver for SensorSampler<'a, A> {
+    /// Setup shared buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `n`: The buffer readings of sensor `n` are stored in.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        if allow_num >= NUM_SENSORS {
+            return ReturnCode::ENOSUPPORT;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                app.buffers[allow_num] = slice;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Setup callbacks.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Callback when a batch of readings is delivered.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self.apps
+                .enter(appid, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Control sampling.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check, returns a bitmask of the sensors present.
+    /// - `1`: Sample sensor `data1 & 0xff` every `data2` ms, in batches of
+    ///        `data1 >> 8` readings.
+    /// - `2`: Stop sampling sensor `data1`.
+    /// - `3`: Deliver the readings collected for sensor `data1`.
+    fn command(&self, command_num: usize, data1: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => {
+                let present = SENSORS
+                    .iter()
+                    .filter(|&&sensor| self.is_present(sensor))
+                    .fold(0, |mask, sensor| mask | sensor.bit());
+                ReturnCode::SuccessWithValue { value: present }
+            }
+            1 => self.start(appid, data1 & 0xff, data1 >> 8, data2),
+            2 => self.flush(appid, data1, true),
+            3 => self.flush(appid, data1, false),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
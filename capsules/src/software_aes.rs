@@ -0,0 +1,406 @@
+//! Software implementation of the AES-128 symmetric encryption interface.
+//!
+//! This provides `AES128`, `AES128Ctr`, `AES128CBC` and `AES128ECB` for chips
+//! without an AES engine (e.g. the CC26xx and TM4C families, or the nRF51 when
+//! its ECB block is in use elsewhere), so that `aes_ccm` and the 802.15.4
+//! `Framer` can be used on them.
+//!
+//! The cipher avoids secret-dependent table lookups and branches: the S-box is
+//! computed as the inverse in GF(2^8) (by exponentiation) followed by the
+//! affine transform, four bytes at a time packed in a `u32`. This is slower
+//! than a table-driven implementation, but its timing does not depend on the
+//! key or the data.
+//!
+//! `crypt()` only records the request; the blocks are processed, and the
+//! client called, from a deferred call, matching the asynchronous behavior of
+//! hardware engines.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let aes = static_init!(
+//!     capsules::software_aes::SoftwareAes128<'static>,
+//!     capsules::software_aes::SoftwareAes128::new()
+//! );
+//! aes.initialize_deferred_call(
+//!     dynamic_deferred_call::register(aes).expect("no deferred call for software_aes"),
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::common::dynamic_deferred_call::{DeferredCallClient, DeferredCallHandle};
+use kernel::hil::symmetric_encryption::{self, AES128, AES128CBC, AES128Ctr, AES128ECB,
+                                        AES128_BLOCK_SIZE, AES128_KEY_SIZE};
+use kernel::ReturnCode;
+
+const ROUNDS: usize = 10;
+const ROUND_KEYS_LEN: usize = (ROUNDS + 1) * AES128_BLOCK_SIZE;
+
+/// Double each byte packed in `x` in GF(2^8).
+fn xtime(x: u32) -> u32 {
+    ((x & 0x7f7f7f7f) << 1) ^ (((x >> 7) & 0x01010101) * 0x1b)
+}
+
+/// Multiply the bytes packed in `a` and `b` pairwise in GF(2^8).
+fn gf_mul(a: u32, b: u32) -> u32 {
+    let mut a = a;
+    let mut r = 0;
+    for i in 0..8 {
+        r ^= a & (((b >> i) & 0x01010101) * 0xff);
+        a = xtime(a);
+    }
+    r
+}
+
+/// Invert each byte packed in `x` in GF(2^8) (zero maps to zero), by raising
+/// it to the 254th power.
+fn gf_inv(x: u32) -> u32 {
+    let x2 = gf_mul(x, x);
+    let x3 = gf_mul(x2, x);
+    let x6 = gf_mul(x3, x3);
+    let x12 = gf_mul(x6, x6);
+    let x15 = gf_mul(x12, x3);
+    let x30 = gf_mul(x15, x15);
+    let x60 = gf_mul(x30, x30);
+    let x120 = gf_mul(x60, x60);
+    let x240 = gf_mul(x120, x120);
+    let x252 = gf_mul(x240, x12);
+    gf_mul(x252, x2)
+}
+
+/// Rotate each byte packed in `x` left by `n` bits.
+fn rotl_bytes(x: u32, n: u32) -> u32 {
+    let hi = (0xffu32 << n) & 0xff;
+    let lo = 0xff >> (8 - n);
+    ((x << n) & (hi * 0x01010101)) | ((x >> (8 - n)) & (lo * 0x01010101))
+}
+
+/// Apply the S-box to each byte packed in `x`.
+fn sub_word(x: u32) -> u32 {
+    let b = gf_inv(x);
+    b ^ rotl_bytes(b, 1) ^ rotl_bytes(b, 2) ^ rotl_bytes(b, 3) ^ rotl_bytes(b, 4) ^ 0x63636363
+}
+
+/// Apply the inverse S-box to each byte packed in `x`.
+fn inv_sub_word(x: u32) -> u32 {
+    gf_inv(rotl_bytes(x, 1) ^ rotl_bytes(x, 3) ^ rotl_bytes(x, 6) ^ 0x05050505)
+}
+
+fn pack(b: &[u8]) -> u32 {
+    b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24
+}
+
+fn unpack(w: u32, b: &mut [u8]) {
+    for (i, byte) in b[..4].iter_mut().enumerate() {
+        *byte = (w >> (8 * i)) as u8;
+    }
+}
+
+/// Expand `key` into the eleven round keys.
+fn expand_key(key: &[u8], round_keys: &mut [u8; ROUND_KEYS_LEN]) {
+    round_keys[..AES128_KEY_SIZE].copy_from_slice(key);
+    let mut rcon = 1u32;
+    for i in 4..4 * (ROUNDS + 1) {
+        let mut w = pack(&round_keys[4 * (i - 1)..]);
+        if i % 4 == 0 {
+            w = sub_word(w.rotate_right(8)) ^ rcon;
+            rcon = xtime(rcon);
+        }
+        w ^= pack(&round_keys[4 * (i - 4)..]);
+        unpack(w, &mut round_keys[4 * i..]);
+    }
+}
+
+fn add_round_key(state: &mut [u8; AES128_BLOCK_SIZE], round_key: &[u8]) {
+    for (s, k) in state.iter_mut().zip(round_key.iter()) {
+        *s ^= *k;
+    }
+}
+
+fn sub_bytes(state: &mut [u8; AES128_BLOCK_SIZE], inverse: bool) {
+    for column in state.chunks_mut(4) {
+        let w = pack(column);
+        unpack(if inverse { inv_sub_word(w) } else { sub_word(w) }, column);
+    }
+}
+
+/// Rotate row `r` of the state left by `r` columns, or right when `inverse`.
+fn shift_rows(state: &mut [u8; AES128_BLOCK_SIZE], inverse: bool) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            let from = if inverse { (c + 4 - r) % 4 } else { (c + r) % 4 };
+            state[4 * c + r] = old[4 * from + r];
+        }
+    }
+}
+
+fn mix_columns(state: &mut [u8; AES128_BLOCK_SIZE], inverse: bool) {
+    for column in state.chunks_mut(4) {
+        let w = pack(column);
+        let mixed = if inverse {
+            // Each byte becomes {0e, 0b, 0d, 09} times itself and the next three
+            gf_mul(w, 0x0e0e0e0e) ^ gf_mul(w.rotate_right(8), 0x0b0b0b0b)
+                ^ gf_mul(w.rotate_right(16), 0x0d0d0d0d)
+                ^ gf_mul(w.rotate_right(24), 0x09090909)
+        } else {
+            // Each byte becomes {02, 03, 01, 01} times itself and the next three
+            let x = xtime(w);
+            let r1 = w.rotate_right(8);
+            x ^ xtime(r1) ^ r1 ^ w.rotate_right(16) ^ w.rotate_right(24)
+        };
+        unpack(mixed, column);
+    }
+}
+
+fn encrypt_block(round_keys: &[u8; ROUND_KEYS_LEN], block: &mut [u8; AES128_BLOCK_SIZE]) {
+    add_round_key(block, &round_keys[..AES128_BLOCK_SIZE]);
+    for round in 1..ROUNDS + 1 {
+        sub_bytes(block, false);
+        shift_rows(block, false);
+        if round != ROUNDS {
+            mix_columns(block, false);
+        }
+        add_round_key(block, &round_keys[round * AES128_BLOCK_SIZE..]);
+    }
+}
+
+fn decrypt_block(round_keys: &[u8; ROUND_KEYS_LEN], block: &mut [u8; AES128_BLOCK_SIZE]) {
+    add_round_key(block, &round_keys[ROUNDS * AES128_BLOCK_SIZE..]);
+    for round in (0..ROUNDS).rev() {
+        shift_rows(block, true);
+        sub_bytes(block, true);
+        add_round_key(block, &round_keys[round * AES128_BLOCK_SIZE..]);
+        if round != 0 {
+            mix_columns(block, true);
+        }
+    }
+}
+
+/// Increment a big-endian 128-bit counter block.
+fn increment(block: &mut [u8; AES128_BLOCK_SIZE]) {
+    let mut carry = 1u16;
+    for b in block.iter_mut().rev() {
+        let sum = *b as u16 + carry;
+        *b = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Mode {
+    ECB,
+    CBC,
+    CTR,
+}
+
+pub struct SoftwareAes128<'a> {
+    deferred_call: Cell<Option<DeferredCallHandle>>,
+    client: Cell<Option<&'a symmetric_encryption::Client<'a>>>,
+
+    round_keys: Cell<[u8; ROUND_KEYS_LEN]>,
+    iv: Cell<[u8; AES128_BLOCK_SIZE]>,
+    // The CBC chaining value or the CTR counter for the current message
+    chain: Cell<[u8; AES128_BLOCK_SIZE]>,
+    mode: Cell<Mode>,
+    encrypting: Cell<bool>,
+
+    source: TakeCell<'a, [u8]>,
+    dest: TakeCell<'a, [u8]>,
+    start_index: Cell<usize>,
+    stop_index: Cell<usize>,
+}
+
+impl<'a> SoftwareAes128<'a> {
+    pub fn new() -> SoftwareAes128<'a> {
+        SoftwareAes128 {
+            deferred_call: Cell::new(None),
+            client: Cell::new(None),
+            round_keys: Cell::new([0; ROUND_KEYS_LEN]),
+            iv: Cell::new([0; AES128_BLOCK_SIZE]),
+            chain: Cell::new([0; AES128_BLOCK_SIZE]),
+            mode: Cell::new(Mode::CTR),
+            encrypting: Cell::new(true),
+            source: TakeCell::empty(),
+            dest: TakeCell::empty(),
+            start_index: Cell::new(0),
+            stop_index: Cell::new(0),
+        }
+    }
+
+    /// Set the deferred call used to process requests. Until this is called,
+    /// `crypt()` fails with `EOFF`.
+    pub fn initialize_deferred_call(&self, handle: DeferredCallHandle) {
+        self.deferred_call.set(Some(handle));
+    }
+
+    fn busy(&self) -> bool {
+        self.dest.is_some()
+    }
+
+    /// Transform one block in place according to the current mode, advancing
+    /// the chaining state.
+    fn crypt_block(&self, round_keys: &[u8; ROUND_KEYS_LEN], block: &mut [u8; AES128_BLOCK_SIZE]) {
+        let mut chain = self.chain.get();
+        match self.mode.get() {
+            Mode::ECB => {
+                if self.encrypting.get() {
+                    encrypt_block(round_keys, block);
+                } else {
+                    decrypt_block(round_keys, block);
+                }
+            }
+            Mode::CBC => {
+                if self.encrypting.get() {
+                    add_round_key(block, &chain);
+                    encrypt_block(round_keys, block);
+                    chain = *block;
+                } else {
+                    let ciphertext = *block;
+                    decrypt_block(round_keys, block);
+                    add_round_key(block, &chain);
+                    chain = ciphertext;
+                }
+            }
+            Mode::CTR => {
+                let mut keystream = chain;
+                encrypt_block(round_keys, &mut keystream);
+                add_round_key(block, &keystream);
+                increment(&mut chain);
+            }
+        }
+        self.chain.set(chain);
+    }
+
+    /// Process the pending request and hand the buffers back to the client.
+    fn process(&self) {
+        let dest = match self.dest.take() {
+            Some(dest) => dest,
+            None => return,
+        };
+        let source = self.source.take();
+        let round_keys = self.round_keys.get();
+        let start = self.start_index.get();
+        let stop = self.stop_index.get();
+
+        for i in 0..(stop - start) / AES128_BLOCK_SIZE {
+            let index = start + i * AES128_BLOCK_SIZE;
+            let mut block = [0; AES128_BLOCK_SIZE];
+            match source {
+                Some(ref source) => block.copy_from_slice(
+                    &source[i * AES128_BLOCK_SIZE..(i + 1) * AES128_BLOCK_SIZE],
+                ),
+                None => block.copy_from_slice(&dest[index..index + AES128_BLOCK_SIZE]),
+            }
+            self.crypt_block(&round_keys, &mut block);
+            dest[index..index + AES128_BLOCK_SIZE].copy_from_slice(&block);
+        }
+
+        if let Some(client) = self.client.get() {
+            client.crypt_done(source, dest);
+        }
+    }
+}
+
+impl<'a> AES128<'a> for SoftwareAes128<'a> {
+    fn enable(&self) {
+        // Nothing to power up
+    }
+
+    fn disable(&self) {
+        // Nothing to power down
+    }
+
+    fn set_client(&'a self, client: &'a symmetric_encryption::Client<'a>) {
+        self.client.set(Some(client));
+    }
+
+    fn set_key(&self, key: &[u8]) -> ReturnCode {
+        if key.len() != AES128_KEY_SIZE {
+            return ReturnCode::EINVAL;
+        }
+        let mut round_keys = [0; ROUND_KEYS_LEN];
+        expand_key(key, &mut round_keys);
+        self.round_keys.set(round_keys);
+        ReturnCode::SUCCESS
+    }
+
+    fn set_iv(&self, iv: &[u8]) -> ReturnCode {
+        if iv.len() != AES128_BLOCK_SIZE {
+            return ReturnCode::EINVAL;
+        }
+        let mut block = [0; AES128_BLOCK_SIZE];
+        block.copy_from_slice(iv);
+        self.iv.set(block);
+        ReturnCode::SUCCESS
+    }
+
+    fn start_message(&self) {
+        if self.busy() {
+            return;
+        }
+        self.chain.set(self.iv.get());
+    }
+
+    fn crypt(
+        &'a self,
+        source: Option<&'a mut [u8]>,
+        dest: &'a mut [u8],
+        start_index: usize,
+        stop_index: usize,
+    ) -> Option<(ReturnCode, Option<&'a mut [u8]>, &'a mut [u8])> {
+        let deferred_call = match self.deferred_call.get() {
+            Some(handle) => handle,
+            None => return Some((ReturnCode::EOFF, source, dest)),
+        };
+        if self.busy() {
+            return Some((ReturnCode::EBUSY, source, dest));
+        }
+
+        let valid = stop_index.checked_sub(start_index).map_or(false, |len| {
+            len % AES128_BLOCK_SIZE == 0 && stop_index <= dest.len()
+                && source.as_ref().map_or(true, |source| source.len() == len)
+        });
+        if !valid {
+            return Some((ReturnCode::EINVAL, source, dest));
+        }
+
+        self.source.put(source);
+        self.dest.replace(dest);
+        self.start_index.set(start_index);
+        self.stop_index.set(stop_index);
+
+        // Do the work, and call back, from a fresh event.
+        deferred_call.set();
+        None
+    }
+}
+
+impl<'a> AES128Ctr for SoftwareAes128<'a> {
+    fn set_mode_aes128ctr(&self, encrypting: bool) {
+        self.mode.set(Mode::CTR);
+        self.encrypting.set(encrypting);
+    }
+}
+
+impl<'a> AES128CBC for SoftwareAes128<'a> {
+    fn set_mode_aes128cbc(&self, encrypting: bool) {
+        self.mode.set(Mode::CBC);
+        self.encrypting.set(encrypting);
+    }
+}
+
+impl<'a> AES128ECB for SoftwareAes128<'a> {
+    fn set_mode_aes128ecb(&self, encrypting: bool) {
+        self.mode.set(Mode::ECB);
+        self.encrypting.set(encrypting);
+    }
+}
+
+impl<'a> DeferredCallClient for SoftwareAes128<'a> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        self.process();
+    }
+}
@@ -0,0 +1,217 @@
+//! Software implementation of the CRC interface.
+//!
+//! This provides a `hil::crc::CRC` implementation for chips that lack a CRC
+//! unit (e.g. the nRF5x, CC26xx and TM4C families). All of the algorithms in
+//! `hil::crc::CrcAlg` are supported. Checksums are computed with half-byte
+//! lookup tables, which keeps the tables small (16 entries per polynomial)
+//! while still avoiding bit-at-a-time processing.
+//!
+//! `compute()` consumes the whole buffer before returning, since the buffer is
+//! only borrowed for the duration of the call. The result is then delivered to
+//! the client from a separate event, so users of this module see the same
+//! asynchronous contract as with a hardware unit. The event is a deferred
+//! call, which must be registered at board initialization.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let software_crc = static_init!(
+//!     capsules::software_crc::SoftwareCrc<'static>,
+//!     capsules::software_crc::SoftwareCrc::new()
+//! );
+//! software_crc.initialize_deferred_call(
+//!     dynamic_deferred_call::register(software_crc).expect("no deferred call for software_crc"),
+//! );
+//!
+//! let crc = static_init!(
+//!     capsules::crc::Crc<'static, capsules::software_crc::SoftwareCrc<'static>>,
+//!     capsules::crc::Crc::new(software_crc, kernel::Grant::create())
+//! );
+//! software_crc.set_client(crc);
+//! ```
+
+use core::cell::Cell;
+use kernel::common::dynamic_deferred_call::{DeferredCallClient, DeferredCallHandle};
+use kernel::hil::crc::{self, CrcAlg};
+use kernel::ReturnCode;
+
+/// Reflected polynomial 0x04C11DB7 ("CRC-32")
+const CRC32_TABLE: [u32; 16] = [
+    0x00000000, 0x1DB71064, 0x3B6E20C8, 0x26D930AC, 0x76DC4190, 0x6B6B51F4, 0x4DB26158,
+    0x5005713C, 0xEDB88320, 0xF00F9344, 0xD6D6A3E8, 0xCB61B38C, 0x9B64C2B0, 0x86D3D2D4,
+    0xA00AE278, 0xBDBDF21C,
+];
+
+/// Reflected polynomial 0x1EDC6F41 ("CRC-32C")
+const CRC32C_TABLE: [u32; 16] = [
+    0x00000000, 0x105EC76F, 0x20BD8EDE, 0x30E349B1, 0x417B1DBC, 0x5125DAD3, 0x61C69362,
+    0x7198540D, 0x82F63B78, 0x92A8FC17, 0xA24BB5A6, 0xB21572C9, 0xC38D26C4, 0xD3D3E1AB,
+    0xE330A81A, 0xF36E6F75,
+];
+
+/// Reflected polynomial 0x1021 (SAM4L-16)
+const CRC16_REFLECTED_TABLE: [u32; 16] = [
+    0x0000, 0x1081, 0x2102, 0x3183, 0x4204, 0x5285, 0x6306, 0x7387, 0x8408, 0x9489, 0xA50A,
+    0xB58B, 0xC60C, 0xD68D, 0xE70E, 0xF78F,
+];
+
+/// Polynomial 0x1021, MSB first ("CRC-16-CCITT")
+const CRC16_CCITT_TABLE: [u32; 16] = [
+    0x0000, 0x1021, 0x2042, 0x3063, 0x4084, 0x50A5, 0x60C6, 0x70E7, 0x8108, 0x9129, 0xA14A,
+    0xB16B, 0xC18C, 0xD1AD, 0xE1CE, 0xF1EF,
+];
+
+/// Polynomial 0x07, MSB first ("CRC-8")
+const CRC8_TABLE: [u32; 16] = [
+    0x00, 0x07, 0x0E, 0x09, 0x1C, 0x1B, 0x12, 0x15, 0x38, 0x3F, 0x36, 0x31, 0x24, 0x23, 0x2A,
+    0x2D,
+];
+
+/// Run a CRC that consumes input bytes from LSB to MSB. The register is kept
+/// bit-reversed, so `table` must hold the reflected polynomial.
+fn crc_reflected(table: &[u32; 16], init: u32, data: &[u8]) -> u32 {
+    let mut crc = init;
+    for &byte in data.iter() {
+        crc = (crc >> 4) ^ table[((crc ^ byte as u32) & 0xf) as usize];
+        crc = (crc >> 4) ^ table[((crc ^ (byte >> 4) as u32) & 0xf) as usize];
+    }
+    crc
+}
+
+/// Run a CRC of `width` bits that consumes input bytes from MSB to LSB.
+fn crc_msb_first(table: &[u32; 16], width: u32, init: u32, data: &[u8]) -> u32 {
+    let mask = (1u64 << width) as u32 - 1;
+    let mut crc = init;
+    for &byte in data.iter() {
+        let top = (crc >> (width - 4)) ^ (byte >> 4) as u32;
+        crc = ((crc << 4) & mask) ^ table[(top & 0xf) as usize];
+        let top = (crc >> (width - 4)) ^ byte as u32;
+        crc = ((crc << 4) & mask) ^ table[(top & 0xf) as usize];
+    }
+    crc
+}
+
+fn reverse(n: u32) -> u32 {
+    let mut out: u32 = 0;
+    for i in 0..32 {
+        out |= ((n >> i) & 1) << (31 - i);
+    }
+    out
+}
+
+/// Compute `alg` over `data`, producing the same value a SAM4L CRCCU (plus
+/// the HIL's post-processing) would.
+pub fn compute_crc(data: &[u8], alg: CrcAlg) -> u32 {
+    match alg {
+        CrcAlg::Crc32 => !crc_reflected(&CRC32_TABLE, 0xffffffff, data),
+        CrcAlg::Crc32C => !crc_reflected(&CRC32C_TABLE, 0xffffffff, data),
+        CrcAlg::Sam4L16 => {
+            let crc = crc_reflected(&CRC16_REFLECTED_TABLE, 0xffff, data);
+            0xffff0000 | (reverse(crc) >> 16)
+        }
+        CrcAlg::Sam4L32 => reverse(crc_reflected(&CRC32_TABLE, 0xffffffff, data)),
+        CrcAlg::Sam4L32C => reverse(crc_reflected(&CRC32C_TABLE, 0xffffffff, data)),
+        CrcAlg::Crc16CCITT => crc_msb_first(&CRC16_CCITT_TABLE, 16, 0xffff, data),
+        CrcAlg::Crc8 => crc_msb_first(&CRC8_TABLE, 8, 0x00, data),
+    }
+}
+
+pub struct SoftwareCrc<'a> {
+    deferred_call: Cell<Option<DeferredCallHandle>>,
+    client: Cell<Option<&'a crc::Client>>,
+    result: Cell<Option<u32>>,
+}
+
+impl<'a> SoftwareCrc<'a> {
+    pub const fn new() -> SoftwareCrc<'a> {
+        SoftwareCrc {
+            deferred_call: Cell::new(None),
+            client: Cell::new(None),
+            result: Cell::new(None),
+        }
+    }
+
+    /// Set the deferred call used to deliver results. Until this is called,
+    /// `compute()` returns `EOFF`.
+    pub fn initialize_deferred_call(&self, handle: DeferredCallHandle) {
+        self.deferred_call.set(Some(handle));
+    }
+
+    /// Set a client to receive results of CRC computations
+    pub fn set_client(&self, client: &'a crc::Client) {
+        self.client.set(Some(client));
+    }
+}
+
+impl<'a> crc::CRC for SoftwareCrc<'a> {
+    fn compute(&self, data: &[u8], alg: CrcAlg) -> ReturnCode {
+        let deferred_call = match self.deferred_call.get() {
+            Some(handle) => handle,
+            None => return ReturnCode::EOFF,
+        };
+        if self.result.get().is_some() {
+            // The previous result has not been delivered yet
+            return ReturnCode::EBUSY;
+        }
+
+        self.result.set(Some(compute_crc(data, alg)));
+
+        // Deliver the result from a fresh event rather than from within this
+        // call.
+        deferred_call.set();
+        ReturnCode::SUCCESS
+    }
+
+    fn disable(&self) {
+        // Nothing to power down
+    }
+}
+
+impl<'a> DeferredCallClient for SoftwareCrc<'a> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        self.result.take().map(|result| {
+            self.client.get().map(|client| client.receive_result(result));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_crc;
+    use kernel::hil::crc::CrcAlg;
+
+    const CHECK: &'static [u8] = b"123456789";
+    const PANAMA: &'static [u8] = b"A man, a plan, a canal, Panama";
+
+    #[test]
+    fn standard_check_values() {
+        assert_eq!(compute_crc(CHECK, CrcAlg::Crc32), 0xCBF43926);
+        assert_eq!(compute_crc(CHECK, CrcAlg::Crc32C), 0xE3069283);
+        assert_eq!(compute_crc(CHECK, CrcAlg::Crc16CCITT), 0x29B1);
+        assert_eq!(compute_crc(CHECK, CrcAlg::Crc8), 0xF4);
+    }
+
+    /// Values from the CRCCU, as in the userland CRC test cases.
+    #[test]
+    fn matches_sam4l_crccu() {
+        assert_eq!(compute_crc(b"ABCDEFG", CrcAlg::Sam4L16), 0xffff1541);
+        assert_eq!(compute_crc(b"ABCD", CrcAlg::Sam4L16), 0xffffB34B);
+        assert_eq!(compute_crc(b"0123", CrcAlg::Sam4L16), 0xffffD5A8);
+        assert_eq!(compute_crc(b"012345678", CrcAlg::Sam4L16), 0xffff35B3);
+        assert_eq!(compute_crc(b"01234567ABCDEFGHI", CrcAlg::Sam4L16), 0xffffB881);
+
+        assert_eq!(compute_crc(b"ABCDEFG", CrcAlg::Sam4L32), 0xC2D6098F);
+        assert_eq!(compute_crc(b"0123", CrcAlg::Sam4L32), 0x4146999A);
+        assert_eq!(compute_crc(PANAMA, CrcAlg::Sam4L32), 0xA4CF5FDD);
+
+        assert_eq!(compute_crc(b"ABCDEFG", CrcAlg::Sam4L32C), 0x599511CB);
+        assert_eq!(compute_crc(b"0123", CrcAlg::Sam4L32C), 0x62B9639F);
+        assert_eq!(compute_crc(PANAMA, CrcAlg::Sam4L32C), 0xDD284452);
+
+        assert_eq!(compute_crc(b"ABCDEFG", CrcAlg::Crc32), 0x0E6F94BC);
+        assert_eq!(compute_crc(PANAMA, CrcAlg::Crc32), 0x44050CDA);
+        assert_eq!(compute_crc(b"ABCDEFG", CrcAlg::Crc32C), 0x2C775665);
+        assert_eq!(compute_crc(PANAMA, CrcAlg::Crc32C), 0xB5DDEB44);
+    }
+}
@@ -0,0 +1,152 @@
+//! Software wall clock on top of an alarm.
+//!
+//! This provides a `hil::date_time::DateTime` implementation for chips whose
+//! real-time counter has no calendar, such as the nRF5x RTC, the SAM4L AST
+//! and the CC26xx RTC. The time is kept as the time it was last set plus the
+//! tics counted since then on the 64-bit clock of an `Alarm64`, so it does not
+//! suffer from wraps of the counter.
+//!
+//! Low-frequency crystals are typically off by tens of parts per million,
+//! which adds up to seconds per day. The clock corrects for this by a rate set
+//! by the board, or estimated when the clock is set again once at least
+//! `MIN_CALIBRATION_SECONDS` have been counted since it was first set: the
+//! difference between the time set and the time counted over that interval
+//! gives the drift of the counter.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let date_time_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let software_date_time = static_init!(
+//!     capsules::software_date_time::SoftwareDateTime<
+//!         'static,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::software_date_time::SoftwareDateTime::new(date_time_alarm)
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::date_time::{self, DateTime};
+use kernel::hil::time::{Alarm64, Frequency};
+use kernel::ReturnCode;
+
+/// Shortest interval between settings of the clock over which its drift is
+/// estimated. Clocks set from the network or by hand may be off by a second
+/// or so, which over a day is an error of about 10 parts per million.
+pub const MIN_CALIBRATION_SECONDS: u64 = 24 * 60 * 60;
+
+/// Largest drift correction, in parts per million. Larger estimates are
+/// assumed to come from a wrong setting of the clock and are ignored.
+pub const MAX_DRIFT_PPM: i32 = 1000;
+
+pub struct SoftwareDateTime<'a, A: Alarm64 + 'a> {
+    alarm: &'a A,
+    /// Seconds since the epoch and the 64-bit clock at the last setting
+    base: Cell<Option<(u64, u64)>>,
+    /// Seconds since the epoch and the 64-bit clock at the setting drift is
+    /// next estimated from
+    calibration: Cell<Option<(u64, u64)>>,
+    /// Parts per million the counter runs slow, negative if it runs fast
+    drift_ppm: Cell<i32>,
+}
+
+impl<'a, A: Alarm64> SoftwareDateTime<'a, A> {
+    pub fn new(alarm: &'a A) -> SoftwareDateTime<'a, A> {
+        SoftwareDateTime {
+            alarm: alarm,
+            base: Cell::new(None),
+            calibration: Cell::new(None),
+            drift_ppm: Cell::new(0),
+        }
+    }
+
+    /// Set the drift of the counter, in parts per million it runs slow, for
+    /// example from the tolerance of a characterized crystal. It is replaced
+    /// when the clock estimates its drift.
+    pub fn set_drift_ppm(&self, ppm: i32) {
+        self.drift_ppm.set(ppm);
+    }
+
+    /// The drift of the counter in use, in parts per million it runs slow.
+    pub fn drift_ppm(&self) -> i32 {
+        self.drift_ppm.get()
+    }
+
+    /// Tics counted since `since`, corrected for drift.
+    fn corrected_tics(&self, since: u64, now: u64) -> u64 {
+        let tics = now - since;
+        let ppm = self.drift_ppm.get();
+        let correction = tics / 1_000_000 * ppm.abs() as u64
+            + tics % 1_000_000 * ppm.abs() as u64 / 1_000_000;
+        if ppm >= 0 {
+            tics + correction
+        } else {
+            tics - correction
+        }
+    }
+
+    /// Estimate drift from the time counted since the calibration setting,
+    /// if enough tics have been counted since.
+    fn calibrate(&self, seconds: u64, now: u64) {
+        let (cal_seconds, cal_tics) = match self.calibration.get() {
+            Some(calibration) => calibration,
+            None => {
+                self.calibration.set(Some((seconds, now)));
+                return;
+            }
+        };
+        let frequency = A::Frequency::frequency() as u64;
+        let counted = now - cal_tics;
+        if counted < MIN_CALIBRATION_SECONDS * frequency {
+            return;
+        }
+
+        // The time set may be earlier than the calibration setting if either
+        // was wrong, in which case there is no estimate
+        let estimate = seconds
+            .checked_sub(cal_seconds)
+            .and_then(|elapsed| elapsed.checked_mul(frequency))
+            .and_then(|actual| {
+                let (difference, slow) = if actual >= counted {
+                    (actual - counted, true)
+                } else {
+                    (counted - actual, false)
+                };
+                difference
+                    .checked_mul(1_000_000)
+                    .map(|difference| (difference / counted, slow))
+            });
+        match estimate {
+            Some((ppm, slow)) if ppm <= MAX_DRIFT_PPM as u64 => {
+                self.drift_ppm
+                    .set(if slow { ppm as i32 } else { -(ppm as i32) });
+            }
+            _ => {}
+        }
+        self.calibration.set(Some((seconds, now)));
+    }
+}
+
+impl<'a, A: Alarm64> DateTime for SoftwareDateTime<'a, A> {
+    fn get_seconds(&self) -> Option<u64> {
+        self.base.get().map(|(seconds, tics)| {
+            let now = self.alarm.now64();
+            seconds + self.corrected_tics(tics, now) / A::Frequency::frequency() as u64
+        })
+    }
+
+    fn set_seconds(&self, seconds: u64) -> ReturnCode {
+        if seconds > date_time::MAX_SECONDS {
+            return ReturnCode::EINVAL;
+        }
+        let now = self.alarm.now64();
+        self.calibrate(seconds, now);
+        self.base.set(Some((seconds, now)));
+        ReturnCode::SUCCESS
+    }
+}
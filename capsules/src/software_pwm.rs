@@ -0,0 +1,142 @@
+//! Software implementation of a PWM output.
+//!
+//! This provides a `hil::pwm::PwmPin` for chips, or pins, without a PWM
+//! unit, by toggling a GPIO pin from alarm callbacks. Each pin needs its own
+//! alarm, usually a `VirtualMuxAlarm`.
+//!
+//! Each edge is scheduled relative to the previous one, so the period does
+//! not drift with interrupt latency. Resolution is limited to one tic of the
+//! alarm: with a 32 kHz alarm, a 1 kHz signal has 32 possible duty cycles.
+//! The frequency is at most half the alarm frequency, and high frequencies
+//! cost many interrupts.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let pwm_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let pwm_pin = static_init!(
+//!     capsules::software_pwm::SoftwarePwmPin<
+//!         'static,
+//!         sam4l::gpio::GPIOPin,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     >,
+//!     capsules::software_pwm::SoftwarePwmPin::new(&sam4l::gpio::PB[14], pwm_alarm)
+//! );
+//! pwm_alarm.set_client(pwm_pin);
+//! ```
+
+use core::cell::Cell;
+use kernel::hil::gpio;
+use kernel::hil::pwm::PwmPin;
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::ReturnCode;
+
+/// The duty cycle for which the pin is always high.
+pub const MAX_DUTY_CYCLE: usize = 0xffff;
+
+pub struct SoftwarePwmPin<'a, P: gpio::Pin + 'a, A: Alarm + 'a> {
+    pin: &'a P,
+    alarm: &'a A,
+    /// Tics the pin stays high each period, zero when stopped
+    high_tics: Cell<u32>,
+    /// Tics the pin stays low each period
+    low_tics: Cell<u32>,
+    /// Time of the last edge
+    edge: Cell<u32>,
+    high: Cell<bool>,
+}
+
+impl<'a, P: gpio::Pin, A: Alarm> SoftwarePwmPin<'a, P, A> {
+    pub fn new(pin: &'a P, alarm: &'a A) -> SoftwarePwmPin<'a, P, A> {
+        SoftwarePwmPin {
+            pin: pin,
+            alarm: alarm,
+            high_tics: Cell::new(0),
+            low_tics: Cell::new(0),
+            edge: Cell::new(0),
+            high: Cell::new(false),
+        }
+    }
+}
+
+impl<'a, P: gpio::Pin, A: Alarm> PwmPin for SoftwarePwmPin<'a, P, A> {
+    fn start(&self, frequency_hz: usize, duty_cycle: usize) -> ReturnCode {
+        if frequency_hz == 0 || frequency_hz > self.get_maximum_frequency_hz()
+            || duty_cycle > MAX_DUTY_CYCLE
+        {
+            return ReturnCode::EINVAL;
+        }
+
+        self.alarm.disable();
+        self.high_tics.set(0);
+        self.pin.make_output();
+
+        let period = A::Frequency::frequency() / frequency_hz as u32;
+        let high_tics = (period as u64 * duty_cycle as u64 / MAX_DUTY_CYCLE as u64) as u32;
+        if high_tics == 0 {
+            self.pin.clear();
+            return ReturnCode::SUCCESS;
+        } else if high_tics == period {
+            self.pin.set();
+            return ReturnCode::SUCCESS;
+        }
+
+        self.high_tics.set(high_tics);
+        self.low_tics.set(period - high_tics);
+        let now = self.alarm.now();
+        self.edge.set(now);
+        self.high.set(true);
+        self.pin.set();
+        self.alarm.set_alarm_from(now, high_tics);
+        ReturnCode::SUCCESS
+    }
+
+    fn stop(&self) -> ReturnCode {
+        self.alarm.disable();
+        self.high_tics.set(0);
+        self.pin.clear();
+        ReturnCode::SUCCESS
+    }
+
+    fn get_maximum_frequency_hz(&self) -> usize {
+        // At least one tic high and one low
+        A::Frequency::frequency() as usize / 2
+    }
+
+    fn get_maximum_duty_cycle(&self) -> usize {
+        MAX_DUTY_CYCLE
+    }
+}
+
+impl<'a, P: gpio::Pin, A: Alarm> time::Client for SoftwarePwmPin<'a, P, A> {
+    fn fired(&self) {
+        if self.high_tics.get() == 0 {
+            return;
+        }
+
+        let (ended, next) = if self.high.get() {
+            self.pin.clear();
+            (self.high_tics.get(), self.low_tics.get())
+        } else {
+            self.pin.set();
+            (self.low_tics.get(), self.high_tics.get())
+        };
+        self.high.set(!self.high.get());
+
+        // Schedule from when this edge was due. If the next edge is due
+        // already, the signal has fallen behind, so start again from now
+        // rather than catching up with a burst of edges.
+        let mask = self.alarm.max_tics();
+        let now = self.alarm.now();
+        let mut edge = self.edge.get().wrapping_add(ended) & mask;
+        if now.wrapping_sub(edge) & mask >= next {
+            edge = now;
+        }
+        self.edge.set(edge);
+        self.alarm.set_alarm_from(edge, next);
+    }
+}
@@ -0,0 +1,324 @@
+//! Software implementation of SHA-256 and HMAC-SHA256.
+//!
+//! This provides `hil::digest::Digest` with the `Sha256` and `HMACSha256`
+//! modes for chips without a hashing engine. The message is compressed as
+//! data is added, so only one partial block is kept between calls.
+//!
+//! As with the other software HIL implementations, each call does its work
+//! immediately and the buffer is handed back to the client from a separate
+//! event, a deferred call.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let sha = static_init!(
+//!     capsules::software_sha256::SoftwareSha256<'static>,
+//!     capsules::software_sha256::SoftwareSha256::new()
+//! );
+//! sha.initialize_deferred_call(
+//!     dynamic_deferred_call::register(sha).expect("no deferred call for software_sha256"),
+//! );
+//! ```
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::common::dynamic_deferred_call::{DeferredCallClient, DeferredCallHandle};
+use kernel::hil::digest::{self, HMACSha256, Sha256, SHA256_DIGEST_LEN};
+use kernel::ReturnCode;
+
+const BLOCK_LEN: usize = 64;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+    0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+    0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+    0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+    0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+    0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+    0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+    0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+    0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+    0x5be0cd19,
+];
+
+/// The running state of a SHA-256 computation.
+#[derive(Copy, Clone)]
+struct Sha256State {
+    h: [u32; 8],
+    block: [u8; BLOCK_LEN],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha256State {
+    const fn new() -> Sha256State {
+        Sha256State {
+            h: H0,
+            block: [0; BLOCK_LEN],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = (self.block[4 * i] as u32) << 24 | (self.block[4 * i + 1] as u32) << 16
+                | (self.block[4 * i + 2] as u32) << 8
+                | self.block[4 * i + 3] as u32;
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let mut v = self.h;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7].wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v[7] = v[6];
+            v[6] = v[5];
+            v[5] = v[4];
+            v[4] = v[3].wrapping_add(t1);
+            v[3] = v[2];
+            v[2] = v[1];
+            v[1] = v[0];
+            v[0] = t1.wrapping_add(t2);
+        }
+        for i in 0..8 {
+            self.h[i] = self.h[i].wrapping_add(v[i]);
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &byte in data.iter() {
+            self.block[self.block_len] = byte;
+            self.block_len += 1;
+            if self.block_len == BLOCK_LEN {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+        self.total_len = self.total_len.wrapping_add(data.len() as u64);
+    }
+
+    fn finish(mut self) -> [u8; SHA256_DIGEST_LEN] {
+        let bit_len = self.total_len.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.block_len != BLOCK_LEN - 8 {
+            self.update(&[0]);
+        }
+        for i in 0..8 {
+            self.block[BLOCK_LEN - 8 + i] = (bit_len >> (56 - 8 * i)) as u8;
+        }
+        self.compress();
+
+        let mut out = [0; SHA256_DIGEST_LEN];
+        for (i, word) in self.h.iter().enumerate() {
+            for j in 0..4 {
+                out[4 * i + j] = (word >> (24 - 8 * j)) as u8;
+            }
+        }
+        out
+    }
+}
+
+/// Compute the SHA-256 digest of `data`.
+pub fn sha256(data: &[u8]) -> [u8; SHA256_DIGEST_LEN] {
+    let mut state = Sha256State::new();
+    state.update(data);
+    state.finish()
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Mode {
+    None,
+    Sha256,
+    HmacSha256,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Pending {
+    AddData,
+    Hash,
+}
+
+pub struct SoftwareSha256<'a> {
+    deferred_call: Cell<Option<DeferredCallHandle>>,
+    client: Cell<Option<&'a digest::Client>>,
+
+    mode: Cell<Mode>,
+    state: Cell<Sha256State>,
+    // The HMAC key, zero padded to a whole block
+    hmac_key: Cell<[u8; BLOCK_LEN]>,
+
+    buffer: TakeCell<'static, [u8]>,
+    pending: Cell<Option<Pending>>,
+}
+
+impl<'a> SoftwareSha256<'a> {
+    pub const fn new() -> SoftwareSha256<'a> {
+        SoftwareSha256 {
+            deferred_call: Cell::new(None),
+            client: Cell::new(None),
+            mode: Cell::new(Mode::None),
+            state: Cell::new(Sha256State::new()),
+            hmac_key: Cell::new([0; BLOCK_LEN]),
+            buffer: TakeCell::empty(),
+            pending: Cell::new(None),
+        }
+    }
+
+    /// Set the deferred call used to hand buffers back. Until this is called,
+    /// requests fail with `EOFF`.
+    pub fn initialize_deferred_call(&self, handle: DeferredCallHandle) {
+        self.deferred_call.set(Some(handle));
+    }
+
+    /// The state at the start of a message: for HMAC, the inner hash has
+    /// already consumed the key XORed with the inner pad.
+    fn initial_state(&self) -> Sha256State {
+        let mut state = Sha256State::new();
+        if self.mode.get() == Mode::HmacSha256 {
+            let mut pad = self.hmac_key.get();
+            pad.iter_mut().for_each(|b| *b ^= 0x36);
+            state.update(&pad);
+        }
+        state
+    }
+
+    /// Hand `buffer` back to the client from a fresh event.
+    fn complete(&self, buffer: &'static mut [u8], pending: Pending) {
+        self.buffer.replace(buffer);
+        self.pending.set(Some(pending));
+        self.deferred_call.get().map(|handle| handle.set());
+    }
+
+    fn check_ready(&self) -> ReturnCode {
+        if self.pending.get().is_some() {
+            ReturnCode::EBUSY
+        } else if self.mode.get() == Mode::None || self.deferred_call.get().is_none() {
+            ReturnCode::EOFF
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+}
+
+impl<'a> digest::Digest<'a> for SoftwareSha256<'a> {
+    fn set_client(&'a self, client: &'a digest::Client) {
+        self.client.set(Some(client));
+    }
+
+    fn add_data(
+        &self,
+        data: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        let res = self.check_ready();
+        if res != ReturnCode::SUCCESS {
+            return (res, Some(data));
+        }
+        if len > data.len() {
+            return (ReturnCode::ESIZE, Some(data));
+        }
+
+        let mut state = self.state.get();
+        state.update(&data[..len]);
+        self.state.set(state);
+
+        self.complete(data, Pending::AddData);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn run(&self, digest: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>) {
+        let res = self.check_ready();
+        if res != ReturnCode::SUCCESS {
+            return (res, Some(digest));
+        }
+        if digest.len() < SHA256_DIGEST_LEN {
+            return (ReturnCode::ESIZE, Some(digest));
+        }
+
+        let mut result = self.state.get().finish();
+        if self.mode.get() == Mode::HmacSha256 {
+            let mut pad = self.hmac_key.get();
+            pad.iter_mut().for_each(|b| *b ^= 0x5c);
+            let mut outer = Sha256State::new();
+            outer.update(&pad);
+            outer.update(&result);
+            result = outer.finish();
+        }
+        digest[..SHA256_DIGEST_LEN].copy_from_slice(&result);
+        self.state.set(self.initial_state());
+
+        self.complete(digest, Pending::Hash);
+        (ReturnCode::SUCCESS, None)
+    }
+
+    fn clear_data(&self) {
+        self.state.set(self.initial_state());
+    }
+}
+
+impl<'a> Sha256 for SoftwareSha256<'a> {
+    fn set_mode_sha256(&self) -> ReturnCode {
+        if self.pending.get().is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.mode.set(Mode::Sha256);
+        self.state.set(self.initial_state());
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> HMACSha256 for SoftwareSha256<'a> {
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> ReturnCode {
+        if self.pending.get().is_some() {
+            return ReturnCode::EBUSY;
+        }
+
+        // Keys longer than a block are replaced by their digest
+        let mut padded_key = [0; BLOCK_LEN];
+        if key.len() > BLOCK_LEN {
+            padded_key[..SHA256_DIGEST_LEN].copy_from_slice(&sha256(key));
+        } else {
+            padded_key[..key.len()].copy_from_slice(key);
+        }
+        self.hmac_key.set(padded_key);
+
+        self.mode.set(Mode::HmacSha256);
+        self.state.set(self.initial_state());
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<'a> DeferredCallClient for SoftwareSha256<'a> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        if let Some(pending) = self.pending.take() {
+            self.buffer.take().map(|buffer| {
+                self.client.get().map(move |client| match pending {
+                    Pending::AddData => client.add_data_done(ReturnCode::SUCCESS, buffer),
+                    Pending::Hash => client.hash_done(ReturnCode::SUCCESS, buffer),
+                });
+            });
+        }
+    }
+}
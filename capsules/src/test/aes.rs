@@ -3,8 +3,8 @@
 use core::cell::Cell;
 use kernel::common::cells::TakeCell;
 use kernel::hil;
-use kernel::hil::symmetric_encryption::{AES128, AES128CBC, AES128Ctr, AES128_BLOCK_SIZE,
-                                        AES128_KEY_SIZE};
+use kernel::hil::symmetric_encryption::{AES128, AES128CBC, AES128Ctr, AES128ECB,
+                                        AES128_BLOCK_SIZE, AES128_KEY_SIZE};
 use kernel::ReturnCode;
 
 pub struct TestAes128Ctr<'a, A: 'a> {
@@ -19,8 +19,11 @@ pub struct TestAes128Ctr<'a, A: 'a> {
     use_source: Cell<bool>,
 }
 
+/// Tests CBC mode, or ECB mode if `ecb` is set, which are both checked
+/// with source and destination buffers and in place.
 pub struct TestAes128Cbc<'a, A: 'a> {
     aes: &'a A,
+    ecb: bool,
 
     key: TakeCell<'a, [u8]>,
     iv: TakeCell<'a, [u8]>,
@@ -176,16 +179,18 @@ impl<'a, A: AES128<'a> + AES128Ctr> hil::symmetric_encryption::Client<'a> for Te
     }
 }
 
-impl<'a, A: AES128<'a> + AES128CBC> TestAes128Cbc<'a, A> {
+impl<'a, A: AES128<'a> + AES128CBC + AES128ECB> TestAes128Cbc<'a, A> {
     pub fn new(
         aes: &'a A,
         key: &'a mut [u8],
         iv: &'a mut [u8],
         source: &'a mut [u8],
         data: &'a mut [u8],
+        ecb: bool,
     ) -> Self {
         TestAes128Cbc {
             aes: aes,
+            ecb: ecb,
 
             key: TakeCell::new(key),
             iv: TakeCell::new(iv),
@@ -209,22 +214,25 @@ impl<'a, A: AES128<'a> + AES128CBC> TestAes128Cbc<'a, A> {
             assert!(self.aes.set_key(key) == ReturnCode::SUCCESS);
         });
 
-        // Copy mode-appropriate IV into IV buffer and configure it in the hardware
-        self.iv.map(|iv| {
-            let iv_mode = &IV_CBC;
+        // Copy mode-appropriate IV into IV buffer and configure it in the
+        // hardware. ECB mode has no IV.
+        if !self.ecb {
+            self.iv.map(|iv| {
+                let iv_mode = &IV_CBC;
 
-            for (i, b) in iv_mode.iter().enumerate() {
-                iv[i] = *b;
-            }
+                for (i, b) in iv_mode.iter().enumerate() {
+                    iv[i] = *b;
+                }
 
-            assert!(self.aes.set_iv(iv) == ReturnCode::SUCCESS);
-        });
+                assert!(self.aes.set_iv(iv) == ReturnCode::SUCCESS);
+            });
+        }
 
         // Copy mode-appropriate source into source buffer
         let source_mode = if self.encrypting.get() {
             &PTXT
         } else {
-            &CTXT_CBC
+            self.ciphertext()
         };
         self.source.map(|source| {
             for (i, b) in source_mode.iter().enumerate() {
@@ -249,7 +257,11 @@ impl<'a, A: AES128<'a> + AES128CBC> TestAes128Cbc<'a, A> {
             );
         }
 
-        self.aes.set_mode_aes128cbc(self.encrypting.get());
+        if self.ecb {
+            self.aes.set_mode_aes128ecb(self.encrypting.get());
+        } else {
+            self.aes.set_mode_aes128cbc(self.encrypting.get());
+        }
         self.aes.start_message();
 
         let start = DATA_OFFSET;
@@ -275,9 +287,19 @@ impl<'a, A: AES128<'a> + AES128CBC> TestAes128Cbc<'a, A> {
             }
         }
     }
+
+    fn ciphertext(&self) -> &'static [u8; 4 * AES128_BLOCK_SIZE] {
+        if self.ecb {
+            &CTXT_ECB
+        } else {
+            &CTXT_CBC
+        }
+    }
 }
 
-impl<'a, A: AES128<'a> + AES128CBC> hil::symmetric_encryption::Client<'a> for TestAes128Cbc<'a, A> {
+impl<'a, A: AES128<'a> + AES128CBC + AES128ECB> hil::symmetric_encryption::Client<'a>
+    for TestAes128Cbc<'a, A>
+{
     fn crypt_done(&'a self, source: Option<&'a mut [u8]>, dest: &'a mut [u8]) {
         if self.use_source.get() {
             // Take back the source buffer
@@ -288,7 +310,7 @@ impl<'a, A: AES128<'a> + AES128CBC> hil::symmetric_encryption::Client<'a> for Te
         self.data.replace(dest);
 
         let expected = if self.encrypting.get() {
-            &CTXT_CBC
+            self.ciphertext()
         } else {
             &PTXT
         };
@@ -298,8 +320,9 @@ impl<'a, A: AES128<'a> + AES128CBC> hil::symmetric_encryption::Client<'a> for Te
             &data[DATA_OFFSET..DATA_OFFSET + DATA_LEN] == expected.as_ref()
         }) {
             debug!(
-                "OK! ({} {})",
+                "OK! ({} {} {})",
                 if self.encrypting.get() { "Enc" } else { "Dec" },
+                if self.ecb { "Ecb" } else { "Cbc" },
                 if self.use_source.get() {
                     "Src/Dst"
                 } else {
@@ -378,3 +401,15 @@ const CTXT_CBC: [u8; 4 * AES128_BLOCK_SIZE] = [
     0x3f, 0xf1, 0xca, 0xa1, 0x68, 0x1f, 0xac, 0x09,
     0x12, 0x0e, 0xca, 0x30, 0x75, 0x86, 0xe1, 0xa7
 ];
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const CTXT_ECB: [u8; 4 * AES128_BLOCK_SIZE] = [
+    0x3a, 0xd7, 0x7b, 0xb4, 0x0d, 0x7a, 0x36, 0x60,
+    0xa8, 0x9e, 0xca, 0xf3, 0x24, 0x66, 0xef, 0x97,
+    0xf5, 0xd3, 0xd5, 0x85, 0x03, 0xb9, 0x69, 0x9d,
+    0xe7, 0x85, 0x89, 0x5a, 0x96, 0xfd, 0xba, 0xaf,
+    0x43, 0xb1, 0xcd, 0x7f, 0x59, 0x8e, 0xce, 0x23,
+    0x88, 0x1b, 0x00, 0xe3, 0xed, 0x03, 0x06, 0x88,
+    0x7b, 0x0c, 0x78, 0x5e, 0x27, 0xe8, 0xad, 0x3f,
+    0x82, 0x23, 0x20, 0x71, 0x04, 0x72, 0x5d, 0xd4
+];
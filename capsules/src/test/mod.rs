@@ -1,2 +1,3 @@
 pub mod aes;
 pub mod aes_ccm;
+pub mod sha256;
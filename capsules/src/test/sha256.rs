@@ -0,0 +1,132 @@
+//! Test SHA-256 and HMAC-SHA256 implementations against known vectors.
+//!
+//! The message is added in two pieces to exercise the streaming interface.
+
+use core::cell::Cell;
+use kernel::common::cells::TakeCell;
+use kernel::hil::digest::{self, Digest, HMACSha256, Sha256, SHA256_DIGEST_LEN};
+use kernel::ReturnCode;
+
+pub struct TestSha256<'a, D: 'a> {
+    digest: &'a D,
+
+    data: TakeCell<'static, [u8]>,
+    dest: TakeCell<'static, [u8]>,
+
+    hmac: Cell<bool>,
+    // Bytes of the message added so far
+    added: Cell<usize>,
+}
+
+impl<'a, D: Digest<'a> + Sha256 + HMACSha256> TestSha256<'a, D> {
+    pub fn new(digest: &'a D, data: &'static mut [u8], dest: &'static mut [u8]) -> Self {
+        TestSha256 {
+            digest: digest,
+            data: TakeCell::new(data),
+            dest: TakeCell::new(dest),
+            hmac: Cell::new(false),
+            added: Cell::new(0),
+        }
+    }
+
+    pub fn run(&self) {
+        let res = if self.hmac.get() {
+            self.digest.set_mode_hmacsha256(&HMAC_KEY)
+        } else {
+            self.digest.set_mode_sha256()
+        };
+        assert!(res == ReturnCode::SUCCESS);
+
+        self.added.set(0);
+        self.add_next();
+    }
+
+    fn message(&self) -> &'static [u8] {
+        if self.hmac.get() {
+            &HMAC_MSG
+        } else {
+            &SHA256_MSG
+        }
+    }
+
+    fn add_next(&self) {
+        let message = self.message();
+        let added = self.added.get();
+        if added == message.len() {
+            let (res, _) = self.digest.run(self.dest.take().unwrap());
+            if res != ReturnCode::SUCCESS {
+                panic!("run() failed: {:?}", res);
+            }
+            return;
+        }
+
+        // First add half of the message, then the rest
+        let len = if added == 0 {
+            message.len() / 2
+        } else {
+            message.len() - added
+        };
+        let data = self.data.take().unwrap();
+        data[..len].copy_from_slice(&message[added..added + len]);
+        self.added.set(added + len);
+        let (res, _) = self.digest.add_data(data, len);
+        if res != ReturnCode::SUCCESS {
+            panic!("add_data() failed: {:?}", res);
+        }
+    }
+}
+
+impl<'a, D: Digest<'a> + Sha256 + HMACSha256> digest::Client for TestSha256<'a, D> {
+    fn add_data_done(&self, result: ReturnCode, data: &'static mut [u8]) {
+        self.data.replace(data);
+        if result != ReturnCode::SUCCESS {
+            panic!("add_data_done: {:?}", result);
+        }
+        self.add_next();
+    }
+
+    fn hash_done(&self, result: ReturnCode, digest: &'static mut [u8]) {
+        let expected = if self.hmac.get() {
+            &HMAC_TAG
+        } else {
+            &SHA256_DIGEST
+        };
+        if result == ReturnCode::SUCCESS && &digest[..SHA256_DIGEST_LEN] == expected.as_ref() {
+            debug!(
+                "OK! ({})",
+                if self.hmac.get() {
+                    "HMAC-SHA256"
+                } else {
+                    "SHA-256"
+                }
+            );
+        } else {
+            panic!("FAIL");
+        }
+        self.dest.replace(digest);
+
+        // Continue testing with HMAC
+        if !self.hmac.get() {
+            self.hmac.set(true);
+            self.run();
+        }
+    }
+}
+
+// FIPS 180-2, Appendix B.2
+static SHA256_MSG: [u8; 56] = *b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+
+static SHA256_DIGEST: [u8; SHA256_DIGEST_LEN] = [
+    0x24, 0x8d, 0x6a, 0x61, 0xd2, 0x06, 0x38, 0xb8, 0xe5, 0xc0, 0x26, 0x93, 0x0c, 0x3e, 0x60, 0x39,
+    0xa3, 0x3c, 0xe4, 0x59, 0x64, 0xff, 0x21, 0x67, 0xf6, 0xec, 0xed, 0xd4, 0x19, 0xdb, 0x06, 0xc1,
+];
+
+// RFC 4231, test case 2
+static HMAC_KEY: [u8; 4] = *b"Jefe";
+
+static HMAC_MSG: [u8; 28] = *b"what do ya want for nothing?";
+
+static HMAC_TAG: [u8; SHA256_DIGEST_LEN] = [
+    0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95, 0x75, 0xc7,
+    0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec, 0x38, 0x43,
+];
@@ -1,13 +1,26 @@
 //! Virtualize the Alarm interface to enable multiple users of an underlying
 //! alarm hardware peripheral.
+//!
+//! The mux also extends the hardware counter to a 64-bit clock, available
+//! through [`Alarm64`](../../kernel/hil/time/trait.Alarm64.html). To notice
+//! every wrap of the counter, the mux keeps the hardware alarm set at most
+//! half a wrap ahead, even when no virtual alarm is armed. The clock starts
+//! when it is first read or an alarm is first set.
+//!
+//! Deadlines are kept as 64-bit times, so virtual alarms are ordered and
+//! expired correctly across wraps of the hardware counter. A deadline set
+//! with [`set_alarm`](../../kernel/hil/time/trait.Alarm.html#tymethod.set_alarm)
+//! is taken relative to the last time the mux serviced its alarms, as the
+//! underlying `Alarm` would.
 
 use core::cell::Cell;
+use core::cmp;
 use kernel::common::{List, ListLink, ListNode};
-use kernel::hil::time::{self, Alarm, Time};
+use kernel::hil::time::{self, Alarm, Alarm64, Time};
 
 pub struct VirtualMuxAlarm<'a, Alrm: Alarm + 'a> {
     mux: &'a MuxAlarm<'a, Alrm>,
-    when: Cell<u32>,
+    when: Cell<u64>,
     armed: Cell<bool>,
     next: ListLink<'a, VirtualMuxAlarm<'a, Alrm>>,
     client: Cell<Option<&'a time::Client>>,
@@ -36,6 +49,15 @@ impl<'a, Alrm: Alarm> VirtualMuxAlarm<'a, Alrm> {
         self.armed.set(false);
         self.client.set(Some(client));
     }
+
+    fn arm(&self, when: u64) {
+        if !self.armed.get() {
+            self.mux.enabled.set(self.mux.enabled.get() + 1);
+            self.armed.set(true);
+        }
+        self.when.set(when);
+        self.mux.set_next_alarm();
+    }
 }
 
 impl<'a, Alrm: Alarm> Time for VirtualMuxAlarm<'a, Alrm> {
@@ -47,15 +69,11 @@ impl<'a, Alrm: Alarm> Time for VirtualMuxAlarm<'a, Alrm> {
         }
 
         self.armed.set(false);
+        self.mux.enabled.set(self.mux.enabled.get() - 1);
 
-        let enabled = self.mux.enabled.get() - 1;
-        self.mux.enabled.set(enabled);
-
-        // If there are not more enabled alarms, disable the underlying alarm
-        // completely.
-        if enabled == 0 {
-            self.mux.alarm.disable();
-        }
+        // The underlying alarm stays set, both because another virtual alarm
+        // may be waiting for it and to keep track of wraps. It will find
+        // nothing to do when it fires.
     }
 
     fn is_armed(&self) -> bool {
@@ -69,30 +87,34 @@ impl<'a, Alrm: Alarm> Alarm for VirtualMuxAlarm<'a, Alrm> {
     }
 
     fn set_alarm(&self, when: u32) {
-        let enabled = self.mux.enabled.get();
+        let reference = self.mux.prev.get() as u32 & self.max_tics();
+        self.set_alarm_from(reference, when.wrapping_sub(reference) & self.max_tics());
+    }
 
-        if !self.is_armed() {
-            self.mux.enabled.set(enabled + 1);
-            self.armed.set(true);
-        }
+    fn set_alarm_from(&self, reference: u32, dt: u32) {
+        let reference = self.mux.extend(reference);
+        self.arm(reference + dt as u64);
+    }
 
-        if enabled > 0 {
-            let cur_alarm = self.mux.alarm.get_alarm();
-            let now = self.now();
+    fn get_alarm(&self) -> u32 {
+        self.when.get() as u32 & self.max_tics()
+    }
 
-            if cur_alarm.wrapping_sub(now) > when.wrapping_sub(now) {
-                self.mux.prev.set(self.mux.alarm.now());
-                self.mux.alarm.set_alarm(when);
-            }
-        } else {
-            self.mux.prev.set(self.mux.alarm.now());
-            self.mux.alarm.set_alarm(when);
-        }
+    fn max_tics(&self) -> u32 {
+        self.mux.alarm.max_tics()
+    }
+}
 
-        self.when.set(when);
+impl<'a, Alrm: Alarm> Alarm64 for VirtualMuxAlarm<'a, Alrm> {
+    fn now64(&self) -> u64 {
+        self.mux.now64()
     }
 
-    fn get_alarm(&self) -> u32 {
+    fn set_alarm64(&self, reference: u64, dt: u64) {
+        self.arm(reference.saturating_add(dt));
+    }
+
+    fn get_alarm64(&self) -> u64 {
         self.when.get()
     }
 }
@@ -108,7 +130,11 @@ impl<'a, Alrm: Alarm> time::Client for VirtualMuxAlarm<'a, Alrm> {
 pub struct MuxAlarm<'a, Alrm: Alarm + 'a> {
     virtual_alarms: List<'a, VirtualMuxAlarm<'a, Alrm>>,
     enabled: Cell<usize>,
-    prev: Cell<u32>,
+    /// When the alarms were last serviced or the underlying alarm last set
+    prev: Cell<u64>,
+    /// The 64-bit time at the last reading of the counter
+    last: Cell<u64>,
+    started: Cell<bool>,
     alarm: &'a Alrm,
 }
 
@@ -118,53 +144,329 @@ impl<'a, Alrm: Alarm> MuxAlarm<'a, Alrm> {
             virtual_alarms: List::new(),
             enabled: Cell::new(0),
             prev: Cell::new(0),
+            last: Cell::new(0),
+            started: Cell::new(false),
             alarm: alarm,
         }
     }
-}
 
-fn has_expired(alarm: u32, now: u32, prev: u32) -> bool {
-    now.wrapping_sub(prev) >= alarm.wrapping_sub(prev)
+    /// Returns the current time on the 64-bit clock.
+    pub fn now64(&self) -> u64 {
+        if !self.started.get() {
+            // Keep the underlying alarm set from now on so no wrap is missed
+            self.started.set(true);
+            self.last.set(self.alarm.now() as u64);
+            self.set_next_alarm();
+        }
+
+        let mask = self.alarm.max_tics();
+        let last = self.last.get();
+        let now = last + (self.alarm.now().wrapping_sub(last as u32) & mask) as u64;
+        self.last.set(now);
+        now
+    }
+
+    /// Returns the 64-bit time of the most recent instant, no later than now,
+    /// at which the counter read `tics`.
+    fn extend(&self, tics: u32) -> u64 {
+        let now = self.now64();
+        let ago = (now as u32).wrapping_sub(tics) & self.alarm.max_tics();
+        now.saturating_sub(ago as u64)
+    }
+
+    /// The longest the underlying alarm may be set ahead, half a wrap.
+    fn max_dt(&self) -> u64 {
+        (self.alarm.max_tics() as u64 + 1) / 2
+    }
+
+    /// Set the underlying alarm to the soonest armed deadline, or half a wrap
+    /// ahead if that is sooner. Returns the soonest deadline, if any.
+    fn set_next_alarm(&self) -> Option<u64> {
+        let now = self.now64();
+        let next = self.virtual_alarms
+            .iter()
+            .filter(|cur| cur.armed.get())
+            .map(|cur| cur.when.get())
+            .min();
+
+        // A deadline that has passed is set just ahead, since the hardware
+        // may not fire for a time it has already reached.
+        let deadline = next.map_or(now + self.max_dt(), |when| {
+            cmp::min(cmp::max(when, now + 1), now + self.max_dt())
+        });
+        self.prev.set(now);
+        self.alarm.set_alarm(deadline as u32 & self.alarm.max_tics());
+        next
+    }
 }
 
 impl<'a, Alrm: Alarm> time::Client for MuxAlarm<'a, Alrm> {
     fn fired(&self) {
-        let now = self.alarm.now();
-
-        // Capture this before the loop because it can change while checking
-        // each alarm. If a timer fires, it can immediately set a new timer
-        // by calling `VirtualMuxAlarm.set_alarm()` which can change `self.prev`
-        // to the current timer time.
-        let prev = self.prev.get();
+        let now = self.now64();
 
         // Check whether to fire each alarm. At this level, alarms are one-shot,
         // so a repeating client will set it again in the fired() callback.
         self.virtual_alarms
             .iter()
-            .filter(|cur| cur.armed.get() && has_expired(cur.when.get(), now, prev))
+            .filter(|cur| cur.armed.get() && cur.when.get() <= now)
             .for_each(|cur| {
                 cur.armed.set(false);
                 self.enabled.set(self.enabled.get() - 1);
                 cur.fired();
             });
 
-        // Find the soonest alarm client (if any) and set the "next" underlying
-        // alarm based on it.  This needs to happen after firing all expired
-        // alarms since those may have reset new alarms.
-        let next = self.virtual_alarms
-            .iter()
-            .filter(|cur| cur.armed.get())
-            .min_by_key(|cur| cur.when.get().wrapping_sub(now));
-
-        self.prev.set(now);
-        // If there is an alarm to fire, set the underlying alarm to it
-        if let Some(valrm) = next {
-            self.alarm.set_alarm(valrm.when.get());
-            if has_expired(valrm.when.get(), self.alarm.now(), prev) {
+        // Set the underlying alarm for the soonest alarm client, if any. This
+        // needs to happen after firing all expired alarms since those may
+        // have set new alarms. An alarm that expired meanwhile is serviced
+        // right away.
+        if let Some(next) = self.set_next_alarm() {
+            if next <= self.now64() {
                 self.fired();
             }
-        } else {
-            self.alarm.disable();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    //! The mux runs over `FakeAlarm`, a counter the tests move forward by
+    //! hand, so they can start the counter just before it wraps. Counters of
+    //! any width can be tested, such as the 24-bit counter of the nRF5x RTC.
+
+    extern crate std;
+
+    use self::std::boxed::Box;
+    use super::{MuxAlarm, VirtualMuxAlarm};
+    use core::cell::Cell;
+    use core::cmp;
+    use kernel::hil::time::{self, Alarm, Alarm64, Time};
+
+    /// The clock, the mux and their clients refer to each other, as they do
+    /// when a board allocates them statically.
+    fn leak<T>(value: T) -> &'static T {
+        Box::leak(Box::new(value))
+    }
+
+    /// A counter that only moves when told to.
+    struct FakeAlarm<'a> {
+        now: Cell<u32>,
+        alarm: Cell<u32>,
+        armed: Cell<bool>,
+        max_tics: u32,
+        client: Cell<Option<&'a time::Client>>,
+    }
+
+    impl<'a> FakeAlarm<'a> {
+        fn new(start: u32, max_tics: u32) -> FakeAlarm<'a> {
+            FakeAlarm {
+                now: Cell::new(start & max_tics),
+                alarm: Cell::new(0),
+                armed: Cell::new(false),
+                max_tics: max_tics,
+                client: Cell::new(None),
+            }
+        }
+
+        fn set_client(&self, client: &'a time::Client) {
+            self.client.set(Some(client));
+        }
+
+        /// Move the counter forward by `tics`, firing the alarm on the way
+        /// each time the counter reaches it.
+        fn advance(&self, mut tics: u64) {
+            while tics > 0 {
+                let step = if self.armed.get() {
+                    let dist = self.alarm.get().wrapping_sub(self.now.get()) & self.max_tics;
+                    // An alarm set for the current value fires after a full wrap
+                    let dist = if dist == 0 {
+                        self.max_tics as u64 + 1
+                    } else {
+                        dist as u64
+                    };
+                    cmp::min(dist, tics)
+                } else {
+                    tics
+                };
+                self.now
+                    .set((self.now.get() as u64 + step) as u32 & self.max_tics);
+                tics -= step;
+
+                if self.armed.get() && self.now.get() == self.alarm.get() {
+                    self.armed.set(false);
+                    self.client.get().map(|client| client.fired());
+                }
+            }
+        }
+
+        /// Move the counter forward until it is `before` tics short of
+        /// wrapping.
+        fn advance_to_wrap(&self, before: u64) {
+            let wrap = self.max_tics as u64 + 1;
+            let now = self.now.get() as u64;
+            self.advance((2 * wrap - before - now) % wrap);
+        }
+    }
+
+    impl<'a> Time for FakeAlarm<'a> {
+        type Frequency = time::Freq32KHz;
+
+        fn disable(&self) {
+            self.armed.set(false);
+        }
+
+        fn is_armed(&self) -> bool {
+            self.armed.get()
+        }
+    }
+
+    impl<'a> Alarm for FakeAlarm<'a> {
+        fn now(&self) -> u32 {
+            self.now.get()
+        }
+
+        fn set_alarm(&self, tics: u32) {
+            self.alarm.set(tics & self.max_tics);
+            self.armed.set(true);
+        }
+
+        fn get_alarm(&self) -> u32 {
+            self.alarm.get()
+        }
+
+        fn max_tics(&self) -> u32 {
+            self.max_tics
+        }
+    }
+
+    /// Records when a virtual alarm fired, on the 64-bit clock.
+    struct FiredAt<'a> {
+        mux: &'a MuxAlarm<'a, FakeAlarm<'a>>,
+        at: Cell<Option<u64>>,
+    }
+
+    impl<'a> time::Client for FiredAt<'a> {
+        fn fired(&self) {
+            self.at.set(Some(self.mux.now64()));
+        }
+    }
+
+    /// Check that `alarm` fires exactly `dt` tics after `reference`, moving
+    /// the clock forward as needed.
+    fn expect_after(
+        clock: &FakeAlarm,
+        alarm: &VirtualMuxAlarm<FakeAlarm>,
+        fired: &FiredAt,
+        reference: u64,
+        dt: u64,
+    ) {
+        let remaining = reference + dt - fired.mux.now64();
+        clock.advance(remaining - 1);
+        assert_eq!(fired.at.get(), None);
+        clock.advance(1);
+        assert_eq!(fired.at.get(), Some(reference + dt));
+        assert!(!alarm.is_armed());
+        fired.at.set(None);
+    }
+
+    /// Run the tests on a counter that starts shortly before it wraps.
+    fn run_with_counter(start: u32, max_tics: u32) {
+        let clock = leak(FakeAlarm::new(start, max_tics));
+        let mux = leak(MuxAlarm::new(clock));
+        clock.set_client(mux);
+        let alarms = [
+            leak(VirtualMuxAlarm::new(mux)),
+            leak(VirtualMuxAlarm::new(mux)),
+        ];
+        let fired = [
+            leak(FiredAt {
+                mux: mux,
+                at: Cell::new(None),
+            }),
+            leak(FiredAt {
+                mux: mux,
+                at: Cell::new(None),
+            }),
+        ];
+        alarms[0].set_client(fired[0]);
+        alarms[1].set_client(fired[1]);
+        let wrap = max_tics as u64 + 1;
+
+        // The 64-bit clock keeps counting past the wrap
+        let start = mux.now64();
+        clock.advance(0x200);
+        assert_eq!(mux.now64(), start + 0x200);
+        clock.advance(3 * wrap + 7);
+        assert_eq!(mux.now64(), start + 0x200 + 3 * wrap + 7);
+
+        // A deadline past the next wrap does not fire early
+        clock.advance_to_wrap(0x100);
+        let now = mux.now64();
+        alarms[0].set_alarm(clock.now().wrapping_add(0x200));
+        expect_after(clock, alarms[0], fired[0], now, 0x200);
+
+        // Deadlines on either side of a wrap fire in order
+        clock.advance_to_wrap(0x100);
+        let now = mux.now64();
+        alarms[1].set_alarm_from(clock.now(), 0x120);
+        alarms[0].set_alarm64(now, 0xe0);
+        expect_after(clock, alarms[0], fired[0], now, 0xe0);
+        assert_eq!(fired[1].at.get(), None);
+        expect_after(clock, alarms[1], fired[1], now, 0x120);
+
+        // A deadline more than a wrap away fires at the right time
+        let now = mux.now64();
+        alarms[0].set_alarm64(now, 2 * wrap + 5);
+        expect_after(clock, alarms[0], fired[0], now, 2 * wrap + 5);
+
+        // A deadline that has passed fires as soon as possible
+        let now = mux.now64();
+        let reference = clock.now().wrapping_sub(10) & max_tics;
+        alarms[0].set_alarm_from(reference, 5);
+        clock.advance(1);
+        assert_eq!(fired[0].at.get(), Some(now + 1));
+        fired[0].at.set(None);
+
+        // A disabled alarm does not fire
+        alarms[0].set_alarm64(mux.now64(), 0x10);
+        alarms[0].disable();
+        clock.advance(wrap);
+        assert_eq!(fired[0].at.get(), None);
+    }
+
+    #[test]
+    fn wraps_32_bit_counter() {
+        run_with_counter(0xffff_ff00, 0xffff_ffff);
+    }
+
+    #[test]
+    fn wraps_24_bit_counter() {
+        run_with_counter(0x00ff_ff00, 0x00ff_ffff);
+    }
+
+    #[test]
+    fn default_set_alarm_from_fires_soon_for_past_deadlines() {
+        let clock = FakeAlarm::new(0x00ff_fff0, 0x00ff_ffff);
+        let reference = clock.now();
+        clock.advance(0x20);
+
+        // The deadline passed across the wrap, so the alarm is set just ahead
+        clock.set_alarm_from(reference, 0x10);
+        assert_eq!(clock.get_alarm(), clock.now() + 1);
+
+        // A deadline still ahead is kept
+        clock.set_alarm_from(reference, 0x30);
+        assert_eq!(clock.get_alarm(), (reference + 0x30) & 0x00ff_ffff);
+    }
+
+    #[test]
+    fn has_expired_wraps_with_the_counter() {
+        let clock = FakeAlarm::new(0x00ff_fff0, 0x00ff_ffff);
+        let reference = clock.now();
+        clock.advance(0x20);
+
+        assert_eq!(clock.elapsed(reference, clock.now()), 0x20);
+        assert!(clock.has_expired(reference, 0x20, clock.now()));
+        assert!(!clock.has_expired(reference, 0x21, clock.now()));
+        assert!(!clock.has_expired(reference, 0x0010_0000, clock.now()));
+    }
+}
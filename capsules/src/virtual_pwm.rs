@@ -0,0 +1,44 @@
+//! Share a PWM controller among users that each drive one of its pins.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let pwm_pin = static_init!(
+//!     capsules::virtual_pwm::PwmPinUser<'static, ChipPwm>,
+//!     capsules::virtual_pwm::PwmPinUser::new(&chip_pwm, &chip_pwm_pin)
+//! );
+//! ```
+
+use kernel::hil::pwm::{Pwm, PwmPin};
+use kernel::ReturnCode;
+
+/// A pin of a `Pwm` controller, usable as a `PwmPin`.
+pub struct PwmPinUser<'a, P: Pwm + 'a> {
+    pwm: &'a P,
+    pin: &'a P::Pin,
+}
+
+impl<'a, P: Pwm> PwmPinUser<'a, P> {
+    pub const fn new(pwm: &'a P, pin: &'a P::Pin) -> PwmPinUser<'a, P> {
+        PwmPinUser { pwm: pwm, pin: pin }
+    }
+}
+
+impl<'a, P: Pwm> PwmPin for PwmPinUser<'a, P> {
+    fn start(&self, frequency_hz: usize, duty_cycle: usize) -> ReturnCode {
+        self.pwm.start(self.pin, frequency_hz, duty_cycle)
+    }
+
+    fn stop(&self) -> ReturnCode {
+        self.pwm.stop(self.pin)
+    }
+
+    fn get_maximum_frequency_hz(&self) -> usize {
+        self.pwm.get_maximum_frequency_hz()
+    }
+
+    fn get_maximum_duty_cycle(&self) -> usize {
+        self.pwm.get_maximum_duty_cycle()
+    }
+}
@@ -5,6 +5,7 @@ use kernel;
 use peripheral_interrupts;
 use rtc;
 use uart;
+use wdt;
 
 pub struct Cc26X2 {
     mpu: cortexm4::mpu::MPU,
@@ -40,6 +41,7 @@ impl kernel::Chip for Cc26X2 {
                     peripheral_interrupts::AON_RTC => rtc::RTC.handle_interrupt(),
                     peripheral_interrupts::UART0 => uart::UART0.handle_interrupt(),
                     peripheral_interrupts::I2C => i2c::I2C0.handle_interrupt(),
+                    peripheral_interrupts::WATCHDOG => wdt::WDT.handle_interrupt(),
                     // AON Programmable interrupt
                     // We need to ignore JTAG events since some debuggers emit these
                     peripheral_interrupts::AON_PROG => (),
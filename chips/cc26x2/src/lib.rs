@@ -17,5 +17,6 @@ pub mod prcm;
 pub mod rtc;
 pub mod trng;
 pub mod uart;
+pub mod wdt;
 
 pub use crt1::init;
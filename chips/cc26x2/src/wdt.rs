@@ -0,0 +1,126 @@
+//! Watchdog timer for the cc26x2 family
+//!
+//! The watchdog counts down from a load value at the MCU clock divided by 32.
+//! When it first reaches zero it raises an interrupt and reloads; if the
+//! interrupt has not been cleared by the time it reaches zero again, it
+//! resets the chip. Servicing the watchdog clears the interrupt, which also
+//! reloads the counter, so the load value is half of the requested period.
+//!
+//! Once started, the watchdog can only be stopped by a reset. `stop()` and
+//! `suspend()` instead set the longest possible period, a little over 47
+//! minutes, and `resume()` restores the requested one.
+
+use core::cell::Cell;
+use kernel::common::regs::{ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+
+#[repr(C)]
+struct WdtRegisters {
+    load: ReadWrite<u32>,
+    _value: ReadOnly<u32>,
+    ctl: ReadWrite<u32, Control::Register>,
+    icr: WriteOnly<u32>,
+    _ris: ReadOnly<u32>,
+    _mis: ReadOnly<u32>,
+
+    _r0: [u8; 0x400],
+
+    test: ReadWrite<u32, Test::Register>,
+    _int_caus: ReadOnly<u32>,
+
+    _r1: [u8; 0x7E0],
+
+    lock: ReadWrite<u32>,
+}
+
+register_bitfields![
+    u32,
+    Control [
+        INTTYPE OFFSET(2) NUMBITS(1) [],
+        RESEN   OFFSET(1) NUMBITS(1) [],
+        INTEN   OFFSET(0) NUMBITS(1) []
+    ],
+    Test [
+        /// Stop the counter while the CPU is halted by a debugger
+        STALL OFFSET(8) NUMBITS(1) []
+    ]
+];
+
+const WDT_BASE: StaticRef<WdtRegisters> =
+    unsafe { StaticRef::new(0x40080000 as *const WdtRegisters) };
+
+/// Writing this to the lock register allows writes to the other registers.
+const UNLOCK_KEY: u32 = 0x1ACCE551;
+
+/// The watchdog counts at the 48MHz MCU clock divided by 32.
+const TICKS_PER_MS: u64 = 48_000 / 32;
+
+pub static mut WDT: Wdt = Wdt::new();
+
+pub struct Wdt {
+    registers: StaticRef<WdtRegisters>,
+    load: Cell<u32>,
+}
+
+impl Wdt {
+    const fn new() -> Wdt {
+        Wdt {
+            registers: WDT_BASE,
+            load: Cell::new(0),
+        }
+    }
+
+    /// Called on the first timeout. The interrupt is left pending so that the
+    /// second timeout resets the chip.
+    pub fn handle_interrupt(&self) {}
+
+    fn set_load(&self, load: u32) {
+        let regs = &*self.registers;
+        regs.lock.set(UNLOCK_KEY);
+        // Writing the load value also reloads the counter
+        regs.load.set(load);
+        regs.lock.set(0);
+    }
+}
+
+impl hil::watchdog::Watchdog for Wdt {
+    fn start(&self, period: usize) {
+        let load = period as u64 * TICKS_PER_MS / 2;
+        let load = if load > 0xFFFF_FFFF {
+            0xFFFF_FFFF
+        } else {
+            load as u32
+        };
+        self.load.set(load);
+
+        let regs = &*self.registers;
+        regs.lock.set(UNLOCK_KEY);
+        regs.test.modify(Test::STALL::SET);
+        regs.load.set(load);
+        regs.ctl
+            .write(Control::INTEN::SET + Control::RESEN::SET + Control::INTTYPE::CLEAR);
+        regs.lock.set(0);
+    }
+
+    fn stop(&self) {
+        self.load.set(0xFFFF_FFFF);
+        self.set_load(0xFFFF_FFFF);
+    }
+
+    fn tickle(&self) {
+        let regs = &*self.registers;
+        regs.lock.set(UNLOCK_KEY);
+        regs.icr.set(1);
+        regs.lock.set(0);
+    }
+
+    fn suspend(&self) {
+        self.set_load(0xFFFF_FFFF);
+    }
+
+    fn resume(&self) {
+        self.set_load(self.load.get());
+        self.tickle();
+    }
+}
@@ -14,3 +14,4 @@ pub mod rtc;
 pub mod temperature;
 pub mod timer;
 pub mod trng;
+pub mod wdt;
@@ -161,4 +161,9 @@ impl Alarm for Rtc {
     fn get_alarm(&self) -> u32 {
         self.registers.cc[0].read(CC::CC)
     }
+
+    fn max_tics(&self) -> u32 {
+        // The counter is 24 bits wide
+        0x00FF_FFFF
+    }
 }
@@ -0,0 +1,145 @@
+//! Watchdog timer, nRF5X-family
+//!
+//! The watchdog counts down from a reload value at 32.768 kHz and resets the
+//! chip when it reaches zero. It runs from the low frequency clock, which
+//! must be started before the watchdog.
+//!
+//! Once started, the watchdog can only be stopped by a reset, so `stop()`
+//! does nothing. It is configured to pause while the CPU sleeps and while the
+//! CPU is halted by a debugger, so `suspend()` and `resume()` need do nothing
+//! either.
+
+use kernel::common::regs::{ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil;
+
+#[repr(C)]
+struct WdtRegisters {
+    /// Start the watchdog
+    /// Address: 0x000 - 0x004
+    task_start: WriteOnly<u32, Task::Register>,
+    _reserved0: [u32; 63],
+    /// Watchdog timeout
+    /// Address: 0x100 - 0x104
+    _event_timeout: ReadWrite<u32, Event::Register>,
+    _reserved1: [u32; 128],
+    /// Enable interrupt
+    /// Address: 0x304 - 0x308
+    _intenset: ReadWrite<u32, Interrupt::Register>,
+    /// Disable interrupt
+    /// Address: 0x308 - 0x30C
+    _intenclr: ReadWrite<u32, Interrupt::Register>,
+    _reserved2: [u32; 61],
+    /// Whether the watchdog is running
+    /// Address: 0x400 - 0x404
+    runstatus: ReadOnly<u32, RunStatus::Register>,
+    /// Which reload requests are pending
+    /// Address: 0x404 - 0x408
+    _reqstatus: ReadOnly<u32>,
+    _reserved3: [u32; 63],
+    /// Counter reload value
+    /// Address: 0x504 - 0x508
+    crv: ReadWrite<u32>,
+    /// Enable reload request registers
+    /// Address: 0x508 - 0x50C
+    rren: ReadWrite<u32, ReloadRequestEnable::Register>,
+    /// Configuration
+    /// Address: 0x50C - 0x510
+    config: ReadWrite<u32, Config::Register>,
+    _reserved4: [u32; 60],
+    /// Reload request registers
+    /// Address: 0x600 - 0x620
+    rr: [WriteOnly<u32>; 8],
+}
+
+register_bitfields![u32,
+    Task [
+        ENABLE 0
+    ],
+
+    Event [
+        READY 0
+    ],
+
+    Interrupt [
+        TIMEOUT 0
+    ],
+
+    RunStatus [
+        RUNNING 0
+    ],
+
+    ReloadRequestEnable [
+        RR0 0
+    ],
+
+    Config [
+        /// Keep the watchdog running while the CPU is sleeping
+        SLEEP OFFSET(0) NUMBITS(1) [
+            Pause = 0,
+            Run = 1
+        ],
+        /// Keep the watchdog running while the CPU is halted by the debugger
+        HALT OFFSET(3) NUMBITS(1) [
+            Pause = 0,
+            Run = 1
+        ]
+    ]
+];
+
+const WDT_BASE: StaticRef<WdtRegisters> =
+    unsafe { StaticRef::new(0x40010000 as *const WdtRegisters) };
+
+/// Value to write to a reload request register to reload the counter.
+const RELOAD_VALUE: u32 = 0x6E524635;
+
+/// Frequency of the watchdog counter.
+const CLOCK_HZ: u64 = 32768;
+
+pub static mut WDT: Wdt = Wdt::new();
+
+pub struct Wdt {
+    registers: StaticRef<WdtRegisters>,
+}
+
+impl Wdt {
+    const fn new() -> Wdt {
+        Wdt {
+            registers: WDT_BASE,
+        }
+    }
+}
+
+impl hil::watchdog::Watchdog for Wdt {
+    fn start(&self, period: usize) {
+        let regs = &*self.registers;
+        if regs.runstatus.is_set(RunStatus::RUNNING) {
+            // The configuration can no longer be changed
+            return;
+        }
+
+        // The counter must be at least 0xF
+        let ticks = (period as u64 * CLOCK_HZ / 1000).saturating_sub(1);
+        let ticks = if ticks < 0xF {
+            0xF
+        } else if ticks > 0xFFFF_FFFF {
+            0xFFFF_FFFF
+        } else {
+            ticks
+        };
+
+        regs.crv.set(ticks as u32);
+        regs.rren.write(ReloadRequestEnable::RR0::SET);
+        regs.config.write(Config::SLEEP::Pause + Config::HALT::Pause);
+        regs.task_start.write(Task::ENABLE::SET);
+    }
+
+    fn stop(&self) {
+        // The watchdog cannot be stopped once started
+    }
+
+    fn tickle(&self) {
+        let regs = &*self.registers;
+        regs.rr[0].set(RELOAD_VALUE);
+    }
+}
@@ -179,6 +179,8 @@ fn poly_for_alg(alg: CrcAlg) -> FieldValue<u32, Mode::Register> {
         CrcAlg::Sam4L16 => Mode::PTYPE::Ccit16,
         CrcAlg::Sam4L32 => Mode::PTYPE::Ccit8023,
         CrcAlg::Sam4L32C => Mode::PTYPE::Castagnoli,
+        // Rejected by `compute()`
+        CrcAlg::Crc16CCITT | CrcAlg::Crc8 => Mode::PTYPE::Ccit16,
     }
 }
 
@@ -189,6 +191,7 @@ fn post_process(result: u32, alg: CrcAlg) -> u32 {
         CrcAlg::Sam4L16 => result,
         CrcAlg::Sam4L32 => result,
         CrcAlg::Sam4L32C => result,
+        CrcAlg::Crc16CCITT | CrcAlg::Crc8 => result,
     }
 }
 
@@ -341,6 +344,14 @@ impl<'a> crc::CRC for Crccu<'a> {
     fn compute(&self, data: &[u8], alg: CrcAlg) -> ReturnCode {
         let regs: &CrccuRegisters = unsafe { &*self.registers };
 
+        match alg {
+            CrcAlg::Crc16CCITT | CrcAlg::Crc8 => {
+                // The unit can only consume input from LSB to MSB
+                return ReturnCode::ENOSUPPORT;
+            }
+            _ => {}
+        }
+
         self.init();
 
         if self.get_tcr().interrupt_enabled() {
@@ -102,6 +102,9 @@ const WDT_REGS: StaticRef<WdtRegisters> =
 
 pub struct Wdt {
     enabled: Cell<bool>,
+    period: Cell<usize>,
+    /// Stopped by `suspend()`, to be restarted by `resume()`
+    suspended: Cell<bool>,
 }
 
 pub static mut WDT: Wdt = Wdt::new();
@@ -125,6 +128,8 @@ impl Wdt {
     const fn new() -> Wdt {
         Wdt {
             enabled: Cell::new(false),
+            period: Cell::new(0),
+            suspended: Cell::new(false),
         }
     }
 
@@ -172,6 +177,7 @@ impl Wdt {
 
     fn start(&self, period: usize) {
         self.enabled.set(true);
+        self.period.set(period);
 
         pm::enable_clock(Clock::PBD(PBDClock::WDT));
 
@@ -230,4 +236,22 @@ impl hil::watchdog::Watchdog for Wdt {
     fn tickle(&self) {
         self.tickle();
     }
+
+    fn suspend(&self) {
+        // The watchdog is clocked from OSC32K, which keeps running in deep
+        // sleep, and has no bit to pause it, so it has to be stopped.
+        if self.enabled.get() {
+            self.stop();
+            self.suspended.set(true);
+        }
+    }
+
+    fn resume(&self) {
+        if self.suspended.get() {
+            self.suspended.set(false);
+            self.start(self.period.get());
+        } else if self.enabled.get() {
+            self.tickle();
+        }
+    }
 }
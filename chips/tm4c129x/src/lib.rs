@@ -144,6 +144,8 @@ unsafe extern "C" fn hard_fault_handler() {
         let mmfar: u32 = core::ptr::read_volatile(0xE000ED34 as *const u32);
         let bfar: u32 = core::ptr::read_volatile(0xE000ED38 as *const u32);
 
+        kernel::crash_log::set_kernel_fault(stacked_pc, stacked_lr, cfsr, hfsr, mmfar, bfar);
+
         let iaccviol = (cfsr & 0x01) == 0x01;
         let daccviol = (cfsr & 0x02) == 0x02;
         let munstkerr = (cfsr & 0x08) == 0x08;
@@ -2,6 +2,7 @@
 
 use core::ptr::NonNull;
 use process;
+use returncode::ReturnCode;
 
 /// Userspace app identifier.
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
@@ -39,6 +40,13 @@ impl AppId {
     }
 }
 
+/// The driver and subscribe number a callback was registered with.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct CallbackId {
+    pub driver_num: usize,
+    pub subscribe_num: usize,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum RustOrRawFnPtr {
     Raw {
@@ -53,14 +61,21 @@ pub enum RustOrRawFnPtr {
 #[derive(Clone, Copy, Debug)]
 pub struct Callback {
     app_id: AppId,
+    callback_id: CallbackId,
     appdata: usize,
     fn_ptr: RustOrRawFnPtr,
 }
 
 impl Callback {
-    pub fn new(appid: AppId, appdata: usize, fn_ptr: NonNull<*mut ()>) -> Callback {
+    pub fn new(
+        appid: AppId,
+        callback_id: CallbackId,
+        appdata: usize,
+        fn_ptr: NonNull<*mut ()>,
+    ) -> Callback {
         Callback {
             app_id: appid,
+            callback_id: callback_id,
             appdata: appdata,
             fn_ptr: RustOrRawFnPtr::Raw { ptr: fn_ptr },
         }
@@ -69,12 +84,31 @@ impl Callback {
     pub const fn kernel_new(appid: AppId, fn_ptr: fn(usize, usize, usize, usize)) -> Callback {
         Callback {
             app_id: appid,
+            callback_id: CallbackId {
+                driver_num: 0,
+                subscribe_num: 0,
+            },
             appdata: 0,
             fn_ptr: RustOrRawFnPtr::Rust { func: fn_ptr },
         }
     }
 
+    /// Schedule the callback. Returns `false` if it could not be queued, see
+    /// `try_schedule()`.
     pub fn schedule(&mut self, r0: usize, r1: usize, r2: usize) -> bool {
+        self.try_schedule(r0, r1, r2) == ReturnCode::SUCCESS
+    }
+
+    /// Schedule the callback, reporting why it could not be queued:
+    ///
+    /// - `SUCCESS`: The callback was queued, or merged into an identical
+    ///   callback that was already queued if the process coalesces callbacks.
+    /// - `ENOMEM`: The process's callback queue is full. The callback was
+    ///   dropped, but scheduling it again may succeed once the process has
+    ///   handled some of its callbacks.
+    /// - `EOFF`: The process has faulted and will not run callbacks.
+    /// - `ENOAPP`: The process does not exist.
+    pub fn try_schedule(&mut self, r0: usize, r1: usize, r2: usize) -> ReturnCode {
         if self.app_id.is_kernel() {
             let fn_ptr = match self.fn_ptr {
                 RustOrRawFnPtr::Raw { ptr } => {
@@ -83,7 +117,7 @@ impl Callback {
                 RustOrRawFnPtr::Rust { func } => func,
             };
             fn_ptr(r0, r1, r2, self.appdata);
-            true
+            ReturnCode::SUCCESS
         } else {
             let fn_ptr = match self.fn_ptr {
                 RustOrRawFnPtr::Raw { ptr } => ptr,
@@ -98,6 +132,7 @@ impl Callback {
                     r2: r2,
                     r3: self.appdata,
                     pc: fn_ptr.as_ptr() as usize,
+                    callback_id: Some(self.callback_id),
                 },
                 self.app_id,
             )
@@ -3,6 +3,10 @@
 //! This is a tool to allow chip peripherals to schedule "interrupts"
 //! in the chip scheduler if the hardware doesn't support interrupts where
 //! they are needed.
+//!
+//! The tasks are enumerated by each chip and serviced by the chip's
+//! `service_pending_interrupts()`. Capsules, which are not tied to a chip,
+//! use `dynamic_deferred_call` instead.
 
 use core::cell::UnsafeCell;
 use core::convert::Into;
@@ -11,6 +15,7 @@ use core::convert::TryInto;
 use core::intrinsics;
 use core::marker::Copy;
 use core::marker::Sync;
+use trace;
 
 /// AtomicUsize with no CAS operations that works on targets that have "no atomic
 /// support" according to their specification. This makes it work on thumbv6
@@ -18,7 +23,7 @@ use core::marker::Sync;
 ///
 /// Borrowed from https://github.com/japaric/heapless/blob/master/src/ring_buffer/mod.rs
 /// See: https://github.com/japaric/heapless/commit/37c8b5b63780ed8811173dc1ec8859cd99efa9ad
-struct AtomicUsize {
+pub(crate) struct AtomicUsize {
     v: UnsafeCell<usize>,
 }
 
@@ -40,6 +45,10 @@ impl AtomicUsize {
     pub fn fetch_or_relaxed(&self, val: usize) {
         unsafe { intrinsics::atomic_store_relaxed(self.v.get(), self.load_relaxed() | val) }
     }
+
+    pub fn fetch_and_relaxed(&self, val: usize) {
+        unsafe { intrinsics::atomic_store_relaxed(self.v.get(), self.load_relaxed() & val) }
+    }
 }
 
 unsafe impl Sync for AtomicUsize {}
@@ -66,6 +75,7 @@ impl<T: Into<usize> + TryFrom<usize> + Copy> DeferredCall<T> {
 
     /// Set the `DeferredCall` as pending
     pub fn set(&self) {
+        trace::record(trace::Event::DeferredCallSet, None, self.0.into(), 0);
         DEFERRED_CALL.fetch_or_relaxed(1 << self.0.into() as usize);
     }
 
@@ -78,6 +88,7 @@ impl<T: Into<usize> + TryFrom<usize> + Copy> DeferredCall<T> {
             let bit = val.trailing_zeros() as usize;
             let new_val = val & !(1 << bit);
             DEFERRED_CALL.store_relaxed(new_val);
+            trace::record(trace::Event::DeferredCallRun, None, bit, 0);
             bit.try_into().ok()
         }
     }
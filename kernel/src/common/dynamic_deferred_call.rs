@@ -0,0 +1,96 @@
+//! Deferred calls for capsules.
+//!
+//! `deferred_call` only supports the tasks a chip enumerates, so it cannot
+//! be used by capsules that do not depend on a particular chip, such as
+//! software implementations of HILs. Here, any object implementing
+//! `DeferredCallClient` can register itself at board initialization and
+//! receives a `DeferredCallHandle`. Setting the handle schedules a call to
+//! the client, which the kernel main loop makes before it next runs
+//! processes, independently of the chip.
+//!
+//! Up to `MAX_CLIENTS` clients can be registered.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let software_crc = static_init!(
+//!     capsules::software_crc::SoftwareCrc<'static>,
+//!     capsules::software_crc::SoftwareCrc::new()
+//! );
+//! software_crc.initialize_deferred_call(
+//!     kernel::common::dynamic_deferred_call::register(software_crc)
+//!         .expect("no deferred call slot for software_crc"),
+//! );
+//! ```
+
+use common::deferred_call::AtomicUsize;
+use trace;
+
+/// Maximum number of registered clients.
+pub const MAX_CLIENTS: usize = 16;
+
+/// Task numbers of dynamic deferred calls in trace events start here, after
+/// the chip's tasks.
+pub const TRACE_TASK_BASE: usize = 32;
+
+/// An object that can be called back from the kernel main loop.
+pub trait DeferredCallClient {
+    /// Called once after each `set()` of `handle`. Calls made while the
+    /// client is being called are not lost.
+    fn call(&self, handle: DeferredCallHandle);
+}
+
+/// Identifies a registered client. Obtained from `register()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DeferredCallHandle(usize);
+
+impl DeferredCallHandle {
+    /// Schedule a call to the client. Setting the handle again before the
+    /// call is made has no further effect.
+    pub fn set(&self) {
+        trace::record(
+            trace::Event::DeferredCallSet,
+            None,
+            TRACE_TASK_BASE + self.0,
+            0,
+        );
+        PENDING.fetch_or_relaxed(1 << self.0);
+    }
+}
+
+static mut CLIENTS: [Option<&'static DeferredCallClient>; MAX_CLIENTS] = [None; MAX_CLIENTS];
+
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Register `client` for deferred calls. Returns `None` if `MAX_CLIENTS`
+/// clients are already registered.
+pub fn register(client: &'static DeferredCallClient) -> Option<DeferredCallHandle> {
+    unsafe {
+        CLIENTS.iter().position(|slot| slot.is_none()).map(|index| {
+            CLIENTS[index] = Some(client);
+            DeferredCallHandle(index)
+        })
+    }
+}
+
+/// Are there any pending calls?
+pub fn has_pending() -> bool {
+    PENDING.load_relaxed() != 0
+}
+
+/// Call every client whose handle was set. Calls scheduled by the clients
+/// themselves are left for the next round, so a client that keeps
+/// rescheduling itself cannot stall the main loop.
+pub(crate) fn service() {
+    let mut pending = PENDING.load_relaxed();
+    while pending != 0 {
+        let index = pending.trailing_zeros() as usize;
+        pending &= !(1 << index);
+        PENDING.fetch_and_relaxed(!(1 << index));
+
+        trace::record(trace::Event::DeferredCallRun, None, TRACE_TASK_BASE + index, 0);
+        let client = unsafe { CLIENTS[index] };
+        client.map(|client| client.call(DeferredCallHandle(index)));
+    }
+}
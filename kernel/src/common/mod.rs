@@ -7,6 +7,7 @@
 //! crates do not need to use unsafe code.
 
 pub mod deferred_call;
+pub mod dynamic_deferred_call;
 pub mod list;
 pub mod math;
 pub mod peripherals;
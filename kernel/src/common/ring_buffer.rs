@@ -17,6 +17,22 @@ impl<'a, T: Copy> RingBuffer<'a, T> {
             ring: ring,
         }
     }
+
+    /// Returns the oldest element for which `f` returns `true`.
+    pub fn find_mut<F>(&mut self, f: F) -> Option<&mut T>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let len = self.ring.len();
+        let mut i = self.head;
+        while i != self.tail {
+            if f(&self.ring[i]) {
+                return Some(&mut self.ring[i]);
+            }
+            i = (i + 1) % len;
+        }
+        None
+    }
 }
 
 impl<'a, T: Copy> queue::Queue<T> for RingBuffer<'a, T> {
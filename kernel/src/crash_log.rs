@@ -0,0 +1,264 @@
+//! Crash records kept across reboots.
+//!
+//! When the kernel panics, `debug::panic` writes a compact record of the
+//! crash into a RAM section that is neither initialized nor zeroed on boot, so
+//! it survives a reset. After the next boot the records can be read back, for
+//! example by `capsules::crash_log`, and cleared.
+//!
+//! Each record holds the program counter and link register at the fault, the
+//! Cortex-M fault status registers, the faulting process if any, the location
+//! of the panic and the kernel version. For panics caused by a process fault,
+//! PC and LR are the process's. For kernel hard faults they are the kernel's,
+//! as reported by the chip's hard fault handler. For other panics they are
+//! zero, and the file and line locate the panic.
+//!
+//! Memory that has lost power holds garbage, so each record carries a magic
+//! number and a checksum and only valid records are reported.
+//!
+//! Setup
+//! -----
+//!
+//! The board's linker script must provide a `.crash_log` section outside the
+//! memory initialized on boot. `boards/kernel_layout.ld` does this.
+
+use core::{mem, ptr, slice, str};
+
+/// Number of crash records kept. Once full, the oldest record is replaced.
+pub const CRASH_LOG_LEN: usize = 4;
+
+/// Length of the process name stored in a record.
+pub const APP_NAME_LEN: usize = 16;
+
+/// Length of the file name stored in a record. Longer paths keep their end.
+pub const FILE_NAME_LEN: usize = 32;
+
+/// Length of the kernel version stored in a record.
+pub const VERSION_LEN: usize = 16;
+
+/// Value of `app_index` when no process was at fault.
+pub const NO_APP: u32 = 0xffff_ffff;
+
+const MAGIC: u32 = 0x7c0c_c4a5;
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct CrashRecord {
+    magic: u32,
+    /// Increases with every crash, so records can be put in order.
+    pub sequence: u32,
+    pub pc: u32,
+    pub lr: u32,
+    pub cfsr: u32,
+    pub hfsr: u32,
+    pub mmfar: u32,
+    pub bfar: u32,
+    /// Line of the panic.
+    pub line: u32,
+    /// Index of the faulting process, or `NO_APP`.
+    pub app_index: u32,
+    app_name: [u8; APP_NAME_LEN],
+    file: [u8; FILE_NAME_LEN],
+    version: [u8; VERSION_LEN],
+    checksum: u32,
+}
+
+impl CrashRecord {
+    const fn empty() -> CrashRecord {
+        CrashRecord {
+            magic: 0,
+            sequence: 0,
+            pc: 0,
+            lr: 0,
+            cfsr: 0,
+            hfsr: 0,
+            mmfar: 0,
+            bfar: 0,
+            line: 0,
+            app_index: NO_APP,
+            app_name: [0; APP_NAME_LEN],
+            file: [0; FILE_NAME_LEN],
+            version: [0; VERSION_LEN],
+            checksum: 0,
+        }
+    }
+
+    /// Name of the faulting process, empty if none.
+    pub fn app_name(&self) -> &str {
+        str_from_padded(&self.app_name)
+    }
+
+    /// File of the panic, possibly missing its beginning.
+    pub fn file(&self) -> &str {
+        str_from_padded(&self.file)
+    }
+
+    /// Version of the kernel that crashed.
+    pub fn version(&self) -> &str {
+        str_from_padded(&self.version)
+    }
+
+    /// The record as bytes, in the layout of the struct.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(
+                self as *const CrashRecord as *const u8,
+                mem::size_of::<CrashRecord>(),
+            )
+        }
+    }
+
+    fn compute_checksum(&self) -> u32 {
+        let bytes = self.as_bytes();
+        // Everything but the checksum itself
+        bytes[..bytes.len() - 4]
+            .iter()
+            .fold(MAGIC, |acc, &b| acc.rotate_left(5) ^ b as u32)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.magic == MAGIC && self.checksum == self.compute_checksum()
+    }
+}
+
+/// Size in bytes of a record, as returned by `CrashRecord::as_bytes()`.
+pub const RECORD_LEN: usize = 4 * 10 + APP_NAME_LEN + FILE_NAME_LEN + VERSION_LEN + 4;
+
+#[link_section = ".crash_log"]
+static mut CRASH_LOG: [CrashRecord; CRASH_LOG_LEN] = [CrashRecord::empty(); CRASH_LOG_LEN];
+
+/// Details of the fault being handled, filled in before the kernel panics.
+struct Fault {
+    pc: u32,
+    lr: u32,
+    scb: [u32; 4],
+    app: Option<(usize, &'static str)>,
+}
+
+static mut FAULT: Fault = Fault {
+    pc: 0,
+    lr: 0,
+    scb: [0; 4],
+    app: None,
+};
+
+/// Note a kernel hard fault that is about to cause a panic. Called by the
+/// chip's hard fault handler.
+pub unsafe fn set_kernel_fault(pc: u32, lr: u32, cfsr: u32, hfsr: u32, mmfar: u32, bfar: u32) {
+    FAULT.pc = pc;
+    FAULT.lr = lr;
+    FAULT.scb = [cfsr, hfsr, mmfar, bfar];
+}
+
+/// Note a process fault that is about to cause a panic.
+pub(crate) unsafe fn set_app_fault(
+    app_index: usize,
+    app_name: &'static str,
+    pc: u32,
+    lr: u32,
+    scb: [u32; 4],
+) {
+    FAULT.pc = pc;
+    FAULT.lr = lr;
+    FAULT.scb = scb;
+    FAULT.app = Some((app_index, app_name));
+}
+
+/// Write a record of the current panic, replacing the oldest record if the
+/// log is full.
+pub(crate) unsafe fn record(file: &str, line: u32) {
+    let newest = CRASH_LOG
+        .iter()
+        .filter(|record| record.is_valid())
+        .map(|record| record.sequence)
+        .max();
+    let sequence = newest.map_or(0, |sequence| sequence.wrapping_add(1));
+
+    // Use a free slot if there is one, otherwise replace the oldest record.
+    let slot = CRASH_LOG
+        .iter()
+        .position(|record| !record.is_valid())
+        .unwrap_or_else(|| {
+            let mut oldest = 0;
+            for (i, record) in CRASH_LOG.iter().enumerate() {
+                if record.sequence < CRASH_LOG[oldest].sequence {
+                    oldest = i;
+                }
+            }
+            oldest
+        });
+
+    let mut record = CrashRecord::empty();
+    record.magic = MAGIC;
+    record.sequence = sequence;
+    record.pc = FAULT.pc;
+    record.lr = FAULT.lr;
+    record.cfsr = FAULT.scb[0];
+    record.hfsr = FAULT.scb[1];
+    record.mmfar = FAULT.scb[2];
+    record.bfar = FAULT.scb[3];
+    record.line = line;
+    if let Some((index, name)) = FAULT.app {
+        record.app_index = index as u32;
+        copy_padded(&mut record.app_name, name.as_bytes());
+    }
+    let file = file.as_bytes();
+    copy_padded(
+        &mut record.file,
+        &file[file.len().saturating_sub(FILE_NAME_LEN)..],
+    );
+    copy_padded(&mut record.version, env!("TOCK_KERNEL_VERSION").as_bytes());
+    record.checksum = record.compute_checksum();
+
+    ptr::write_volatile(&mut CRASH_LOG[slot], record);
+}
+
+/// Number of valid records.
+pub fn count() -> usize {
+    unsafe { CRASH_LOG.iter().filter(|record| record.is_valid()).count() }
+}
+
+/// The `n`th valid record, oldest first.
+pub fn get(n: usize) -> Option<CrashRecord> {
+    let mut records = [CrashRecord::empty(); CRASH_LOG_LEN];
+    let mut len = 0;
+    unsafe {
+        for record in CRASH_LOG.iter().filter(|record| record.is_valid()) {
+            // Insert in order of sequence number
+            let mut i = len;
+            while i > 0 && records[i - 1].sequence > record.sequence {
+                records[i] = records[i - 1];
+                i -= 1;
+            }
+            records[i] = *record;
+            len += 1;
+        }
+    }
+    if n < len {
+        Some(records[n])
+    } else {
+        None
+    }
+}
+
+/// Erase all records.
+pub fn clear() {
+    unsafe {
+        for record in CRASH_LOG.iter_mut() {
+            ptr::write_volatile(record, CrashRecord::empty());
+        }
+    }
+}
+
+fn copy_padded(dest: &mut [u8], src: &[u8]) {
+    let len = if src.len() < dest.len() {
+        src.len()
+    } else {
+        dest.len()
+    };
+    dest[..len].copy_from_slice(&src[..len]);
+}
+
+fn str_from_padded(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    str::from_utf8(&bytes[..len]).unwrap_or("")
+}
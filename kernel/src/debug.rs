@@ -38,6 +38,7 @@ use core::cmp::min;
 use core::fmt::{write, Arguments, Result, Write};
 use core::ptr::{read_volatile, write_volatile};
 use core::{slice, str};
+use crash_log;
 use driver::Driver;
 use hil;
 use mem::AppSlice;
@@ -58,6 +59,7 @@ pub unsafe fn panic<L: hil::led::Led, W: Write>(
     line: u32,
 ) -> ! {
     panic_begin();
+    crash_log::record(file, line);
     panic_banner(writer, args, file, line);
     // Flush debug buffer if needed
     flush(writer);
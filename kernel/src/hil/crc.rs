@@ -4,7 +4,8 @@ use returncode::ReturnCode;
 
 /// CRC algorithms
 ///
-/// In all cases, input bytes are bit-reversed (i.e., consumed from LSB to MSB.)
+/// Unless noted otherwise, input bytes are bit-reversed (i.e., consumed from
+/// LSB to MSB.)
 ///
 /// Algorithms prefixed with `Sam4L` are native to that chip and thus require
 /// no software post-processing on platforms using it.
@@ -22,6 +23,13 @@ pub enum CrcAlg {
     Sam4L32,
     /// Polynomial 0x1EDC6F41, no output post-processing
     Sam4L32C,
+
+    /// Polynomial 0x1021, initial value 0xFFFF, input consumed from MSB to
+    /// LSB, no output post-processing ("CRC-16-CCITT")
+    Crc16CCITT,
+    /// Polynomial 0x07, initial value 0x00, input consumed from MSB to LSB, no
+    /// output post-processing ("CRC-8")
+    Crc8,
 }
 
 pub trait CRC {
@@ -0,0 +1,226 @@
+//! Interface for wall-clock date and time.
+//!
+//! Time is kept in UTC as seconds since the Unix epoch,
+//! 1970-01-01T00:00:00Z, ignoring leap seconds. A [`Date`](struct.Date.html)
+//! is the same time broken down into calendar fields.
+//! [`date_from_seconds`](fn.date_from_seconds.html) and
+//! [`seconds_from_date`](fn.seconds_from_date.html) convert between the two.
+
+use returncode::ReturnCode;
+
+/// Latest year that can be represented.
+pub const MAX_YEAR: u16 = 9999;
+
+/// Seconds since the Unix epoch at the end of `MAX_YEAR`,
+/// 9999-12-31T23:59:59Z. Later times are not valid.
+pub const MAX_SECONDS: u64 = 253_402_300_799;
+
+/// A UTC date and time broken down into calendar fields.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Date {
+    /// Full year, 1970 to `MAX_YEAR`
+    pub year: u16,
+    /// Month, 1 to 12
+    pub month: u8,
+    /// Day of the month, 1 to 31
+    pub day: u8,
+    /// Hour, 0 to 23
+    pub hour: u8,
+    /// Minute, 0 to 59
+    pub minute: u8,
+    /// Second, 0 to 59
+    pub second: u8,
+    /// Day of the week, 0 for Sunday to 6 for Saturday. Ignored when setting
+    /// the date.
+    pub day_of_week: u8,
+}
+
+/// A wall clock that keeps time once it has been set.
+pub trait DateTime {
+    /// Returns the current time in seconds since the Unix epoch, or `None`
+    /// if the clock has not been set.
+    fn get_seconds(&self) -> Option<u64>;
+
+    /// Sets the current time in seconds since the Unix epoch. Returns
+    /// `EINVAL` if `seconds` is past `MAX_SECONDS`.
+    fn set_seconds(&self, seconds: u64) -> ReturnCode;
+
+    /// Returns the current date and time, or `None` if the clock has not
+    /// been set.
+    fn get_date(&self) -> Option<Date> {
+        self.get_seconds().map(date_from_seconds)
+    }
+
+    /// Sets the current date and time. Returns `EINVAL` if `date` is not a
+    /// valid date.
+    fn set_date(&self, date: Date) -> ReturnCode {
+        match seconds_from_date(&date) {
+            Some(seconds) => self.set_seconds(seconds),
+            None => ReturnCode::EINVAL,
+        }
+    }
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Days from 0000-03-01 to the Unix epoch in the proleptic Gregorian calendar.
+const EPOCH_DAYS: u64 = 719468;
+
+/// Days in a 400 year cycle.
+const DAYS_PER_ERA: u64 = 146097;
+
+fn is_leap_year(year: u64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Breaks down seconds since the Unix epoch, up to `MAX_SECONDS`, into a
+/// date.
+pub fn date_from_seconds(seconds: u64) -> Date {
+    let days = seconds / SECONDS_PER_DAY;
+    let time = seconds % SECONDS_PER_DAY;
+
+    // Count years from March, so that the leap day is the last of the year
+    let z = days + EPOCH_DAYS;
+    let era = z / DAYS_PER_ERA;
+    let day_of_era = z % DAYS_PER_ERA;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    Date {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+        hour: (time / 3600) as u8,
+        minute: (time / 60 % 60) as u8,
+        second: (time % 60) as u8,
+        // The epoch was a Thursday
+        day_of_week: ((days + 4) % 7) as u8,
+    }
+}
+
+/// Returns the seconds since the Unix epoch of a date, or `None` if the date
+/// is not valid, is before the epoch or is after `MAX_YEAR`.
+pub fn seconds_from_date(date: &Date) -> Option<u64> {
+    let year = date.year as u64;
+    let month = date.month as u64;
+    let day = date.day as u64;
+    if year < 1970
+        || year > MAX_YEAR as u64
+        || month < 1
+        || month > 12
+        || day < 1
+        || day > days_in_month(year, month)
+        || date.hour > 23
+        || date.minute > 59
+        || date.second > 59
+    {
+        return None;
+    }
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * DAYS_PER_ERA + day_of_era - EPOCH_DAYS;
+
+    Some(
+        days * SECONDS_PER_DAY + date.hour as u64 * 3600 + date.minute as u64 * 60
+            + date.second as u64,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{date_from_seconds, seconds_from_date, Date, MAX_SECONDS, SECONDS_PER_DAY};
+
+    fn date(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Date {
+        Date {
+            year: year,
+            month: month,
+            day: day,
+            hour: hour,
+            minute: minute,
+            second: second,
+            day_of_week: 0,
+        }
+    }
+
+    /// Check that `seconds` and `date`, falling on `day_of_week`, convert to
+    /// each other.
+    fn check(seconds: u64, date: Date, day_of_week: u8) {
+        assert_eq!(seconds_from_date(&date), Some(seconds));
+        assert_eq!(
+            date_from_seconds(seconds),
+            Date {
+                day_of_week: day_of_week,
+                ..date
+            }
+        );
+    }
+
+    #[test]
+    fn epoch() {
+        check(0, date(1970, 1, 1, 0, 0, 0), 4);
+    }
+
+    #[test]
+    fn leap_days() {
+        check(951_782_400, date(2000, 2, 29, 0, 0, 0), 2);
+        // 2100 is not a leap year, so March follows February 28th
+        check(4_107_542_400, date(2100, 3, 1, 0, 0, 0), 1);
+        assert_eq!(seconds_from_date(&date(2100, 2, 29, 0, 0, 0)), None);
+        assert_eq!(seconds_from_date(&date(1900, 2, 29, 0, 0, 0)), None);
+        assert_eq!(seconds_from_date(&date(2023, 2, 29, 0, 0, 0)), None);
+    }
+
+    #[test]
+    fn end_of_range() {
+        check(MAX_SECONDS, date(9999, 12, 31, 23, 59, 59), 5);
+        assert_eq!(seconds_from_date(&date(10000, 1, 1, 0, 0, 0)), None);
+    }
+
+    #[test]
+    fn invalid_fields() {
+        assert_eq!(seconds_from_date(&date(1969, 12, 31, 23, 59, 59)), None);
+        assert_eq!(seconds_from_date(&date(2018, 0, 1, 0, 0, 0)), None);
+        assert_eq!(seconds_from_date(&date(2018, 13, 1, 0, 0, 0)), None);
+        assert_eq!(seconds_from_date(&date(2018, 4, 31, 0, 0, 0)), None);
+        assert_eq!(seconds_from_date(&date(2018, 4, 30, 24, 0, 0)), None);
+        assert_eq!(seconds_from_date(&date(2018, 4, 30, 0, 60, 0)), None);
+        assert_eq!(seconds_from_date(&date(2018, 4, 30, 0, 0, 60)), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut previous = date_from_seconds(0);
+        let mut day = 0;
+        while day * SECONDS_PER_DAY <= MAX_SECONDS {
+            let seconds = day * SECONDS_PER_DAY + day % SECONDS_PER_DAY;
+            let date = date_from_seconds(seconds);
+            assert_eq!(seconds_from_date(&date), Some(seconds));
+            assert!(date.year >= previous.year);
+            previous = date;
+            day += 367;
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Interface for message digests (hashes) and keyed message authentication
+//! codes.
+//!
+//! A digest is computed by selecting a mode (e.g. `set_mode_sha256()`), then
+//! making any number of calls to `add_data()` followed by one call to `run()`.
+//! Each call completes asynchronously: the buffer passed in is handed back to
+//! the client in `add_data_done()` or `hash_done()` respectively, and no new
+//! call may be made until it has been. `clear_data()` abandons the message
+//! being hashed so that a new one can be started.
+
+use returncode::ReturnCode;
+
+/// Length in bytes of a SHA-256 digest (and of an HMAC-SHA256 tag).
+pub const SHA256_DIGEST_LEN: usize = 32;
+
+/// Implement this trait and use `set_client()` in order to receive callbacks
+/// from a `Digest` implementation.
+pub trait Client {
+    /// Called when the first `len` bytes of `data` passed to `add_data()`
+    /// have been added to the message. `data` is returned to the client.
+    fn add_data_done(&self, result: ReturnCode, data: &'static mut [u8]);
+
+    /// Called when the digest of the message has been written to the start
+    /// of `digest`, which is returned to the client. The implementation is
+    /// then ready for a new message in the same mode.
+    fn hash_done(&self, result: ReturnCode, digest: &'static mut [u8]);
+}
+
+pub trait Digest<'a> {
+    /// Set the client instance which will receive `add_data_done()` and
+    /// `hash_done()` callbacks
+    fn set_client(&'a self, client: &'a Client);
+
+    /// Add the first `len` bytes of `data` to the message.
+    ///
+    /// If `SUCCESS` is returned, `add_data_done()` will be called once the
+    /// data has been consumed. Otherwise `data` is returned along with the
+    /// error: `EBUSY` if an operation is already in progress, `ESIZE` if
+    /// `len` exceeds the length of `data`, and `EOFF` if no mode has been
+    /// selected.
+    fn add_data(
+        &self,
+        data: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Finish the message and write its digest to `digest`, which must be
+    /// at least as long as the digest of the selected mode.
+    ///
+    /// If `SUCCESS` is returned, `hash_done()` will be called with the
+    /// result. Otherwise `digest` is returned along with the error, as for
+    /// `add_data()`.
+    fn run(&self, digest: &'static mut [u8]) -> (ReturnCode, Option<&'static mut [u8]>);
+
+    /// Discard any data added to the message so far. The mode, and the key
+    /// for keyed modes, are kept.
+    fn clear_data(&self);
+}
+
+pub trait Sha256 {
+    /// Call before `Digest::add_data()` to compute a SHA-256 digest
+    fn set_mode_sha256(&self) -> ReturnCode;
+}
+
+pub trait HMACSha256 {
+    /// Call before `Digest::add_data()` to compute an HMAC-SHA256 tag using
+    /// `key`, which may be of any length.
+    fn set_mode_hmacsha256(&self, key: &[u8]) -> ReturnCode;
+}
@@ -1,6 +1,7 @@
 //! Interface for I2C master and slave peripherals.
 
 use core::fmt::{Display, Formatter, Result};
+use returncode::ReturnCode;
 
 /// The type of error encoutered during I2C communication.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -38,6 +39,19 @@ impl Display for Error {
     }
 }
 
+/// Both kinds of NACK map to `ENOACK`. Drivers that need to tell them apart,
+/// for example to report them to userspace, should pass the `Error` along.
+impl From<Error> for ReturnCode {
+    fn from(error: Error) -> ReturnCode {
+        match error {
+            Error::AddressNak | Error::DataNak => ReturnCode::ENOACK,
+            Error::ArbitrationLost => ReturnCode::EBUSY,
+            Error::Overrun => ReturnCode::FAIL,
+            Error::CommandComplete => ReturnCode::SUCCESS,
+        }
+    }
+}
+
 /// This specifies what type of transmission just finished from a Master device.
 #[derive(Copy, Clone, Debug)]
 pub enum SlaveTransmissionType {
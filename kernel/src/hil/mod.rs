@@ -4,12 +4,15 @@ pub mod adc;
 pub mod ble_advertising;
 pub mod crc;
 pub mod dac;
+pub mod date_time;
+pub mod digest;
 pub mod flash;
 pub mod gpio;
 pub mod gpio_async;
 pub mod i2c;
 pub mod led;
 pub mod nonvolatile_storage;
+pub mod pwm;
 pub mod radio;
 pub mod rng;
 pub mod sensors;
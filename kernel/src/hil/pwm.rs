@@ -0,0 +1,53 @@
+//! Interfaces for Pulse Width Modulation output.
+//!
+//! A [`Pwm`](trait.Pwm.html) controller drives a signal on any of its pins. A
+//! [`PwmPin`](trait.PwmPin.html) is a single output, such as a pin of a
+//! controller (see `capsules::virtual_pwm`) or a pin toggled in software, and
+//! is what capsules usually use.
+//!
+//! Duty cycles are given as a fraction of the maximum duty cycle of the
+//! implementation: a duty cycle of `0` keeps the pin low and a duty cycle
+//! equal to the maximum keeps it high.
+
+use returncode::ReturnCode;
+
+/// A PWM controller able to drive several pins.
+pub trait Pwm {
+    /// The chip-dependent type of a pin.
+    type Pin;
+
+    /// Start a PWM signal on `pin` with the given frequency in hertz and duty
+    /// cycle, replacing any signal already on the pin.
+    ///
+    /// Returns `EINVAL` if the frequency is zero or higher than the maximum,
+    /// or if the duty cycle is higher than the maximum.
+    fn start(&self, pin: &Self::Pin, frequency_hz: usize, duty_cycle: usize) -> ReturnCode;
+
+    /// Stop the signal on `pin`, leaving it low.
+    fn stop(&self, pin: &Self::Pin) -> ReturnCode;
+
+    /// The highest frequency supported, in hertz.
+    fn get_maximum_frequency_hz(&self) -> usize;
+
+    /// The duty cycle for which the signal is always high.
+    fn get_maximum_duty_cycle(&self) -> usize;
+}
+
+/// A single PWM output.
+pub trait PwmPin {
+    /// Start a PWM signal with the given frequency in hertz and duty cycle,
+    /// replacing any signal already on the pin.
+    ///
+    /// Returns `EINVAL` if the frequency is zero or higher than the maximum,
+    /// or if the duty cycle is higher than the maximum.
+    fn start(&self, frequency_hz: usize, duty_cycle: usize) -> ReturnCode;
+
+    /// Stop the signal, leaving the pin low.
+    fn stop(&self) -> ReturnCode;
+
+    /// The highest frequency supported, in hertz.
+    fn get_maximum_frequency_hz(&self) -> usize;
+
+    /// The duty cycle for which the signal is always high.
+    fn get_maximum_duty_cycle(&self) -> usize;
+}
@@ -81,6 +81,66 @@ pub trait Alarm: Time {
 
     /// Returns the value set in [`set_alarm`](#tymethod.set_alarm)
     fn get_alarm(&self) -> u32;
+
+    /// Sets a one-shot alarm to fire `dt` tics after `reference`.
+    ///
+    /// Unlike [`set_alarm`](#tymethod.set_alarm), the deadline is
+    /// unambiguous even if `reference` has already passed: the alarm fires as
+    /// soon as possible if `reference + dt` is in the past. `reference` is
+    /// usually a value previously returned by [`now`](#tymethod.now).
+    ///
+    /// The default implementation sets an alarm at `reference + dt`, or one
+    /// tic from now if that time has passed.
+    fn set_alarm_from(&self, reference: u32, dt: u32) {
+        let now = self.now();
+        let when = if self.has_expired(reference, dt, now) {
+            // Hardware may not fire for a time it has already reached
+            now.wrapping_add(1)
+        } else {
+            reference.wrapping_add(dt)
+        };
+        self.set_alarm(when & self.max_tics());
+    }
+
+    /// Returns the largest value of the counter, after which it wraps to
+    /// zero. This must be one less than a power of two.
+    fn max_tics(&self) -> u32 {
+        u32::max_value()
+    }
+
+    /// Returns the number of tics from `reference` to `now`, taking into
+    /// account that the counter wraps after [`max_tics`](#method.max_tics).
+    ///
+    /// This is correct as long as less than a full wrap of the counter has
+    /// elapsed since `reference`.
+    fn elapsed(&self, reference: u32, now: u32) -> u32 {
+        now.wrapping_sub(reference) & self.max_tics()
+    }
+
+    /// Returns whether the deadline `dt` tics after `reference` has passed at
+    /// time `now`. See [`elapsed`](#method.elapsed).
+    fn has_expired(&self, reference: u32, dt: u32, now: u32) -> bool {
+        self.elapsed(reference, now) >= dt
+    }
+}
+
+/// An alarm whose clock is extended to 64 bits by software, so that it does
+/// not wrap for the lifetime of the device.
+///
+/// Times are in the same units as the underlying [`Alarm`](trait.Alarm.html)
+/// and the client is signaled through the same
+/// [`Client`](trait.Client.html) trait.
+pub trait Alarm64: Time {
+    /// Returns the current time in hardware clock units, since the clock was
+    /// first read.
+    fn now64(&self) -> u64;
+
+    /// Sets a one-shot alarm to fire `dt` tics after `reference`, or as soon
+    /// as possible if that time has passed.
+    fn set_alarm64(&self, reference: u64, dt: u64);
+
+    /// Returns the deadline of the last alarm set.
+    fn get_alarm64(&self) -> u64;
 }
 
 /// A client of an implementor of the [`Alarm`](trait.Alarm.html) trait.
@@ -11,4 +11,17 @@ pub trait Watchdog {
     /// Service the watchdog to let the hardware know the application
     /// is still executing.
     fn tickle(&self);
+
+    /// Keep the watchdog from expiring while the chip sleeps. Called by the
+    /// kernel before it puts the chip to sleep. Watchdogs that pause on their
+    /// own while the chip sleeps need not do anything.
+    fn suspend(&self) {}
+
+    /// Undo `suspend()` after the chip wakes up, servicing the watchdog so
+    /// that time spent asleep is not counted against it. Called by the kernel
+    /// at the start of every pass of its loop, so it may be called when not
+    /// suspended.
+    fn resume(&self) {
+        self.tickle();
+    }
 }
@@ -1,11 +1,36 @@
 //! Inter-process communication mechanism for Tock.
 //!
-//! This is a special syscall driver that allows userspace applications to
+//! `IPC` is a special syscall driver that allows userspace applications to
 //! share memory.
+//!
+//! `MessageIPC` is a second syscall driver that lets applications exchange
+//! small messages instead. Messages are copied into a bounded mailbox in the
+//! receiver's grant, tagged with the sender's process id, so a service can
+//! serve any number of clients without setting up shared buffers with each
+//! of them. Requests are given an id by the kernel that the service echoes
+//! in its reply, letting clients match replies to outstanding requests. The
+//! kernel keeps track of each client's outstanding requests and refuses
+//! replies to anything else, so a reply cannot be forged.
 
 /// Syscall number
 pub const DRIVER_NUM: usize = 0x00010000;
 
+/// Syscall number of the message passing driver
+pub const MESSAGE_DRIVER_NUM: usize = 0x00010001;
+
+/// Maximum payload of a single message, in bytes
+pub const MAX_MESSAGE_LEN: usize = 32;
+
+/// Number of messages each process can have waiting to be received
+pub const MAILBOX_LEN: usize = 4;
+
+/// Length of the header written before the payload of a received message
+pub const MESSAGE_HEADER_LEN: usize = 8;
+
+/// Number of requests each process can have waiting for a reply. Sending
+/// another forgets the oldest, which can then no longer be replied to.
+pub const MAX_OUTSTANDING_REQUESTS: usize = 4;
+
 use callback::{AppId, Callback};
 use driver::Driver;
 use grant::Grant;
@@ -135,6 +160,11 @@ impl Driver for IPC {
     /// and notifying an IPC client is done by setting client_or_svc to 1.
     /// In either case, the target_id is the same number as provided in a notify
     /// callback or as returned by allow.
+    ///
+    /// A buffer the caller shared with the target is made accessible to the
+    /// target here. Notifying returns `EINVAL` if the buffer is not aligned to
+    /// its power-of-two length, and `ENOMEM` if `process::MAX_SHARED_REGIONS`
+    /// other buffers are already shared with the target.
     fn command(
         &self,
         target_id: usize,
@@ -144,7 +174,7 @@ impl Driver for IPC {
     ) -> ReturnCode {
         let procs = unsafe { &mut process::PROCS };
         if target_id == 0 || target_id > procs.len() {
-            return ReturnCode::EINVAL; /* Request to IPC to impossible process */
+            return ReturnCode::ENOAPP; /* Request to IPC to impossible process */
         }
 
         let cb_type = if client_or_svc == 0 {
@@ -153,13 +183,23 @@ impl Driver for IPC {
             process::IPCType::Client
         };
 
+        let exposed = self.data
+            .enter(appid, |data, _| match data.shared_memory.get(target_id - 1) {
+                Some(&Some(ref slice)) => unsafe { slice.expose_to(AppId::new(target_id - 1)) },
+                _ => ReturnCode::SUCCESS,
+            })
+            .unwrap_or(ReturnCode::SUCCESS);
+        if exposed != ReturnCode::SUCCESS {
+            return exposed;
+        }
+
         procs[target_id - 1]
             .as_mut()
             .map(|target| {
                 target.schedule_ipc(appid, cb_type);
                 ReturnCode::SUCCESS
             })
-            .unwrap_or(ReturnCode::EINVAL) /* Request to IPC to unknown process */
+            .unwrap_or(ReturnCode::ENOAPP) /* Request to IPC to unknown process */
     }
 
     /// allow enables processes to discover IPC services on the platform or
@@ -215,8 +255,266 @@ impl Driver for IPC {
                         *smem = slice;
                         ReturnCode::SUCCESS
                     })
-                    .unwrap_or(ReturnCode::EINVAL) /* Target process does not exist */
+                    .unwrap_or(ReturnCode::ENOAPP) /* Target process does not exist */
             })
             .unwrap_or(ReturnCode::EBUSY);
     }
 }
+
+#[derive(Copy, Clone, Default)]
+struct Message {
+    /// Process id (as used by `IPC`) of the sender
+    sender: usize,
+    reply: bool,
+    request_id: usize,
+    len: usize,
+    data: [u8; MAX_MESSAGE_LEN],
+}
+
+#[derive(Default)]
+struct Mailbox {
+    callback: Option<Callback>,
+    rx_buffer: Option<AppSlice<Shared, u8>>,
+    tx_buffer: Option<AppSlice<Shared, u8>>,
+
+    messages: [Message; MAILBOX_LEN],
+    head: usize,
+    count: usize,
+
+    // Id given to the last request this process sent
+    last_request_id: usize,
+    // Process id of the target and id of the requests this process sent that
+    // have not been replied to
+    outstanding: [Option<(usize, usize)>; MAX_OUTSTANDING_REQUESTS],
+    // Slot in `outstanding` to record the next request in
+    next_outstanding: usize,
+}
+
+impl Mailbox {
+    fn expect_reply(&mut self, target_id: usize, request_id: usize) {
+        self.outstanding[self.next_outstanding] = Some((target_id, request_id));
+        self.next_outstanding = (self.next_outstanding + 1) % MAX_OUTSTANDING_REQUESTS;
+    }
+
+    /// Forget the outstanding request a reply answers, returning false if
+    /// there is no such request.
+    fn take_reply(&mut self, sender_id: usize, request_id: usize) -> bool {
+        match self.outstanding
+            .iter_mut()
+            .find(|request| **request == Some((sender_id, request_id)))
+        {
+            Some(request) => {
+                *request = None;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub struct MessageIPC {
+    mailboxes: Grant<Mailbox>,
+}
+
+impl MessageIPC {
+    pub unsafe fn new() -> MessageIPC {
+        MessageIPC {
+            mailboxes: Grant::create(),
+        }
+    }
+
+    /// Copy the message in the sender's transmit buffer into the mailbox of
+    /// process `target_id`.
+    fn send(&self, sender: AppId, target_id: usize, reply_to: Option<usize>) -> ReturnCode {
+        let procs = unsafe { &process::PROCS };
+        if target_id == 0 || target_id > procs.len() || procs[target_id - 1].is_none() {
+            return ReturnCode::ENOAPP; /* No such process */
+        }
+        let target = AppId::new(target_id - 1);
+
+        // Copy the message out of the sender's grant first, as the target
+        // may be the sender itself
+        let message = self.mailboxes.enter(sender, |mailbox, _| {
+            let len = match mailbox.tx_buffer {
+                Some(ref slice) if slice.len() > MAX_MESSAGE_LEN => {
+                    return Err(ReturnCode::ESIZE);
+                }
+                Some(ref slice) => slice.len(),
+                None => return Err(ReturnCode::EINVAL),
+            };
+            let request_id = match reply_to {
+                Some(request_id) => request_id,
+                None => {
+                    // Request ids are never zero, and never repeat among the
+                    // requests a process can have outstanding
+                    mailbox.last_request_id = mailbox.last_request_id % 0xffffff + 1;
+                    mailbox.last_request_id
+                }
+            };
+
+            let mut message = Message {
+                sender: sender.idx() + 1,
+                reply: reply_to.is_some(),
+                request_id: request_id,
+                len: len,
+                data: [0; MAX_MESSAGE_LEN],
+            };
+            mailbox.tx_buffer.as_ref().map(|slice| {
+                message.data[..len].copy_from_slice(slice.as_ref());
+            });
+            Ok(message)
+        });
+        let message = match message {
+            Ok(Ok(message)) => message,
+            Ok(Err(err)) => return err,
+            Err(err) => return err.into(),
+        };
+
+        let result = self.mailboxes
+            .enter(target, |mailbox, _| {
+                if mailbox.callback.is_none() {
+                    return ReturnCode::EINVAL; /* Target does not accept messages */
+                }
+                if mailbox.count == MAILBOX_LEN {
+                    return ReturnCode::ENOMEM; /* Mailbox full, try again later */
+                }
+                if message.reply && !mailbox.take_reply(message.sender, message.request_id) {
+                    return ReturnCode::EINVAL; /* Not a reply to an outstanding request */
+                }
+                let tail = (mailbox.head + mailbox.count) % MAILBOX_LEN;
+                mailbox.messages[tail] = message;
+                mailbox.count += 1;
+                mailbox
+                    .callback
+                    .map(|mut cb| cb.schedule(message.sender, mailbox.count, 0));
+
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+        if result != ReturnCode::SUCCESS || message.reply {
+            return result;
+        }
+
+        self.mailboxes
+            .enter(sender, |mailbox, _| {
+                mailbox.expect_reply(target_id, message.request_id);
+                ReturnCode::SuccessWithValue {
+                    value: message.request_id,
+                }
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Move the oldest message in the mailbox of `appid` into its receive
+    /// buffer.
+    fn receive(&self, appid: AppId) -> ReturnCode {
+        self.mailboxes
+            .enter(appid, |mailbox, _| {
+                if mailbox.count == 0 {
+                    return ReturnCode::FAIL; /* Mailbox empty */
+                }
+                let message = mailbox.messages[mailbox.head];
+                match mailbox.rx_buffer {
+                    Some(ref mut slice) => {
+                        if slice.len() < MESSAGE_HEADER_LEN + message.len {
+                            return ReturnCode::ESIZE;
+                        }
+                        let buf = slice.as_mut();
+                        buf[0] = message.sender as u8;
+                        buf[1] = (message.sender >> 8) as u8;
+                        buf[2] = message.reply as u8;
+                        buf[3] = message.len as u8;
+                        for i in 0..4 {
+                            buf[4 + i] = (message.request_id >> (8 * i)) as u8;
+                        }
+                        buf[MESSAGE_HEADER_LEN..MESSAGE_HEADER_LEN + message.len]
+                            .copy_from_slice(&message.data[..message.len]);
+                    }
+                    None => return ReturnCode::EINVAL,
+                }
+                mailbox.head = (mailbox.head + 1) % MAILBOX_LEN;
+                mailbox.count -= 1;
+                ReturnCode::SuccessWithValue {
+                    value: message.len,
+                }
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl Driver for MessageIPC {
+    /// subscribe(0) registers the callback invoked each time a message
+    /// arrives in this process's mailbox. Its arguments are the sender's
+    /// process id and the number of messages now waiting. A process must
+    /// subscribe before others can send it messages.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self.mailboxes
+                .enter(app_id, |mailbox, _| {
+                    mailbox.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Send the transmit buffer as a request to process `data`.
+    ///        Returns the id of the request, which the service will quote in
+    ///        its reply.
+    /// - `2`: Send the transmit buffer as a reply to request `data2` of
+    ///        process `data`.
+    /// - `3`: Copy the oldest waiting message into the receive buffer,
+    ///        removing it from the mailbox. The buffer receives an 8 byte
+    ///        header (sender process id: u16, is reply: u8, payload length:
+    ///        u8, request id: u32, all little endian) followed by the payload.
+    ///        Returns the payload length, or `FAIL` if no message is waiting.
+    ///
+    /// Sending returns `ENOAPP` if the target process does not exist,
+    /// `EINVAL` if it does not accept messages because it has not subscribed,
+    /// `ESIZE` if the message is longer than `MAX_MESSAGE_LEN` and `ENOMEM` if
+    /// the target's mailbox is full. Replying also returns
+    /// `EINVAL` unless the request is one of the target's last
+    /// `MAX_OUTSTANDING_REQUESTS` requests to the caller and has not been
+    /// replied to yet.
+    fn command(&self, command_num: usize, data: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self.send(appid, data, None),
+            2 => self.send(appid, data, Some(data2)),
+            3 => self.receive(appid),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// ### `allow_num`
+    ///
+    /// - `0`: The receive buffer, which must have room for the header and
+    ///        the payload of received messages.
+    /// - `1`: The transmit buffer. Its length is the length of the message.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        self.mailboxes
+            .enter(appid, |mailbox, _| {
+                match allow_num {
+                    0 => mailbox.rx_buffer = slice,
+                    1 => mailbox.tx_buffer = slice,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
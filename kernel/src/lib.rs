@@ -15,8 +15,10 @@
 pub mod common;
 #[macro_use]
 pub mod debug;
+pub mod crash_log;
 pub mod hil;
 pub mod ipc;
+pub mod trace;
 
 mod callback;
 mod driver;
@@ -29,7 +31,7 @@ mod returncode;
 mod sched;
 mod syscall;
 
-pub use callback::{AppId, Callback};
+pub use callback::{AppId, Callback, CallbackId};
 pub use driver::Driver;
 pub use grant::Grant;
 pub use mem::{AppPtr, AppSlice, Private, Shared};
@@ -43,15 +45,23 @@ pub use returncode::ReturnCode;
 // functions and types are used by board files to setup the platform and setup
 // processes.
 pub mod procs {
-    pub use process::{load_processes, FaultResponse, Process};
+    pub use process::{load_processes, number_of_process_slots, process_name, process_stats,
+                      FaultKind, FaultResponse, Process, ProcessStats, DRIVER_SYSCALL_SLOTS};
 }
 
 /// Main loop.
+///
+/// If a `watchdog` is given, it must already be started. It is serviced each
+/// time the kernel gets through servicing interrupts and running processes,
+/// so a capsule or interrupt handler that never returns resets the board. It
+/// is suspended just before the chip sleeps and resumed when the kernel
+/// wakes up, before it services the interrupt that woke it.
 pub fn main<P: Platform, C: Chip>(
     platform: &P,
     chip: &mut C,
     processes: &'static mut [Option<&mut process::Process<'static>>],
     ipc: Option<&ipc::IPC>,
+    watchdog: Option<&hil::watchdog::Watchdog>,
 ) {
     let processes = unsafe {
         process::PROCS = processes;
@@ -60,19 +70,34 @@ pub fn main<P: Platform, C: Chip>(
 
     loop {
         unsafe {
+            watchdog.map(|watchdog| watchdog.resume());
+
+            trace::record(trace::Event::InterruptsStart, None, 0, 0);
             chip.service_pending_interrupts();
+            common::dynamic_deferred_call::service();
+            trace::record(trace::Event::InterruptsEnd, None, 0, 0);
+            trace::drain();
 
             for (i, p) in processes.iter_mut().enumerate() {
                 p.as_mut().map(|process| {
                     sched::do_process(platform, chip, process, callback::AppId::new(i), ipc);
                 });
-                if chip.has_pending_interrupts() {
+                if chip.has_pending_interrupts()
+                    || common::dynamic_deferred_call::has_pending()
+                {
                     break;
                 }
             }
 
+            // The loop made progress
+            watchdog.map(|watchdog| watchdog.tickle());
+
             chip.atomic(|| {
-                if !chip.has_pending_interrupts() && process::processes_blocked() {
+                if !chip.has_pending_interrupts()
+                    && !common::dynamic_deferred_call::has_pending()
+                    && process::processes_blocked()
+                {
+                    watchdog.map(|watchdog| watchdog.suspend());
                     chip.sleep();
                 }
             });
@@ -7,6 +7,7 @@ use core::slice;
 
 use callback::AppId;
 use process;
+use returncode::ReturnCode;
 
 #[derive(Debug)]
 pub struct Private;
@@ -77,15 +78,17 @@ impl<L, T> AppSlice<L, T> {
         unsafe { self.ptr.ptr.as_ref() as *const T }
     }
 
-    pub unsafe fn expose_to(&self, appid: AppId) -> bool {
+    /// Give another process access to this buffer. See
+    /// `Process::add_mpu_region` for the errors returned.
+    pub unsafe fn expose_to(&self, appid: AppId) -> ReturnCode {
         let ps = &mut process::PROCS;
         if appid.idx() != self.ptr.process.idx() && ps.len() > appid.idx() {
             ps[appid.idx()]
                 .as_ref()
                 .map(|process| process.add_mpu_region(self.ptr() as *const u8, self.len() as u32))
-                .unwrap_or(false)
+                .unwrap_or(ReturnCode::ENOAPP)
         } else {
-            false
+            ReturnCode::EINVAL
         }
     }
 
@@ -21,6 +21,9 @@ pub trait SysTick {
     /// Returns if there is at least `us` microseconds left
     fn greater_than(&self, us: u32) -> bool;
 
+    /// Returns the number of microseconds left before the timer expires
+    fn get_value(&self) -> u32;
+
     /// Returns true if the timer has expired
     fn overflowed(&self) -> bool;
 
@@ -49,6 +52,10 @@ impl SysTick for () {
 
     fn enable(&self, _: bool) {}
 
+    fn get_value(&self) -> u32 {
+        u32::max_value()
+    }
+
     fn overflowed(&self) -> bool {
         false
     }
@@ -1,19 +1,22 @@
 //! Support for creating and running userspace applications.
 
-use callback::AppId;
+use callback::{AppId, CallbackId};
 use common::cells::VolatileCell;
 use common::{Queue, RingBuffer};
 
 use core::cell::Cell;
+use core::fmt;
 use core::fmt::Write;
 use core::ptr::{read_volatile, write, write_volatile};
 use core::{mem, ptr, slice, str};
 use grant;
 
 use common::math;
+use crash_log;
 use platform::mpu;
 use returncode::ReturnCode;
 use syscall::Syscall;
+use trace;
 
 /// Takes a value and rounds it up to be aligned % 8
 macro_rules! align8 {
@@ -96,37 +99,66 @@ pub unsafe fn load_processes(
     }
 }
 
-pub fn schedule(callback: FunctionCall, appid: AppId) -> bool {
+/// Queue `callback` for the process. See `Callback::try_schedule()` for the
+/// return values.
+pub fn schedule(callback: FunctionCall, appid: AppId) -> ReturnCode {
     let procs = unsafe { &mut PROCS };
     let idx = appid.idx();
     if idx >= procs.len() {
-        return false;
+        return ReturnCode::ENOAPP;
     }
 
     match procs[idx] {
-        None => false,
+        None => ReturnCode::ENOAPP,
         Some(ref mut p) => {
             // If this app is in the `Fault` state then we shouldn't schedule
             // any work for it.
             if p.current_state() == State::Fault {
-                return false;
+                return ReturnCode::EOFF;
             }
 
-            unsafe {
-                HAVE_WORK.set(HAVE_WORK.get() + 1);
+            // A process waiting for this callback receives its arguments as
+            // the result of the syscall instead.
+            if let State::Waiting(callback_id) = p.current_state() {
+                if callback.callback_id == Some(callback_id) {
+                    p.complete_wait(callback);
+                    return ReturnCode::SUCCESS;
+                }
+            }
+
+            // A process that coalesces callbacks only needs the latest
+            // arguments of a callback that is already queued.
+            if p.header.coalesce_callbacks() && callback.callback_id.is_some() {
+                let queued = p.tasks.find_mut(|task| match *task {
+                    Task::FunctionCall(ref queued) => {
+                        queued.callback_id == callback.callback_id && queued.pc == callback.pc
+                    }
+                    Task::IPC(_) => false,
+                });
+                if let Some(task) = queued {
+                    *task = Task::FunctionCall(callback);
+                    trace::record(trace::Event::CallbackEnqueued, Some(appid), 0, 1);
+                    return ReturnCode::SUCCESS;
+                }
             }
 
             let ret = p.tasks.enqueue(Task::FunctionCall(callback));
+            trace::record(trace::Event::CallbackEnqueued, Some(appid), !ret as usize, 0);
 
-            // Make a note that we lost this callback if the enqueue function
-            // fails.
-            if ret == false {
+            if ret {
+                if p.tasks_are_work() {
+                    unsafe {
+                        HAVE_WORK.set(HAVE_WORK.get() + 1);
+                    }
+                }
+                ReturnCode::SUCCESS
+            } else {
+                // Make a note that we lost this callback.
                 p.debug
                     .dropped_callback_count
                     .set(p.debug.dropped_callback_count.get() + 1);
+                ReturnCode::ENOMEM
             }
-
-            ret
         }
     }
 }
@@ -163,7 +195,7 @@ impl From<Error> for ReturnCode {
         match err {
             Error::OutOfMemory => ReturnCode::ENOMEM,
             Error::AddressOutOfBounds => ReturnCode::EINVAL,
-            Error::NoSuchApp => ReturnCode::EINVAL,
+            Error::NoSuchApp => ReturnCode::ENOAPP,
         }
     }
 }
@@ -173,6 +205,9 @@ pub enum State {
     Running,
     Yielded,
     Fault,
+    /// Blocked in a `COMMAND_WAIT` syscall until a callback for the
+    /// subscription is scheduled.
+    Waiting(CallbackId),
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -181,6 +216,49 @@ pub enum FaultResponse {
     Restart,
 }
 
+/// What a process was doing when it faulted, as far as the kernel can tell
+/// from the fault status registers and the process's memory layout.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FaultKind {
+    /// The stack grew into the guard region below it.
+    StackOverflow,
+    /// The process accessed the grant region at the top of its memory,
+    /// usually because its heap grew into it.
+    HeapGrantCollision,
+    /// The process accessed, or jumped to, an address close to zero.
+    NullDereference,
+    /// The process jumped to memory it may not execute.
+    BadExecute,
+    /// Any other fault.
+    Other,
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            FaultKind::StackOverflow => "stack overflow",
+            FaultKind::HeapGrantCollision => "heap/grant collision",
+            FaultKind::NullDereference => "null dereference",
+            FaultKind::BadExecute => "bad execute",
+            FaultKind::Other => "other fault",
+        })
+    }
+}
+
+/// Faulting addresses below this are treated as null dereferences.
+const NULL_DEREFERENCE_LIMIT: usize = 0x400;
+
+/// MPU region number of the guard region placed below the process stack.
+const STACK_GUARD_REGION: usize = 7;
+
+/// Number of buffers other processes may share with a process through IPC.
+/// Each takes one of MPU regions 3 to 6, as region 7 is the stack guard.
+pub const MAX_SHARED_REGIONS: usize = 4;
+
+/// Size in bytes of the guard region placed below the process stack. This is
+/// the smallest region the Cortex-M MPU supports.
+const STACK_GUARD_SIZE: usize = 32;
+
 #[derive(Copy, Clone, Debug)]
 pub enum IPCType {
     Service,
@@ -200,8 +278,17 @@ pub struct FunctionCall {
     pub r2: usize,
     pub r3: usize,
     pub pc: usize,
+    /// The subscription this call is for, if it is a callback.
+    pub callback_id: Option<CallbackId>,
 }
 
+/// Number of callbacks a process can have queued, unless its TBF header asks
+/// for a different number.
+pub const DEFAULT_CALLBACK_QUEUE_LEN: usize = 10;
+
+/// Largest number of queued callbacks a process can ask for.
+pub const MAX_CALLBACK_QUEUE_LEN: usize = 64;
+
 /// Legacy Tock Binary Format header.
 ///
 /// Version 1 of the header is deprecated but can still be parsed by the kernel
@@ -249,7 +336,8 @@ enum TbfHeaderTypes {
     TbfHeaderMain = 1,
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
-    Unused = 5,
+    TbfHeaderCallbackQueue = 5,
+    Unused = 6,
 }
 
 /// The TLV header (T and L).
@@ -283,6 +371,20 @@ struct TbfHeaderV2WriteableFlashRegion {
     writeable_flash_region_size: u32,
 }
 
+/// How the process's callback queue is sized and managed.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct TbfHeaderV2CallbackQueue {
+    /// Number of callbacks that can be queued, up to `MAX_CALLBACK_QUEUE_LEN`.
+    queue_len: u16,
+    /// Bit 0: coalesce callbacks for the same subscription.
+    flags: u16,
+}
+
+/// Set in `TbfHeaderV2CallbackQueue::flags` to replace a queued callback
+/// rather than queue another one for the same subscription.
+const CALLBACK_QUEUE_FLAG_COALESCE: u16 = 0x1;
+
 /// PIC fields for kernel provided PIC fixup.
 ///
 /// If an app wants the kernel to do the PIC fixup for it, it must pass this
@@ -309,6 +411,7 @@ struct TbfHeaderV2 {
     main: Option<&'static TbfHeaderV2Main>,
     package_name: Option<&'static str>,
     writeable_regions: Option<&'static [TbfHeaderV2WriteableFlashRegion]>,
+    callback_queue: Option<&'static TbfHeaderV2CallbackQueue>,
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -417,6 +520,31 @@ impl TbfHeader {
         }
     }
 
+    /// Get the number of callbacks the app can have queued.
+    fn get_callback_queue_len(&self) -> usize {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => {
+                hd.callback_queue
+                    .map_or(DEFAULT_CALLBACK_QUEUE_LEN, |cq| match cq.queue_len as usize {
+                        0 => DEFAULT_CALLBACK_QUEUE_LEN,
+                        len if len > MAX_CALLBACK_QUEUE_LEN => MAX_CALLBACK_QUEUE_LEN,
+                        len => len,
+                    })
+            }
+            _ => DEFAULT_CALLBACK_QUEUE_LEN,
+        }
+    }
+
+    /// Whether a callback for a subscription that already has one queued
+    /// replaces the queued one.
+    fn coalesce_callbacks(&self) -> bool {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.callback_queue
+                .map_or(false, |cq| cq.flags & CALLBACK_QUEUE_FLAG_COALESCE != 0),
+            _ => false,
+        }
+    }
+
     /// Get the number of flash regions this app has specified in its header.
     fn number_writeable_flash_regions(&self) -> usize {
         match *self {
@@ -534,6 +662,7 @@ unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfHeader>
                     &'static [TbfHeaderV2WriteableFlashRegion],
                 > = None;
                 let mut app_name_str = "";
+                let mut callback_queue_pointer: Option<&TbfHeaderV2CallbackQueue> = None;
 
                 // Loop through the header looking for known options.
                 while remaining_length > mem::size_of::<TbfHeaderTlv>() {
@@ -572,6 +701,13 @@ unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfHeader>
                                     let _ = str::from_utf8(package_name_byte_array).map(|name_str| { app_name_str = name_str; });
                                 }
                             }
+                            TbfHeaderTypes::TbfHeaderCallbackQueue => /* Callback Queue */ {
+                                if remaining_length >= mem::size_of::<TbfHeaderV2CallbackQueue>() &&
+                                   tbf_tlv_header.length as usize == mem::size_of::<TbfHeaderV2CallbackQueue>() {
+                                    let callback_queue = &*(address.offset(offset) as *const TbfHeaderV2CallbackQueue);
+                                    callback_queue_pointer = Some(callback_queue);
+                                }
+                            }
                             TbfHeaderTypes::Unused => {}
                         }
                     }
@@ -587,6 +723,7 @@ unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfHeader>
                     main: main_pointer,
                     package_name: Some(app_name_str),
                     writeable_regions: wfr_pointer,
+                    callback_queue: callback_queue_pointer,
                 };
 
                 Some(TbfHeader::TbfHeaderV2(tbf_header))
@@ -625,6 +762,12 @@ struct ProcessDebug {
     /// How low have we ever seen the stack pointer.
     min_stack_pointer: *const u8,
 
+    /// How high has the app break ever been.
+    max_app_break: *const u8,
+
+    /// The kind of the most recent fault, if the process has faulted.
+    last_fault: Option<FaultKind>,
+
     /// How many syscalls have occurred since the process started.
     syscall_count: Cell<usize>,
 
@@ -638,6 +781,70 @@ struct ProcessDebug {
     /// How many times this process has entered into a fault condition and the
     /// kernel has restarted it.
     restart_count: Cell<usize>,
+
+    /// How long the process has run for, in microseconds.
+    cpu_time_us: Cell<u64>,
+
+    /// How many times the process was preempted because its timeslice ran out.
+    timeslice_expiration_count: Cell<usize>,
+
+    /// How many times the process yielded.
+    yield_count: Cell<usize>,
+
+    /// Subscribe, command and allow calls made to each driver, as
+    /// `(driver_num, count)`. Unused slots have a count of zero.
+    driver_syscall_counts: [Cell<(usize, usize)>; DRIVER_SYSCALL_SLOTS],
+
+    /// Calls to drivers that did not fit in `driver_syscall_counts`.
+    other_driver_syscall_count: Cell<usize>,
+}
+
+/// Number of distinct drivers whose syscalls are counted for each process.
+pub const DRIVER_SYSCALL_SLOTS: usize = 8;
+
+/// Accounting information about a process, as returned by `process_stats()`.
+#[derive(Copy, Clone, Debug)]
+pub struct ProcessStats {
+    /// Time spent running the process, in microseconds.
+    pub cpu_time_us: u64,
+    /// Number of times the process was preempted at the end of its timeslice.
+    pub timeslice_expirations: usize,
+    /// Number of times the process yielded.
+    pub yields: usize,
+    /// Number of syscalls of any kind.
+    pub syscalls: usize,
+    /// Subscribe, command and allow calls per driver, as `(driver_num, count)`.
+    /// Unused slots have a count of zero.
+    pub driver_syscalls: [(usize, usize); DRIVER_SYSCALL_SLOTS],
+    /// Subscribe, command and allow calls to drivers not in `driver_syscalls`.
+    pub other_driver_syscalls: usize,
+    /// Number of callbacks dropped because the process's queue was full.
+    pub dropped_callbacks: usize,
+    /// Number of times the process was restarted after a fault.
+    pub restarts: usize,
+}
+
+/// Returns the accounting information of the process at index `app_idx`, or
+/// `None` if there is no such process. Counts other than `restarts` start over
+/// when a process is restarted.
+pub fn process_stats(app_idx: usize) -> Option<ProcessStats> {
+    let procs = unsafe { &PROCS };
+    procs
+        .get(app_idx)
+        .and_then(|process| process.as_ref().map(|process| process.stats()))
+}
+
+/// Returns the package name of the process at index `app_idx`.
+pub fn process_name(app_idx: usize) -> Option<&'static str> {
+    let procs = unsafe { &PROCS };
+    procs
+        .get(app_idx)
+        .and_then(|process| process.as_ref().map(|process| process.package_name))
+}
+
+/// Returns the number of process slots, some of which may be empty.
+pub fn number_of_process_slots() -> usize {
+    unsafe { PROCS.len() }
 }
 
 pub struct Process<'a> {
@@ -704,7 +911,9 @@ pub struct Process<'a> {
     /// How to deal with Faults occurring in the process
     fault_response: FaultResponse,
 
-    /// MPU regions are saved as a pointer-size pair.
+    /// MPU regions shared with the process through IPC, which use MPU regions 3
+    /// to 6, so there are at most `MAX_SHARED_REGIONS` of them. They are saved
+    /// as a pointer-size pair.
     ///
     /// size is encoded as X where
     /// SIZE = 2<sup>(X + 1)</sup> and X >= 4.
@@ -715,7 +924,7 @@ pub struct Process<'a> {
     ///
     /// The pointer must be aligned to the size. E.g. if the size is 32 bytes, the pointer must be
     /// 32-byte aligned.
-    mpu_regions: [Cell<(*const u8, math::PowerOfTwo)>; 5],
+    mpu_regions: [Cell<(*const u8, math::PowerOfTwo)>; MAX_SHARED_REGIONS],
 
     /// Essentially a list of callbacks that want to call functions in the
     /// process.
@@ -737,14 +946,14 @@ pub fn processes_blocked() -> bool {
 
 impl<'a> Process<'a> {
     pub fn schedule_ipc(&mut self, from: AppId, cb_type: IPCType) {
-        unsafe {
-            HAVE_WORK.set(HAVE_WORK.get() + 1);
-        }
         let ret = self.tasks.enqueue(Task::IPC((from, cb_type)));
 
-        // Make a note that we lost this callback if the enqueue function
-        // fails.
-        if ret == false {
+        if ret && self.tasks_are_work() {
+            unsafe {
+                HAVE_WORK.set(HAVE_WORK.get() + 1);
+            }
+        } else if !ret {
+            // Make a note that we lost this callback.
             self.debug
                 .dropped_callback_count
                 .set(self.debug.dropped_callback_count.get() + 1);
@@ -755,6 +964,49 @@ impl<'a> Process<'a> {
         self.state
     }
 
+    /// Queued tasks count as work for the kernel, unless the process is
+    /// waiting for a particular callback and so cannot run them yet.
+    fn tasks_are_work(&self) -> bool {
+        match self.state {
+            State::Waiting(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Block the running process until a callback for `callback_id` is
+    /// scheduled. The process must be in a syscall.
+    pub fn wait_for(&mut self, callback_id: CallbackId) {
+        if self.state == State::Running {
+            self.state = State::Waiting(callback_id);
+            unsafe {
+                HAVE_WORK.set(HAVE_WORK.get() - 1 - self.tasks.len());
+            }
+        }
+    }
+
+    /// Let a waiting process run again without a result.
+    pub fn stop_waiting(&mut self) {
+        if let State::Waiting(_) = self.state {
+            self.state = State::Running;
+            unsafe {
+                HAVE_WORK.set(HAVE_WORK.get() + 1 + self.tasks.len());
+            }
+        }
+    }
+
+    /// Return from the `COMMAND_WAIT` syscall with `SUCCESS` in r0 and the
+    /// callback's arguments in r1 to r3.
+    fn complete_wait(&mut self, callback: FunctionCall) {
+        let pspr = self.current_stack_pointer as *mut usize;
+        unsafe {
+            write_volatile(pspr.offset(1), callback.r0);
+            write_volatile(pspr.offset(2), callback.r1);
+            write_volatile(pspr.offset(3), callback.r2);
+        }
+        self.set_return_code(ReturnCode::SUCCESS);
+        self.stop_waiting();
+    }
+
     pub fn yield_state(&mut self) {
         if self.state == State::Running {
             self.state = State::Yielded;
@@ -768,12 +1020,46 @@ impl<'a> Process<'a> {
         write_volatile(&mut APP_FAULT, 0);
         self.state = State::Fault;
 
+        let fault = self.classify_fault();
+        self.debug.last_fault = Some(fault);
+
         match self.fault_response {
             FaultResponse::Panic => {
+                // Note the fault for the crash log
+                let app_index = PROCS
+                    .iter()
+                    .position(|process| {
+                        process.as_ref().map_or(false, |process| {
+                            *process as *const Process == self as *const Process
+                        })
+                    })
+                    .unwrap_or(0);
+                crash_log::set_app_fault(
+                    app_index,
+                    self.package_name,
+                    self.pc() as u32,
+                    self.lr() as u32,
+                    [
+                        SCB_REGISTERS[1],
+                        SCB_REGISTERS[2],
+                        SCB_REGISTERS[3],
+                        SCB_REGISTERS[4],
+                    ],
+                );
+
                 // process faulted. Panic and print status
-                panic!("Process {} had a fault", self.package_name);
+                panic!("Process {} had a fault: {}", self.package_name, fault);
             }
             FaultResponse::Restart => {
+                debug!(
+                    "Process {} had a fault: {}. Stack high-water: {} bytes, \
+                     heap high-water: {} bytes. Restarting.",
+                    self.package_name,
+                    fault,
+                    self.stack_high_water(),
+                    self.heap_high_water()
+                );
+
                 // Remove the tasks that were scheduled for the app from the
                 // amount of work queue.
                 if HAVE_WORK.get() < self.tasks.len() {
@@ -795,6 +1081,14 @@ impl<'a> Process<'a> {
                 self.debug.syscall_count.set(0);
                 self.debug.last_syscall.set(None);
                 self.debug.dropped_callback_count.set(0);
+                self.debug.cpu_time_us.set(0);
+                self.debug.timeslice_expiration_count.set(0);
+                self.debug.yield_count.set(0);
+                for slot in self.debug.driver_syscall_counts.iter() {
+                    slot.set((0, 0));
+                }
+                self.debug.other_driver_syscall_count.set(0);
+                self.debug.max_app_break = self.original_app_break;
 
                 // We are going to start this process over again, so need
                 // the init_fn location.
@@ -824,6 +1118,7 @@ impl<'a> Process<'a> {
                     r1: self.memory.as_ptr() as usize,
                     r2: self.memory.len() as usize,
                     r3: self.app_break as usize,
+                    callback_id: None,
                 }));
 
                 HAVE_WORK.set(HAVE_WORK.get() + 1);
@@ -925,18 +1220,7 @@ impl<'a> Process<'a> {
         }
 
         // Disallow access to grant region
-        let grant_len = unsafe {
-            math::PowerOfTwo::ceiling(
-                self.memory.as_ptr().offset(self.memory.len() as isize) as u32
-                    - (self.kernel_memory_break as u32),
-            ).as_num::<u32>()
-        };
-        let grant_base = unsafe {
-            self.memory
-                .as_ptr()
-                .offset(self.memory.len() as isize)
-                .offset(-(grant_len as isize))
-        };
+        let (grant_base, grant_len) = self.grant_region();
 
         match MPU::create_region(
             2,
@@ -975,24 +1259,145 @@ impl<'a> Process<'a> {
                 Some(region) => mpu.set_mpu(region),
             }
         }
+
+        // Disallow access to the bottom of the stack, so that an overflow
+        // faults before it reaches memory outside the process.
+        match self.stack_guard() {
+            None => mpu.set_mpu(mpu::Region::empty(STACK_GUARD_REGION)),
+            Some(guard_base) => match MPU::create_region(
+                STACK_GUARD_REGION,
+                guard_base as usize,
+                STACK_GUARD_SIZE,
+                mpu::ExecutePermission::ExecutionNotPermitted,
+                mpu::AccessPermission::PrivilegedOnly,
+            ) {
+                None => panic!(
+                    "Infeasible MPU allocation. Base {:#x}, Length: {:#x}",
+                    guard_base as usize, STACK_GUARD_SIZE
+                ),
+                Some(region) => mpu.set_mpu(region),
+            },
+        }
+    }
+
+    /// The base and length of the MPU region protecting the grant region. The
+    /// region is rounded up to a power of two, so it may cover the top of the
+    /// heap as well.
+    fn grant_region(&self) -> (*const u8, u32) {
+        let grant_len =
+            math::PowerOfTwo::ceiling(self.mem_end() as u32 - (self.kernel_memory_break as u32))
+                .as_num::<u32>();
+        let grant_base = unsafe { self.mem_end().offset(-(grant_len as isize)) };
+        (grant_base, grant_len)
+    }
+
+    /// The base of the stack guard region, if the process has told the
+    /// kernel where its stack is. The stack grows down towards the start of
+    /// process memory, so the guard covers the lowest bytes of the stack.
+    fn stack_guard(&self) -> Option<*const u8> {
+        self.debug.app_stack_start_pointer.and_then(|stack_start| {
+            let guard_base = self.mem_start();
+            // Leave the stack room to do anything at all.
+            if (stack_start as usize) < guard_base as usize + 2 * STACK_GUARD_SIZE {
+                None
+            } else {
+                Some(guard_base)
+            }
+        })
+    }
+
+    /// Largest number of bytes the stack has used, if the process has told
+    /// the kernel where its stack is.
+    pub fn stack_high_water(&self) -> usize {
+        self.debug.app_stack_start_pointer.map_or(0, |stack_start| {
+            (stack_start as usize).saturating_sub(self.debug.min_stack_pointer as usize)
+        })
+    }
+
+    /// Largest number of bytes the heap has used, if the process has told the
+    /// kernel where its heap is.
+    pub fn heap_high_water(&self) -> usize {
+        self.debug.app_heap_start_pointer.map_or(0, |heap_start| {
+            (self.debug.max_app_break as usize).saturating_sub(heap_start as usize)
+        })
+    }
+
+    /// The kind of the most recent fault, if the process has faulted.
+    pub fn last_fault(&self) -> Option<FaultKind> {
+        self.debug.last_fault
+    }
+
+    /// Work out what caused the current fault from the fault status
+    /// registers saved by the hard fault handler.
+    unsafe fn classify_fault(&self) -> FaultKind {
+        let cfsr = SCB_REGISTERS[1] as usize;
+        let mmfar = SCB_REGISTERS[3] as usize;
+
+        let iaccviol = (cfsr & 0x01) == 0x01;
+        let daccviol = (cfsr & 0x02) == 0x02;
+        let mstkerr = (cfsr & 0x10) == 0x10;
+        let mmfarvalid = (cfsr & 0x80) == 0x80;
+
+        let mem_start = self.mem_start() as usize;
+        let (grant_base, _) = self.grant_region();
+        let in_guard = |addr: usize| {
+            self.stack_guard().map_or(false, |guard_base| {
+                addr >= guard_base as usize && addr < guard_base as usize + STACK_GUARD_SIZE
+            }) || (addr < mem_start && addr >= mem_start.saturating_sub(STACK_GUARD_SIZE))
+        };
+
+        if daccviol && mmfarvalid {
+            if mmfar < NULL_DEREFERENCE_LIMIT {
+                FaultKind::NullDereference
+            } else if in_guard(mmfar) {
+                FaultKind::StackOverflow
+            } else if mmfar >= grant_base as usize && mmfar < self.mem_end() as usize {
+                FaultKind::HeapGrantCollision
+            } else {
+                FaultKind::Other
+            }
+        } else if mstkerr {
+            // The exception frame could not be pushed, so the stack pointer
+            // shows where the process tried to put it.
+            if in_guard(self.current_stack_pointer as usize)
+                || (self.current_stack_pointer as usize) < mem_start
+            {
+                FaultKind::StackOverflow
+            } else {
+                FaultKind::Other
+            }
+        } else if iaccviol {
+            // The stacked PC holds the address the process tried to execute.
+            if self.pc() < NULL_DEREFERENCE_LIMIT {
+                FaultKind::NullDereference
+            } else {
+                FaultKind::BadExecute
+            }
+        } else {
+            FaultKind::Other
+        }
     }
 
-    pub fn add_mpu_region(&self, base: *const u8, size: u32) -> bool {
-        if size >= 16 && size.count_ones() == 1 && (base as u32) % size == 0 {
-            let mpu_size = math::PowerOfTwo::floor(size);
-            for region in self.mpu_regions.iter() {
-                if region.get().0 == ptr::null() {
+    /// Give the process access to a buffer shared with it. Returns `EINVAL` if
+    /// the buffer cannot be covered by an MPU region, and `ENOMEM` if
+    /// `MAX_SHARED_REGIONS` other buffers are already shared with it.
+    pub fn add_mpu_region(&self, base: *const u8, size: u32) -> ReturnCode {
+        if size < 16 || size.count_ones() != 1 || (base as u32) % size != 0 {
+            return ReturnCode::EINVAL;
+        }
+        let mpu_size = math::PowerOfTwo::floor(size);
+        for region in self.mpu_regions.iter() {
+            if region.get().0 == ptr::null() {
+                region.set((base, mpu_size));
+                return ReturnCode::SUCCESS;
+            } else if region.get().0 == base {
+                if region.get().1 < mpu_size {
                     region.set((base, mpu_size));
-                    return true;
-                } else if region.get().0 == base {
-                    if region.get().1 < mpu_size {
-                        region.set((base, mpu_size));
-                    }
-                    return true;
                 }
+                return ReturnCode::SUCCESS;
             }
         }
-        return false;
+        ReturnCode::ENOMEM
     }
 
     pub unsafe fn create(
@@ -1029,9 +1434,10 @@ impl<'a> Process<'a> {
             let grant_ptrs_num = read_volatile(&grant::CONTAINER_COUNTER);
             let grant_ptrs_offset = grant_ptrs_num * grant_ptr_size;
 
-            // Allocate memory for callback ring buffer.
+            // Allocate memory for callback ring buffer. The ring buffer keeps
+            // one entry free to tell a full buffer from an empty one.
             let callback_size = mem::size_of::<Task>();
-            let callback_len = 10;
+            let callback_len = tbf_header.get_callback_queue_len() + 1;
             let callbacks_offset = callback_len * callback_size;
 
             // Make room to store this process's metadata.
@@ -1121,7 +1527,6 @@ impl<'a> Process<'a> {
                 Cell::new((ptr::null(), math::PowerOfTwo::zero())),
                 Cell::new((ptr::null(), math::PowerOfTwo::zero())),
                 Cell::new((ptr::null(), math::PowerOfTwo::zero())),
-                Cell::new((ptr::null(), math::PowerOfTwo::zero())),
             ];
             process.tasks = tasks;
             process.package_name = package_name;
@@ -1130,10 +1535,17 @@ impl<'a> Process<'a> {
                 app_heap_start_pointer: app_heap_start_pointer,
                 app_stack_start_pointer: app_stack_start_pointer,
                 min_stack_pointer: initial_stack_pointer,
+                max_app_break: initial_sbrk_pointer,
+                last_fault: None,
                 syscall_count: Cell::new(0),
                 last_syscall: Cell::new(None),
                 dropped_callback_count: Cell::new(0),
                 restart_count: Cell::new(0),
+                cpu_time_us: Cell::new(0),
+                timeslice_expiration_count: Cell::new(0),
+                yield_count: Cell::new(0),
+                driver_syscall_counts: Default::default(),
+                other_driver_syscall_count: Cell::new(0),
             };
 
             if (init_fn & 0x1) != 1 {
@@ -1153,6 +1565,7 @@ impl<'a> Process<'a> {
                 r1: process.memory.as_ptr() as usize,
                 r2: process.memory.len() as usize,
                 r3: process.app_break as usize,
+                callback_id: None,
             }));
 
             HAVE_WORK.set(HAVE_WORK.get() + 1);
@@ -1175,6 +1588,9 @@ impl<'a> Process<'a> {
         } else {
             let old_break = self.app_break;
             self.app_break = new_break;
+            if new_break > self.debug.max_app_break {
+                self.debug.max_app_break = new_break;
+            }
             Ok(old_break)
         }
     }
@@ -1306,6 +1722,7 @@ impl<'a> Process<'a> {
                 2 => Some(Syscall::COMMAND),
                 3 => Some(Syscall::ALLOW),
                 4 => Some(Syscall::MEMOP),
+                5 => Some(Syscall::COMMAND_WAIT),
                 _ => None,
             }
         }
@@ -1316,6 +1733,72 @@ impl<'a> Process<'a> {
             .syscall_count
             .set(self.debug.syscall_count.get() + 1);
         self.debug.last_syscall.set(self.svc_number());
+
+        match self.svc_number() {
+            Some(Syscall::SUBSCRIBE)
+            | Some(Syscall::COMMAND)
+            | Some(Syscall::ALLOW)
+            | Some(Syscall::COMMAND_WAIT) => {
+                let driver_num = self.r0();
+                let slot = self.debug
+                    .driver_syscall_counts
+                    .iter()
+                    .find(|slot| slot.get().0 == driver_num && slot.get().1 > 0)
+                    .or_else(|| {
+                        self.debug
+                            .driver_syscall_counts
+                            .iter()
+                            .find(|slot| slot.get().1 == 0)
+                    });
+                match slot {
+                    Some(slot) => slot.set((driver_num, slot.get().1 + 1)),
+                    None => self.debug
+                        .other_driver_syscall_count
+                        .set(self.debug.other_driver_syscall_count.get() + 1),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Charge the process for `us` microseconds of CPU time.
+    pub fn add_cpu_time(&self, us: u32) {
+        self.debug
+            .cpu_time_us
+            .set(self.debug.cpu_time_us.get() + us as u64);
+    }
+
+    pub fn incr_timeslice_expiration_count(&self) {
+        self.debug
+            .timeslice_expiration_count
+            .set(self.debug.timeslice_expiration_count.get() + 1);
+    }
+
+    pub fn incr_yield_count(&self) {
+        self.debug
+            .yield_count
+            .set(self.debug.yield_count.get() + 1);
+    }
+
+    pub fn stats(&self) -> ProcessStats {
+        let mut driver_syscalls = [(0, 0); DRIVER_SYSCALL_SLOTS];
+        for (out, slot) in driver_syscalls
+            .iter_mut()
+            .zip(self.debug.driver_syscall_counts.iter())
+        {
+            *out = slot.get();
+        }
+
+        ProcessStats {
+            cpu_time_us: self.debug.cpu_time_us.get(),
+            timeslice_expirations: self.debug.timeslice_expiration_count.get(),
+            yields: self.debug.yield_count.get(),
+            syscalls: self.debug.syscall_count.get(),
+            driver_syscalls: driver_syscalls,
+            other_driver_syscalls: self.debug.other_driver_syscall_count.get(),
+            dropped_callbacks: self.debug.dropped_callback_count.get(),
+            restarts: self.debug.restart_count.get(),
+        }
     }
 
     pub fn sp(&self) -> usize {
@@ -1616,14 +2099,19 @@ impl<'a> Process<'a> {
         let _ = writer.write_fmt(format_args!(
             "\
              App: {}   -   [{:?}]\
-             \r\n Events Queued: {}   Syscall Count: {}   Dropped Callback Count: {}\
-             \n Restart Count: {}\n",
+             \r\n Events Queued: {}/{}   Syscall Count: {}   Dropped Callback Count: {}\
+             \n Restart Count: {}\
+             \n CPU Time: {} us   Timeslice Expirations: {}   Yields: {}\n",
             self.package_name,
             self.state,
             events_queued,
+            self.header.get_callback_queue_len(),
             syscall_count,
             dropped_callback_count,
             restart_count,
+            self.debug.cpu_time_us.get(),
+            self.debug.timeslice_expiration_count.get(),
+            self.debug.yield_count.get(),
         ));
 
         let _ = match last_syscall {
@@ -1631,6 +2119,16 @@ impl<'a> Process<'a> {
             None => writer.write_fmt(format_args!(" Last Syscall: None")),
         };
 
+        let _ = match self.debug.last_fault {
+            Some(fault) => writer.write_fmt(format_args!("   Last Fault: {}", fault)),
+            None => writer.write_fmt(format_args!("   Last Fault: None")),
+        };
+        let _ = writer.write_fmt(format_args!(
+            "\r\n Stack High-Water: {} bytes   Heap High-Water: {} bytes",
+            self.stack_high_water(),
+            self.heap_high_water(),
+        ));
+
         let _ = writer.write_fmt(format_args!("\
 \r\n\
 \r\n ╔═══════════╤══════════════════════════════════════════╗\
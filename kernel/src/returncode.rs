@@ -21,6 +21,10 @@ pub enum ReturnCode {
     ENODEVICE,    // Device does not exist
     EUNINSTALLED, // Device is not physically installed
     ENOACK,       // Packet transmission not acknowledged
+    EPERM,        // The caller is not permitted to perform the operation
+    ETIMEOUT,     // The operation did not complete in time
+    ENOAPP,       // The application referred to does not exist
+    EWOULDBLOCK,  // The operation cannot complete without waiting
 }
 
 impl From<ReturnCode> for isize {
@@ -41,6 +45,10 @@ impl From<ReturnCode> for isize {
             ReturnCode::ENODEVICE => -11,
             ReturnCode::EUNINSTALLED => -12,
             ReturnCode::ENOACK => -13,
+            ReturnCode::EPERM => -14,
+            ReturnCode::ETIMEOUT => -15,
+            ReturnCode::ENOAPP => -16,
+            ReturnCode::EWOULDBLOCK => -17,
         }
     }
 }
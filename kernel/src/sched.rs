@@ -3,7 +3,8 @@
 use core::ptr;
 use core::ptr::NonNull;
 
-use callback::{AppId, Callback};
+use callback::{AppId, Callback, CallbackId};
+use common::dynamic_deferred_call;
 use mem::AppSlice;
 use memop;
 use platform::mpu::MPU;
@@ -13,6 +14,7 @@ use process;
 use process::{Process, Task};
 use returncode::ReturnCode;
 use syscall::Syscall;
+use trace;
 
 /// The time a process is permitted to run before being pre-empted
 const KERNEL_TICK_DURATION_US: u32 = 10000;
@@ -31,8 +33,13 @@ pub unsafe fn do_process<P: Platform, C: Chip>(
     systick.set_timer(KERNEL_TICK_DURATION_US);
     systick.enable(true);
 
+    // Reading the overflow flag clears it, so remember whether the timeslice
+    // ran out while the process was running.
+    let mut expired = false;
+
     loop {
-        if chip.has_pending_interrupts() || systick.overflowed()
+        if chip.has_pending_interrupts() || dynamic_deferred_call::has_pending() || expired
+            || systick.overflowed()
             || !systick.greater_than(MIN_QUANTA_THRESHOLD_US)
         {
             break;
@@ -43,8 +50,22 @@ pub unsafe fn do_process<P: Platform, C: Chip>(
                 process.setup_mpu(chip.mpu());
                 chip.mpu().enable_mpu();
                 systick.enable(true);
+                trace::record(trace::Event::ProcessRun, Some(appid), 0, 0);
+                let time_left = systick.get_value();
                 process.switch_to();
+                trace::record(trace::Event::ProcessReturn, Some(appid), 0, 0);
                 systick.enable(false);
+
+                // Charge the process for the time it ran. If the timeslice
+                // expired the timer has since reloaded, so the process used
+                // everything that was left.
+                if systick.overflowed() {
+                    expired = true;
+                    process.incr_timeslice_expiration_count();
+                    process.add_cpu_time(time_left);
+                } else {
+                    process.add_cpu_time(time_left.saturating_sub(systick.get_value()));
+                }
                 chip.mpu().disable_mpu();
             }
             process::State::Yielded => match process.dequeue_task() {
@@ -71,6 +92,7 @@ pub unsafe fn do_process<P: Platform, C: Chip>(
                     continue;
                 }
             },
+            process::State::Waiting(_) => break,
             process::State::Fault => {
                 // we should never be scheduling a process in fault
                 panic!("Attempted to schedule a faulty process");
@@ -90,12 +112,35 @@ pub unsafe fn do_process<P: Platform, C: Chip>(
 
         // process had a system call, count it
         process.incr_syscall_count();
+        if let Some(svc) = process.svc_number() {
+            let (driver_num, subdriver_num) = match svc {
+                Syscall::SUBSCRIBE | Syscall::COMMAND | Syscall::ALLOW => {
+                    (process.r0(), process.r1())
+                }
+                Syscall::COMMAND_WAIT => (process.r0(), process.r1() & 0xffff),
+                Syscall::MEMOP => (0, process.r0()),
+                Syscall::YIELD => (0, 0),
+            };
+            let subdriver_num = if subdriver_num > 0xfff {
+                0xfff
+            } else {
+                subdriver_num
+            };
+            trace::record(
+                trace::Event::Syscall,
+                Some(appid),
+                svc as usize | subdriver_num << 4,
+                driver_num,
+            );
+        }
+
         match process.svc_number() {
             Some(Syscall::MEMOP) => {
                 let res = memop::memop(process);
                 process.set_return_code(res);
             }
             Some(Syscall::YIELD) => {
+                process.incr_yield_count();
                 process.yield_state();
                 process.pop_syscall_stack();
 
@@ -109,7 +154,12 @@ pub unsafe fn do_process<P: Platform, C: Chip>(
                 let appdata = process.r3();
 
                 let callback_ptr = NonNull::new(callback_ptr_raw);
-                let callback = callback_ptr.map(|ptr| Callback::new(appid, appdata, ptr.cast()));
+                let callback_id = CallbackId {
+                    driver_num: driver_num,
+                    subscribe_num: subdriver_num,
+                };
+                let callback =
+                    callback_ptr.map(|ptr| Callback::new(appid, callback_id, appdata, ptr.cast()));
 
                 let res = platform.with_driver(driver_num, |driver| match driver {
                     Some(d) => d.subscribe(subdriver_num, callback, appid),
@@ -124,6 +174,30 @@ pub unsafe fn do_process<P: Platform, C: Chip>(
                 });
                 process.set_return_code(res);
             }
+            Some(Syscall::COMMAND_WAIT) => {
+                let driver_num = process.r0();
+                let command_num = process.r1() & 0xffff;
+                let subscribe_num = process.r1() >> 16;
+                let data = process.r2();
+                let arg2 = process.r3();
+
+                // Wait before issuing the command, in case the driver
+                // schedules the callback from within it.
+                process.wait_for(CallbackId {
+                    driver_num: driver_num,
+                    subscribe_num: subscribe_num,
+                });
+                let res = platform.with_driver(driver_num, |driver| match driver {
+                    Some(d) => d.command(command_num, data, arg2, appid),
+                    None => ReturnCode::ENODEVICE,
+                });
+                let err: isize = res.into();
+                if err < 0 {
+                    // No callback is coming
+                    process.stop_waiting();
+                    process.set_return_code(res);
+                }
+            }
             Some(Syscall::ALLOW) => {
                 let res = platform.with_driver(process.r0(), |driver| {
                     match driver {
@@ -2,6 +2,7 @@
 
 /// The syscall number assignments.
 #[derive(Copy, Clone, Debug)]
+#[allow(non_camel_case_types)]
 pub enum Syscall {
     /// Return to the kernel to allow other processes to execute or to wait for
     /// interrupts and callbacks.
@@ -18,4 +19,8 @@ pub enum Syscall {
 
     /// Various memory operations.
     MEMOP = 4,
+
+    /// Perform a command, then block until the driver issues the matching
+    /// callback and return the callback's arguments.
+    COMMAND_WAIT = 5,
 }
@@ -0,0 +1,272 @@
+//! Kernel event tracing.
+//!
+//! When the kernel is built with the `trace` feature, the scheduler, the
+//! syscall path, callback delivery, interrupt servicing and deferred calls
+//! record timestamped events into a ring buffer in RAM. The buffer is drained
+//! in binary packets through an `Output`, usually a UART, and decoded on the
+//! host by `tools/trace_decode.py`. Without the feature every function in this
+//! module compiles to nothing.
+//!
+//! Setup
+//! -----
+//!
+//! The board enables the feature on its kernel dependency:
+//!
+//! ```toml
+//! kernel = { path = "../../kernel", features = ["trace"] }
+//! ```
+//!
+//! and provides a timestamp source and an output, ideally a UART not also
+//! used by the console:
+//!
+//! ```rust
+//! kernel::trace::set_clock(|| sam4l::ast::AST.now());
+//! let trace_output = static_init!(
+//!     kernel::trace::UartOutput<'static, sam4l::usart::USART>,
+//!     kernel::trace::UartOutput::new(&sam4l::usart::USART3, &mut kernel::trace::UART_BUF)
+//! );
+//! hil::uart::UART::set_client(&sam4l::usart::USART3, trace_output);
+//! kernel::trace::set_output(trace_output);
+//! ```
+//!
+//! Wire format
+//! -----------
+//!
+//! Each packet starts with the bytes `T`, `R`, then the number of records it
+//! holds and the number of records dropped because the ring buffer was full
+//! (saturating at 255). Each record is 12 bytes, all fields little endian:
+//!
+//! ```text
+//! timestamp: u32 | event: u8 | process: u8 | arg0: u16 | arg1: u32
+//! ```
+//!
+//! `process` is the index of the process concerned, or `0xff` for none. The
+//! meaning of the arguments is documented on `Event`.
+
+use callback::AppId;
+use common::cells::TakeCell;
+use hil::uart;
+
+/// Kinds of events recorded.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    /// The scheduler is switching to the process.
+    ProcessRun = 1,
+    /// The process has returned control to the kernel.
+    ProcessReturn = 2,
+    /// The process made a syscall. `arg0` holds the syscall class in its low
+    /// 4 bits and the subscribe, command or allow number (saturating at
+    /// 0xfff) above them. `arg1` is the driver number.
+    Syscall = 3,
+    /// A callback was enqueued for the process. `arg0` is 1 if it was
+    /// dropped because the process's queue was full.
+    CallbackEnqueued = 4,
+    /// The kernel started servicing pending interrupts.
+    InterruptsStart = 5,
+    /// The kernel finished servicing pending interrupts.
+    InterruptsEnd = 6,
+    /// A deferred call was set. `arg0` is the task number. Numbers from
+    /// `dynamic_deferred_call::TRACE_TASK_BASE` are capsule deferred calls.
+    DeferredCallSet = 7,
+    /// A deferred call is about to be serviced. `arg0` is the task number.
+    DeferredCallRun = 8,
+}
+
+/// Length in bytes of an encoded record.
+pub const RECORD_LEN: usize = 12;
+
+/// Length in bytes of a packet header.
+pub const PACKET_HEADER_LEN: usize = 4;
+
+/// Number of records the ring buffer holds.
+pub const TRACE_LEN: usize = 128;
+
+/// Records are only drained once this many are waiting, so that the events
+/// generated by draining do not by themselves keep the output busy.
+pub const DRAIN_THRESHOLD: usize = TRACE_LEN / 4;
+
+/// A suitable transmit buffer for `UartOutput`, holding 20 records per packet.
+pub static mut UART_BUF: [u8; PACKET_HEADER_LEN + 20 * RECORD_LEN] =
+    [0; PACKET_HEADER_LEN + 20 * RECORD_LEN];
+
+/// Something that can carry trace records off the chip.
+pub trait Output {
+    /// Called from the main loop when at least `DRAIN_THRESHOLD` records
+    /// are waiting. Implementations should take records with
+    /// `fill_packet()` if they are not already busy sending a packet.
+    fn drain(&self);
+}
+
+#[cfg(feature = "trace")]
+mod state {
+    use super::{Output, TRACE_LEN};
+
+    #[derive(Copy, Clone)]
+    pub struct Record {
+        pub timestamp: u32,
+        pub event: u8,
+        pub process: u8,
+        pub arg0: u16,
+        pub arg1: u32,
+    }
+
+    pub static mut CLOCK: Option<fn() -> u32> = None;
+    pub static mut OUTPUT: Option<&'static Output> = None;
+
+    pub static mut RECORDS: [Record; TRACE_LEN] = [Record {
+        timestamp: 0,
+        event: 0,
+        process: 0,
+        arg0: 0,
+        arg1: 0,
+    }; TRACE_LEN];
+    pub static mut HEAD: usize = 0;
+    pub static mut COUNT: usize = 0;
+    pub static mut DROPPED: usize = 0;
+}
+
+/// Set the function used to timestamp records, typically reading a free
+/// running timer. Records are stamped 0 until this is called.
+#[cfg(feature = "trace")]
+pub fn set_clock(clock: fn() -> u32) {
+    unsafe {
+        state::CLOCK = Some(clock);
+    }
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn set_clock(_clock: fn() -> u32) {}
+
+/// Set where records are drained to.
+#[cfg(feature = "trace")]
+pub fn set_output(output: &'static Output) {
+    unsafe {
+        state::OUTPUT = Some(output);
+    }
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn set_output(_output: &'static Output) {}
+
+/// Record an event.
+#[cfg(feature = "trace")]
+#[inline]
+pub fn record(event: Event, process: Option<AppId>, arg0: usize, arg1: usize) {
+    unsafe {
+        if state::COUNT == TRACE_LEN {
+            state::DROPPED += 1;
+            return;
+        }
+        let timestamp = state::CLOCK.map_or(0, |clock| clock());
+        let tail = (state::HEAD + state::COUNT) % TRACE_LEN;
+        state::RECORDS[tail] = state::Record {
+            timestamp: timestamp,
+            event: event as u8,
+            process: process.map_or(0xff, |appid| appid.idx() as u8),
+            arg0: arg0 as u16,
+            arg1: arg1 as u32,
+        };
+        state::COUNT += 1;
+    }
+}
+
+#[cfg(not(feature = "trace"))]
+#[inline(always)]
+pub fn record(_event: Event, _process: Option<AppId>, _arg0: usize, _arg1: usize) {}
+
+/// Hand records to the output if enough are waiting. Called by the kernel
+/// main loop.
+#[cfg(feature = "trace")]
+pub fn drain() {
+    unsafe {
+        if state::COUNT >= DRAIN_THRESHOLD {
+            state::OUTPUT.map(|output| output.drain());
+        }
+    }
+}
+
+#[cfg(not(feature = "trace"))]
+#[inline(always)]
+pub fn drain() {}
+
+/// Move as many waiting records as fit into `buf` as a packet, returning the
+/// packet's length, or 0 if no records are waiting.
+#[cfg(feature = "trace")]
+pub fn fill_packet(buf: &mut [u8]) -> usize {
+    unsafe {
+        if state::COUNT == 0 || buf.len() < PACKET_HEADER_LEN + RECORD_LEN {
+            return 0;
+        }
+        let n = (buf.len() - PACKET_HEADER_LEN) / RECORD_LEN;
+        let n = if n > 255 { 255 } else { n };
+        let n = if n > state::COUNT { state::COUNT } else { n };
+
+        buf[0] = b'T';
+        buf[1] = b'R';
+        buf[2] = n as u8;
+        buf[3] = if state::DROPPED > 255 {
+            255
+        } else {
+            state::DROPPED as u8
+        };
+        state::DROPPED = 0;
+
+        for i in 0..n {
+            let record = state::RECORDS[(state::HEAD + i) % TRACE_LEN];
+            let out = &mut buf[PACKET_HEADER_LEN + i * RECORD_LEN..];
+            for j in 0..4 {
+                out[j] = (record.timestamp >> (8 * j)) as u8;
+                out[8 + j] = (record.arg1 >> (8 * j)) as u8;
+            }
+            out[4] = record.event;
+            out[5] = record.process;
+            out[6] = record.arg0 as u8;
+            out[7] = (record.arg0 >> 8) as u8;
+        }
+        state::HEAD = (state::HEAD + n) % TRACE_LEN;
+        state::COUNT -= n;
+
+        PACKET_HEADER_LEN + n * RECORD_LEN
+    }
+}
+
+#[cfg(not(feature = "trace"))]
+pub fn fill_packet(_buf: &mut [u8]) -> usize {
+    0
+}
+
+/// Drains trace records over a UART. The UART must already be configured.
+pub struct UartOutput<'a, U: uart::UART + 'a> {
+    uart: &'a U,
+    buffer: TakeCell<'static, [u8]>,
+}
+
+impl<'a, U: uart::UART> UartOutput<'a, U> {
+    pub fn new(uart: &'a U, buffer: &'static mut [u8]) -> UartOutput<'a, U> {
+        UartOutput {
+            uart: uart,
+            buffer: TakeCell::new(buffer),
+        }
+    }
+}
+
+impl<'a, U: uart::UART> Output for UartOutput<'a, U> {
+    fn drain(&self) {
+        self.buffer.take().map(|buffer| {
+            let len = fill_packet(buffer);
+            if len > 0 {
+                self.uart.transmit(buffer, len);
+            } else {
+                self.buffer.replace(buffer);
+            }
+        });
+    }
+}
+
+impl<'a, U: uart::UART> uart::Client for UartOutput<'a, U> {
+    fn transmit_complete(&self, buffer: &'static mut [u8], _error: uart::Error) {
+        self.buffer.replace(buffer);
+    }
+
+    fn receive_complete(&self, _buffer: &'static mut [u8], _rx_len: usize, _error: uart::Error) {}
+}
@@ -0,0 +1,127 @@
+#!/usr/bin/env python
+#
+# usage: trace_decode.py [-h] [--freq HZ] [--names NAMES] [FILE]
+#
+# Decode a binary trace captured from a kernel built with the `trace` feature
+# (see kernel/src/trace.rs) into a timeline, one event per line.
+#
+# positional arguments:
+#   FILE              Captured trace (default: stdin)
+#
+# optional arguments:
+#   -h, --help        show this help message and exit
+#   --freq HZ         Frequency of the board's trace clock. When given, times
+#                     are printed in microseconds instead of ticks.
+#   --names NAMES     Comma separated process names, in process index order
+#
+# Examples:
+#   Capture from a serial port, then decode
+#     cat /dev/ttyUSB1 > trace.bin
+#     trace_decode.py --freq 16000 --names blink,sensors trace.bin
+
+import argparse
+import struct
+import sys
+
+MAGIC = b'TR'
+HEADER_LEN = 4
+RECORD_LEN = 12
+
+EVENTS = {
+    1: 'run',
+    2: 'return',
+    3: 'syscall',
+    4: 'callback',
+    5: 'irq-start',
+    6: 'irq-end',
+    7: 'deferred-set',
+    8: 'deferred-run',
+}
+
+SYSCALLS = ['yield', 'subscribe', 'command', 'allow', 'memop', 'command_wait']
+
+
+def packets(data):
+    """Yield (dropped, records) for each packet, skipping anything between
+    packets such as console output sharing the UART."""
+    pos = 0
+    while True:
+        pos = data.find(MAGIC, pos)
+        if pos < 0 or pos + HEADER_LEN > len(data):
+            return
+        count = bytearray(data[pos + 2:pos + 3])[0]
+        dropped = bytearray(data[pos + 3:pos + 4])[0]
+        end = pos + HEADER_LEN + count * RECORD_LEN
+        if count == 0 or end > len(data):
+            pos += 1
+            continue
+        records = []
+        for i in range(count):
+            start = pos + HEADER_LEN + i * RECORD_LEN
+            records.append(struct.unpack('<IBBHI', data[start:start + RECORD_LEN]))
+        yield dropped, records
+        pos = end
+
+
+def describe(event, arg0, arg1):
+    if event == 3:
+        svc = arg0 & 0xf
+        name = SYSCALLS[svc] if svc < len(SYSCALLS) else str(svc)
+        if svc in (1, 2, 3):
+            return '{} driver={:#x} num={}'.format(name, arg1, arg0 >> 4)
+        if svc == 4:
+            return '{} op={}'.format(name, arg0 >> 4)
+        return name
+    if event == 4:
+        return 'dropped' if arg0 else ''
+    if event in (7, 8):
+        return 'task={}'.format(arg0)
+    return ''
+
+
+def main():
+    parser = argparse.ArgumentParser(description='Decode a Tock kernel trace')
+    parser.add_argument('file', nargs='?', help='Captured trace (default: stdin)')
+    parser.add_argument('--freq', type=float, help='Trace clock frequency in Hz')
+    parser.add_argument('--names', default='', help='Comma separated process names')
+    args = parser.parse_args()
+
+    if args.file:
+        with open(args.file, 'rb') as f:
+            data = f.read()
+    else:
+        data = getattr(sys.stdin, 'buffer', sys.stdin).read()
+
+    names = [name for name in args.names.split(',') if name]
+
+    def process_name(idx):
+        if idx == 0xff:
+            return '-'
+        if idx < len(names):
+            return names[idx]
+        return 'app{}'.format(idx)
+
+    def fmt_time(ticks):
+        if args.freq:
+            return '{:12.1f}us'.format(ticks * 1e6 / args.freq)
+        return '{:12d}'.format(ticks)
+
+    first = None
+    last = None
+    for dropped, records in packets(data):
+        if dropped:
+            print('--- {}{} records dropped ---'.format(dropped, '+' if dropped == 255 else ''))
+        for timestamp, event, process, arg0, arg1 in records:
+            if first is None:
+                first = last = timestamp
+            # The clock is 32 bits wide and may wrap between records
+            elapsed = (timestamp - first) & 0xffffffff
+            delta = (timestamp - last) & 0xffffffff
+            last = timestamp
+            print('{} (+{}) {:8} {:13} {}'.format(
+                fmt_time(elapsed), fmt_time(delta).strip(), process_name(process),
+                EVENTS.get(event, 'event{}'.format(event)), describe(event, arg0, arg1)))
+
+
+if __name__ == '__main__':
+    main()
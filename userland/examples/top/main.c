@@ -0,0 +1,59 @@
+/* vim: set sw=2 expandtab tw=80: */
+
+// Prints the CPU time and syscall counts of every process once a second,
+// along with the share of the CPU each used over the last second.
+
+#include <stdio.h>
+
+#include <process_info.h>
+#include <timer.h>
+
+#define MAX_PROCESSES 8
+#define INTERVAL_MS 1000
+
+static uint64_t last_cpu_time_us[MAX_PROCESSES];
+
+int main(void) {
+  if (!process_info_exists()) {
+    printf("Process info driver not present\n");
+    return -1;
+  }
+
+  int slots = process_info_num_slots();
+  if (slots > MAX_PROCESSES) {
+    slots = MAX_PROCESSES;
+  }
+
+  while (1) {
+    printf("\n  # name             cpu%%      cpu ms  expired   yields syscalls\n");
+    for (int i = 0; i < slots; i++) {
+      char name[17];
+      process_info_stats_t stats;
+      if (process_info_name(i, name, sizeof(name)) < 0 ||
+          process_info_stats(i, &stats) < 0) {
+        continue;
+      }
+
+      uint64_t used_us = stats.cpu_time_us - last_cpu_time_us[i];
+      last_cpu_time_us[i] = stats.cpu_time_us;
+
+      printf("%3d %-16s %4lu %11lu %8lu %8lu %8lu\n", i, name,
+             (unsigned long) (used_us / (INTERVAL_MS * 10)),
+             (unsigned long) (stats.cpu_time_us / 1000),
+             (unsigned long) stats.timeslice_expirations,
+             (unsigned long) stats.yields,
+             (unsigned long) stats.syscalls);
+      for (int j = 0; j < stats.num_drivers; j++) {
+        printf("      driver %#7lx: %lu\n",
+               (unsigned long) stats.drivers[j].driver,
+               (unsigned long) stats.drivers[j].count);
+      }
+      if (stats.other_driver_syscalls > 0) {
+        printf("      other drivers: %lu\n",
+               (unsigned long) stats.other_driver_syscalls);
+      }
+    }
+
+    delay_ms(INTERVAL_MS);
+  }
+}
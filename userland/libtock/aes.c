@@ -4,26 +4,29 @@
 //
 // fired -  set when the callback has been called
 // error - error received from the kernel less than zero indicates an error
+// tag_valid - set when a CCM* tag was verified successfully
 typedef struct {
   bool fired;
   int error;
+  bool tag_valid;
 } aes_data_t;
 
 
 // Internal callback for creating synchronous functions
 //
-// callback_type - number indicating which type of callback occurred
-// currently 1(encryption) and 2(decryption)
+// callback_type - result of the operation
+// tag_valid - whether a CCM* tag was verified successfully
 // callback_args - user data passed into the set_callback function
 //
 static void aes_cb(int callback_type,
-                   __attribute__ ((unused)) int unused1,
-                   __attribute__ ((unused)) int unused2,
+                   __attribute__ ((unused)) int command_num,
+                   int tag_valid,
                    void *callback_args) {
 
   aes_data_t *result = (aes_data_t*)callback_args;
   result->fired = true;
   result->error = callback_type;
+  result->tag_valid = tag_valid;
 }
 
 
@@ -44,6 +47,11 @@ int aes128_set_ctr(const unsigned char* ctr, unsigned char len) {
   return allow(AES_DRIVER, AES_CTR, (void*)ctr, len);
 }
 
+// Internal function to configure additional authenticated data for CCM*
+int aes128_set_aad(const unsigned char* aad, unsigned char len) {
+  return allow(AES_DRIVER, AES_AAD, (void*)aad, len);
+}
+
 // Internal function to trigger encryption operation. Note that this doesn't
 // work by itself aes128_set_data() and aes128_set_ctr() must be called first
 int aes128_encrypt_start(void) {
@@ -117,7 +125,7 @@ int aes128_encrypt_ctr_sync(unsigned const char* buf, unsigned char buf_len,
                             unsigned const char* ctr, unsigned char ctr_len) {
 
   int err;
-  aes_data_t result = { .fired = false, .error = TOCK_SUCCESS };
+  aes_data_t result = { .fired = false, .error = TOCK_SUCCESS, .tag_valid = false };
 
   err = aes128_set_callback(aes_cb, &result);
   if (err < TOCK_SUCCESS) return err;
@@ -143,7 +151,7 @@ int aes128_decrypt_ctr_sync(const unsigned char* buf, unsigned char buf_len,
                             const unsigned char* ctr, unsigned char ctr_len) {
 
   int err;
-  aes_data_t result = { .fired = false, .error = TOCK_SUCCESS };
+  aes_data_t result = { .fired = false, .error = TOCK_SUCCESS, .tag_valid = false };
 
   err = aes128_set_callback(aes_cb, &result);
   if (err < TOCK_SUCCESS) return err;
@@ -161,3 +169,53 @@ int aes128_decrypt_ctr_sync(const unsigned char* buf, unsigned char buf_len,
 
   return result.error;
 }
+
+
+// Shared implementation of the synchronous CCM* operations
+static int aes128_ccm_sync(int command_num, unsigned char* buf, unsigned char buf_len,
+                           unsigned char payload_len, const unsigned char* aad,
+                           unsigned char aad_len, const unsigned char* nonce,
+                           unsigned char mic_len) {
+
+  int err;
+  aes_data_t result = { .fired = false, .error = TOCK_SUCCESS, .tag_valid = false };
+
+  err = aes128_set_callback(aes_cb, &result);
+  if (err < TOCK_SUCCESS) return err;
+
+  err = aes128_set_data(buf, buf_len);
+  if (err < TOCK_SUCCESS) return err;
+
+  err = aes128_set_ctr(nonce, 13);
+  if (err < TOCK_SUCCESS) return err;
+
+  err = aes128_set_aad(aad, aad == NULL ? 0 : aad_len);
+  if (err < TOCK_SUCCESS) return err;
+
+  err = command(AES_DRIVER, command_num, payload_len, mic_len);
+  if (err < TOCK_SUCCESS) return err;
+
+  yield_for(&result.fired);
+
+  if (result.error < TOCK_SUCCESS) return result.error;
+  if (command_num == AES_CCM_DEC && !result.tag_valid) return TOCK_FAIL;
+  return TOCK_SUCCESS;
+}
+
+
+// Function to encrypt and authenticate by aes128 CCM* synchronously
+int aes128_ccm_encrypt_sync(unsigned char* buf, unsigned char buf_len,
+                            unsigned char payload_len, const unsigned char* aad,
+                            unsigned char aad_len, const unsigned char* nonce,
+                            unsigned char mic_len) {
+  return aes128_ccm_sync(AES_CCM_ENC, buf, buf_len, payload_len, aad, aad_len, nonce, mic_len);
+}
+
+
+// Function to decrypt and verify by aes128 CCM* synchronously
+int aes128_ccm_decrypt_sync(unsigned char* buf, unsigned char buf_len,
+                            unsigned char payload_len, const unsigned char* aad,
+                            unsigned char aad_len, const unsigned char* nonce,
+                            unsigned char mic_len) {
+  return aes128_ccm_sync(AES_CCM_DEC, buf, buf_len, payload_len, aad, aad_len, nonce, mic_len);
+}
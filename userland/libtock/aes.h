@@ -12,6 +12,9 @@ extern "C" {
 #define AES_ENC    2
 #define AES_DEC    3
 #define AES_CTR    4
+#define AES_AAD    2
+#define AES_CCM_ENC 5
+#define AES_CCM_DEC 6
 
 
 // function called by the encryption or decryption operation when they are 
@@ -98,6 +101,37 @@ int aes128_encrypt_ctr_sync(const unsigned char* buf, unsigned char buf_len,
 int aes128_decrypt_ctr_sync(const unsigned char* buf, unsigned char buf_len, 
     const unsigned char* ctr, unsigned char ctr_len);
 
+
+// configures a buffer with additional authenticated data for CCM*
+//
+// aad            - buffer with the additional authenticated data
+// len            - length of the buffer
+int aes128_set_aad(const unsigned char *aad, unsigned char len);
+
+
+// encrypts and authenticates a payload according to aes-128 CCM*. The
+// `mic_len` byte tag is written to `buf` right after the payload.
+//
+// buf      - buffer holding the payload, with room for the tag after it
+// buf_len  - length of the buffer (at least payload_len + mic_len)
+// payload_len - length of the payload
+// aad      - additional authenticated data (may be NULL)
+// aad_len  - length of the additional authenticated data
+// nonce    - buffer with the nonce (should be 13 bytes)
+// mic_len  - length of the tag (0, 4, 6, 8, 10, 12, 14 or 16)
+int aes128_ccm_encrypt_sync(unsigned char* buf, unsigned char buf_len,
+    unsigned char payload_len, const unsigned char* aad, unsigned char aad_len,
+    const unsigned char* nonce, unsigned char mic_len);
+
+
+// decrypts a payload according to aes-128 CCM* and verifies the `mic_len`
+// byte tag following it. Returns TOCK_FAIL if the tag is not valid.
+//
+// arguments are as for aes128_ccm_encrypt_sync()
+int aes128_ccm_decrypt_sync(unsigned char* buf, unsigned char buf_len,
+    unsigned char payload_len, const unsigned char* aad, unsigned char aad_len,
+    const unsigned char* nonce, unsigned char mic_len);
+
 #ifdef __cplusplus
 }
 #endif
@@ -20,3 +20,7 @@ int button_read(int pin_num) {
   return command(DRIVER_NUM_BUTTON, 3, pin_num, 0);
 }
 
+
+int button_select_events(int mask) {
+  return command(DRIVER_NUM_BUTTON, 4, mask, 0);
+}
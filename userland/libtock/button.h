@@ -14,6 +14,17 @@ int button_disable_interrupt(int pin_num);
 int button_read(int pin_num);
 int button_count(void);
 
+// Button events, passed as the third callback argument.
+#define BUTTON_PRESS        0
+#define BUTTON_RELEASE      1
+#define BUTTON_LONG_PRESS   2
+#define BUTTON_DOUBLE_CLICK 3
+
+// Choose which events are delivered, as a mask of `1 << BUTTON_*` bits.
+// Press and release are delivered by default. Only supported on boards with
+// debounced buttons.
+int button_select_events(int mask);
+
 
 #ifdef __cplusplus
 }
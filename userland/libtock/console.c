@@ -107,15 +107,17 @@ int getnstr_async(char *str, size_t len, subscribe_cb cb, void* userdata) {
 typedef struct getnstr_data {
   bool called;
   int result;
+  int len;
 } getnstr_data_t;
 
-static getnstr_data_t getnstr_data = { true, 0 };
+static getnstr_data_t getnstr_data = { true, 0, 0 };
 
 static void getnstr_cb(int result,
-                       int _y __attribute__ ((unused)),
+                       int len,
                        int _z __attribute__ ((unused)),
                        void* ud __attribute__ ((unused))) {
   getnstr_data.result = result;
+  getnstr_data.len    = len;
   getnstr_data.called = true;
 }
 
@@ -148,3 +150,27 @@ int getch(void) {
 int getnstr_abort(void) {
   return command(DRIVER_NUM_CONSOLE, 3, 0, 0);
 }
+
+int console_set_mode(int flags) {
+  return command(DRIVER_NUM_CONSOLE, 4, flags, 0);
+}
+
+int getline(char *str, size_t len) {
+  int ret;
+
+  ret = console_set_mode(CONSOLE_MODE_ECHO | CONSOLE_MODE_LINE);
+  if (ret < 0) return ret;
+
+  ret = getnstr(str, len);
+  if (ret < 0) return ret;
+
+  return getnstr_data.len;
+}
+
+int console_take_focus(void) {
+  return command(DRIVER_NUM_CONSOLE, 5, 0, 0);
+}
+
+int console_release_focus(void) {
+  return command(DRIVER_NUM_CONSOLE, 6, 0, 0);
+}
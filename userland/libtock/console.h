@@ -21,6 +21,25 @@ int getch(void);
 // Abort an ongoing receive call.
 int getnstr_abort(void);
 
+// Receive mode flags for `console_set_mode`.
+#define CONSOLE_MODE_ECHO 0x1
+// Edit input by line: backspace erases, CR/LF end the line as '\n', and a
+// read completes at the end of the line.
+#define CONSOLE_MODE_LINE 0x2
+
+// Set how this process's input is received. Raw bytes without echo are the
+// default.
+int console_set_mode(int flags);
+
+// Read up to `len` bytes ending with a newline, in line mode. Returns the
+// number of bytes read or a negative error.
+int getline(char *str, size_t len);
+
+// Take the input focus, so that all input goes to this process while it has
+// a read pending. Returns TOCK_EBUSY if another process has the focus.
+int console_take_focus(void);
+int console_release_focus(void);
+
 #ifdef __cplusplus
 }
 #endif
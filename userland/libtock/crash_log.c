@@ -0,0 +1,22 @@
+#include "crash_log.h"
+
+int crash_log_exists(void) {
+  return command(DRIVER_NUM_CRASH_LOG, 0, 0, 0) >= 0;
+}
+
+int crash_log_count(void) {
+  return command(DRIVER_NUM_CRASH_LOG, 1, 0, 0);
+}
+
+int crash_log_read(int n, crash_log_record_t *record) {
+  int err = allow(DRIVER_NUM_CRASH_LOG, 0, (void*) record, sizeof(*record));
+  if (err < 0) return err;
+
+  int len = command(DRIVER_NUM_CRASH_LOG, 2, n, 0);
+  allow(DRIVER_NUM_CRASH_LOG, 0, NULL, 0);
+  return len < 0 ? len : TOCK_SUCCESS;
+}
+
+int crash_log_clear(void) {
+  return command(DRIVER_NUM_CRASH_LOG, 3, 0, 0);
+}
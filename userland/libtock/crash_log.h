@@ -0,0 +1,49 @@
+#pragma once
+
+#include "tock.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DRIVER_NUM_CRASH_LOG 0x10003
+
+// A crash record, as kept by the kernel across reboots.
+typedef struct {
+  uint32_t magic;
+  // Increases with every crash
+  uint32_t sequence;
+  uint32_t pc;
+  uint32_t lr;
+  uint32_t cfsr;
+  uint32_t hfsr;
+  uint32_t mmfar;
+  uint32_t bfar;
+  // Line of the kernel panic
+  uint32_t line;
+  // Index of the faulting process, or 0xffffffff if none
+  uint32_t app_index;
+  // The following strings are NUL padded, not NUL terminated
+  char app_name[16];
+  char file[32];
+  char version[16];
+  uint32_t checksum;
+} crash_log_record_t;
+
+// Does the driver exist?
+int crash_log_exists(void);
+
+// Returns the number of stored crash records.
+int crash_log_count(void);
+
+// Reads record `n`, counting from the oldest.
+//
+// Returns SUCCESS, or EINVAL if there is no such record.
+int crash_log_read(int n, crash_log_record_t *record);
+
+// Erases all records.
+int crash_log_clear(void);
+
+#ifdef __cplusplus
+}
+#endif
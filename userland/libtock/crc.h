@@ -10,7 +10,8 @@ extern "C" {
 
 // CRC algorithms
 //
-// In all cases, input bytes are bit-reversed (i.e., consumed from LSB to MSB.)
+// Unless noted otherwise, input bytes are bit-reversed (i.e., consumed from
+// LSB to MSB.)
 //
 // Algorithms prefixed with `SAM4L_` are native to that chip and thus require
 // no software post-processing on platforms using it.
@@ -27,6 +28,13 @@ enum crc_alg {
     CRC_SAM4L_32,
     /// Polynomial 0x1EDC6F41, no output post-processing
     CRC_SAM4L_32C,
+
+    // Polynomial 0x1021, initial value 0xFFFF, input consumed from MSB to LSB,
+    // no output post-processing ("CRC-16-CCITT")
+    CRC_16_CCITT,
+    // Polynomial 0x07, initial value 0x00, input consumed from MSB to LSB, no
+    // output post-processing ("CRC-8")
+    CRC_8,
 };
 
 // Does the driver exist?
@@ -0,0 +1,41 @@
+#include "date_time.h"
+
+#define TIME_LEN 16
+
+int date_time_exists(void) {
+  return command(DRIVER_NUM_DATE_TIME, 0, 0, 0) >= 0;
+}
+
+int date_time_get(date_time_t *time) {
+  uint8_t buf[TIME_LEN];
+
+  int err = allow(DRIVER_NUM_DATE_TIME, 0, (void*) buf, sizeof(buf));
+  if (err < 0) return err;
+
+  int len = command(DRIVER_NUM_DATE_TIME, 1, 0, 0);
+  allow(DRIVER_NUM_DATE_TIME, 0, NULL, 0);
+  if (len < 0) return len;
+
+  time->seconds = 0;
+  for (int i = 7; i >= 0; i--) {
+    time->seconds = time->seconds << 8 | buf[i];
+  }
+  time->year        = buf[8] | buf[9] << 8;
+  time->month       = buf[10];
+  time->day         = buf[11];
+  time->hour        = buf[12];
+  time->minute      = buf[13];
+  time->second      = buf[14];
+  time->day_of_week = buf[15];
+  return TOCK_SUCCESS;
+}
+
+int date_time_set_seconds(uint64_t seconds) {
+  return command(DRIVER_NUM_DATE_TIME, 2, (int) (seconds & 0xffffffff), (int) (seconds >> 32));
+}
+
+int date_time_set(const date_time_t *time) {
+  int date = time->year << 16 | time->month << 8 | time->day;
+  int tod  = time->hour << 16 | time->minute << 8 | time->second;
+  return command(DRIVER_NUM_DATE_TIME, 3, date, tod);
+}
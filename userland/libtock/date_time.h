@@ -0,0 +1,50 @@
+#pragma once
+
+#include "tock.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DRIVER_NUM_DATE_TIME 0x00007
+
+// A UTC date and time.
+typedef struct {
+  // Seconds since the Unix epoch, ignoring leap seconds
+  uint64_t seconds;
+  uint16_t year;
+  // 1 to 12
+  uint8_t month;
+  // 1 to 31
+  uint8_t day;
+  uint8_t hour;
+  uint8_t minute;
+  uint8_t second;
+  // 0 for Sunday to 6 for Saturday
+  uint8_t day_of_week;
+} date_time_t;
+
+// Does the driver exist?
+int date_time_exists(void);
+
+// Reads the current time.
+//
+// Returns SUCCESS, or EOFF if the time has not been set yet.
+int date_time_get(date_time_t *time);
+
+// Sets the current time in seconds since the Unix epoch.
+//
+// Returns SUCCESS, EPERM if the board does not allow this process to set the
+// time, or EINVAL for times past the year 9999.
+int date_time_set_seconds(uint64_t seconds);
+
+// Sets the current date and time in UTC. The `seconds` and `day_of_week`
+// fields are ignored.
+//
+// Returns SUCCESS, EPERM if the board does not allow this process to set the
+// time, or EINVAL if this is not a valid date between 1970 and 9999.
+int date_time_set(const date_time_t *time);
+
+#ifdef __cplusplus
+}
+#endif
@@ -0,0 +1,80 @@
+#include "digest.h"
+
+int digest_exists(void) {
+  return command(DRIVER_NUM_DIGEST, 0, 0, 0) >= 0;
+}
+
+int digest_request_sha256(size_t len) {
+  return command(DRIVER_NUM_DIGEST, 1, len, 0);
+}
+
+int digest_request_hmac_sha256(size_t len) {
+  return command(DRIVER_NUM_DIGEST, 2, len, 0);
+}
+
+int digest_subscribe(subscribe_cb callback, void *ud) {
+  return subscribe(DRIVER_NUM_DIGEST, 0, callback, ud);
+}
+
+int digest_set_key(const void* key, size_t len) {
+  return allow(DRIVER_NUM_DIGEST, 0, (void*) key, len);
+}
+
+int digest_set_data(const void* buf, size_t len) {
+  return allow(DRIVER_NUM_DIGEST, 1, (void*) buf, len);
+}
+
+int digest_set_dest(uint8_t* buf, size_t len) {
+  return allow(DRIVER_NUM_DIGEST, 2, (void*) buf, len);
+}
+
+struct data {
+  bool fired;
+  int status;
+};
+
+static void callback(int status,
+                     __attribute__((unused)) int v1,
+                     __attribute__((unused)) int v2,
+                     void *data)
+{
+  struct data *d = data;
+
+  d->fired  = true;
+  d->status = status;
+}
+
+static int digest_sync(int hmac, const void *buf, size_t buflen, uint8_t *digest)
+{
+  struct data d = { .fired = false };
+  int err;
+
+  err = digest_set_data(buf, buflen);
+  if (err < TOCK_SUCCESS) return err;
+
+  err = digest_set_dest(digest, DIGEST_SHA256_LEN);
+  if (err < TOCK_SUCCESS) return err;
+
+  err = digest_subscribe(callback, (void *) &d);
+  if (err < TOCK_SUCCESS) return err;
+
+  err = hmac ? digest_request_hmac_sha256(buflen) : digest_request_sha256(buflen);
+  if (err < TOCK_SUCCESS) return err;
+
+  yield_for(&d.fired);
+  return d.status;
+}
+
+int digest_sha256(const void *buf, size_t buflen, uint8_t *digest)
+{
+  return digest_sync(0, buf, buflen, digest);
+}
+
+int digest_hmac_sha256(const void *key, size_t keylen,
+                       const void *buf, size_t buflen, uint8_t *tag)
+{
+  int err = digest_set_key(key, keylen);
+  if (err < TOCK_SUCCESS) return err;
+
+  return digest_sync(1, buf, buflen, tag);
+}
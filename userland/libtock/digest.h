@@ -0,0 +1,53 @@
+#pragma once
+
+#include "tock.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DRIVER_NUM_DIGEST 0x40003
+
+#define DIGEST_SHA256_LEN 32
+
+// Does the driver exist?
+int digest_exists(void);
+
+// Compute the SHA-256 digest of the given buffer
+//
+// Returns SUCCESS and writes DIGEST_SHA256_LEN bytes to `digest` on success.
+int digest_sha256(const void *buf, size_t buflen, uint8_t *digest);
+
+// Compute the HMAC-SHA256 tag of the given buffer using `key`
+//
+// Returns SUCCESS and writes DIGEST_SHA256_LEN bytes to `tag` on success.
+int digest_hmac_sha256(const void *key, size_t keylen,
+                       const void *buf, size_t buflen, uint8_t *tag);
+
+// Register a callback to receive the completion of a request
+//
+// The callback will receive these parameters, in order:
+//    status: SUCCESS if the digest was written to the destination buffer
+int digest_subscribe(subscribe_cb, void *);
+
+// Provide the key for HMAC requests
+int digest_set_key(const void*, size_t);
+
+// Provide the buffer to compute a digest of
+int digest_set_data(const void*, size_t);
+
+// Provide the buffer (at least DIGEST_SHA256_LEN bytes) receiving the digest
+int digest_set_dest(uint8_t*, size_t);
+
+// Request a SHA-256 digest of the first `len` bytes of the data buffer, or of
+// all of it if `len` is 0
+//
+// Returns EBUSY if this app already has a request in progress.
+int digest_request_sha256(size_t len);
+
+// Request an HMAC-SHA256 tag, as for digest_request_sha256()
+int digest_request_hmac_sha256(size_t len);
+
+#ifdef __cplusplus
+}
+#endif
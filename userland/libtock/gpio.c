@@ -40,3 +40,15 @@ int gpio_interrupt_callback(subscribe_cb callback, void* callback_args) {
   return subscribe(GPIO_DRIVER_NUM, 0, callback, callback_args);
 }
 
+
+int gpio_read_port(uint32_t* values) {
+  int ret = command(GPIO_DRIVER_NUM, 10, 0, 0);
+  if (ret < 0) return ret;
+
+  *values = (uint32_t) ret;
+  return TOCK_SUCCESS;
+}
+
+int gpio_write_port(uint32_t mask, uint32_t values) {
+  return command(GPIO_DRIVER_NUM, 11, mask, values);
+}
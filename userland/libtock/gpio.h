@@ -34,6 +34,12 @@ int gpio_disable_interrupt(GPIO_Pin_t pin);
 int gpio_disable(GPIO_Pin_t pin);
 int gpio_interrupt_callback(subscribe_cb callback, void* callback_args);
 
+// Read pins 0 to 30 at once, as a bit mask of the pins that are high.
+int gpio_read_port(uint32_t* values);
+// Set the pins in `mask` to the values of the same bits in `values`, without
+// other processes running in between.
+int gpio_write_port(uint32_t mask, uint32_t values);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,76 @@
+#include "i2c_master.h"
+
+static int i2c_master_transaction(int command_num, uint8_t addr, uint8_t* buf,
+                                  size_t buf_len, int arg, subscribe_cb cb,
+                                  void* userdata) {
+  int ret;
+
+  ret = allow(DRIVER_NUM_I2C_MASTER, 1, (void*) buf, buf_len);
+  if (ret < 0) return ret;
+
+  ret = subscribe(DRIVER_NUM_I2C_MASTER, 1, cb, userdata);
+  if (ret < 0) return ret;
+
+  return command(DRIVER_NUM_I2C_MASTER, command_num, addr, arg);
+}
+
+int i2c_master_write_async(uint8_t addr, uint8_t* buf, size_t len,
+                           subscribe_cb cb, void* userdata) {
+  return i2c_master_transaction(1, addr, buf, len, len, cb, userdata);
+}
+
+int i2c_master_read_async(uint8_t addr, uint8_t* buf, size_t len,
+                          subscribe_cb cb, void* userdata) {
+  return i2c_master_transaction(2, addr, buf, len, len, cb, userdata);
+}
+
+int i2c_master_write_read_async(uint8_t addr, uint8_t* buf, size_t write_len,
+                                size_t read_len, subscribe_cb cb, void* userdata) {
+  size_t buf_len = write_len > read_len ? write_len : read_len;
+  return i2c_master_transaction(3, addr, buf, buf_len,
+                                (write_len & 0xff) | (read_len << 8), cb, userdata);
+}
+
+typedef struct {
+  bool fired;
+  int result;
+  int error;
+} i2c_master_result_t;
+
+static void i2c_master_cb(int result,
+                          int _len __attribute__ ((unused)),
+                          int error,
+                          void* ud) {
+  i2c_master_result_t* data = (i2c_master_result_t*) ud;
+  data->result = result;
+  data->error  = error;
+  data->fired  = true;
+}
+
+static int i2c_master_wait(int ret, i2c_master_result_t* data, int* error) {
+  if (ret < 0) return ret;
+
+  yield_for(&data->fired);
+  if (error != NULL) *error = data->error;
+  return data->result;
+}
+
+int i2c_master_write(uint8_t addr, uint8_t* buf, size_t len, int* error) {
+  i2c_master_result_t data = { false, 0, 0 };
+  int ret = i2c_master_write_async(addr, buf, len, i2c_master_cb, &data);
+  return i2c_master_wait(ret, &data, error);
+}
+
+int i2c_master_read(uint8_t addr, uint8_t* buf, size_t len, int* error) {
+  i2c_master_result_t data = { false, 0, 0 };
+  int ret = i2c_master_read_async(addr, buf, len, i2c_master_cb, &data);
+  return i2c_master_wait(ret, &data, error);
+}
+
+int i2c_master_write_read(uint8_t addr, uint8_t* buf, size_t write_len,
+                          size_t read_len, int* error) {
+  i2c_master_result_t data = { false, 0, 0 };
+  int ret = i2c_master_write_read_async(addr, buf, write_len, read_len,
+                                        i2c_master_cb, &data);
+  return i2c_master_wait(ret, &data, error);
+}
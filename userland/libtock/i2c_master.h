@@ -0,0 +1,46 @@
+#pragma once
+
+#include "tock.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DRIVER_NUM_I2C_MASTER 0x20003
+
+// How a transaction failed, the third argument of the callback.
+#define I2C_MASTER_ERROR_NONE             0
+#define I2C_MASTER_ERROR_ADDRESS_NAK      1
+#define I2C_MASTER_ERROR_DATA_NAK         2
+#define I2C_MASTER_ERROR_ARBITRATION_LOST 3
+#define I2C_MASTER_ERROR_OVERRUN          4
+
+// Number of transactions a process may have outstanding at once.
+#define I2C_MASTER_MAX_QUEUED 4
+
+// The process may only use the addresses the board allows it. Transactions
+// return TOCK_EPERM for other addresses, and TOCK_ENOMEM if the process
+// already has I2C_MASTER_MAX_QUEUED transactions outstanding. As these
+// functions share `buf` with the kernel for each transaction, only use them
+// for one transaction at a time. The callback receives the result
+// (TOCK_ENOACK if the device did not acknowledge, TOCK_EBUSY if arbitration
+// was lost), the number of bytes read, and one of the I2C_MASTER_ERROR_
+// values.
+int i2c_master_write_async(uint8_t addr, uint8_t* buf, size_t len,
+                           subscribe_cb cb, void* userdata);
+int i2c_master_read_async(uint8_t addr, uint8_t* buf, size_t len,
+                          subscribe_cb cb, void* userdata);
+// Writes `write_len` bytes of `buf`, then reads `read_len` bytes into it.
+int i2c_master_write_read_async(uint8_t addr, uint8_t* buf, size_t write_len,
+                                size_t read_len, subscribe_cb cb, void* userdata);
+
+// Synchronous versions. On failure, `error`, if not NULL, is set to one of the
+// I2C_MASTER_ERROR_ values.
+int i2c_master_write(uint8_t addr, uint8_t* buf, size_t len, int* error);
+int i2c_master_read(uint8_t addr, uint8_t* buf, size_t len, int* error);
+int i2c_master_write_read(uint8_t addr, uint8_t* buf, size_t write_len,
+                          size_t read_len, int* error);
+
+#ifdef __cplusplus
+}
+#endif
@@ -35,6 +35,31 @@ int alarm_internal_set(uint32_t tics);
  */
 int alarm_internal_stop(void);
 
+/*
+ * Starts a oneshot alarm identified by the returned id, alongside any others
+ *
+ * reference - clock value the delay is counted from, usually a recent reading
+ * dt - delay in clock tics
+ *
+ * Returns the alarm's id, or TOCK_ENOMEM if the process has too many alarms.
+ */
+int alarm_internal_oneshot(uint32_t reference, uint32_t dt);
+
+/*
+ * Starts a periodic alarm identified by the returned id, alongside any others
+ *
+ * The first expiration is `period` tics after `reference`. The kernel rearms
+ * the alarm relative to its previous expiration, so it does not drift.
+ *
+ * Returns the alarm's id, or TOCK_ENOMEM if the process has too many alarms.
+ */
+int alarm_internal_periodic(uint32_t reference, uint32_t period);
+
+/*
+ * Cancels the alarm with the given id
+ */
+int alarm_internal_cancel(int id);
+
 /*
  * Get the the timer frequency in Hz.
  */
@@ -12,6 +12,18 @@ int alarm_internal_stop(void) {
   return command(DRIVER_NUM_ALARM, 3, 0, 0);
 }
 
+int alarm_internal_oneshot(uint32_t reference, uint32_t dt) {
+  return command(DRIVER_NUM_ALARM, 5, (int)reference, (int)dt);
+}
+
+int alarm_internal_periodic(uint32_t reference, uint32_t period) {
+  return command(DRIVER_NUM_ALARM, 6, (int)reference, (int)period);
+}
+
+int alarm_internal_cancel(int id) {
+  return command(DRIVER_NUM_ALARM, 7, id, 0);
+}
+
 unsigned int alarm_internal_frequency(void) {
   return (unsigned int) command(DRIVER_NUM_ALARM, 1, 0, 0);
 }
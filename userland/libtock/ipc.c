@@ -35,3 +35,27 @@ int ipc_share(int pid, void* base, int len) {
   return allow(IPC_DRIVER_NUM, pid, base, len);
 }
 
+
+int ipc_msg_register(subscribe_cb callback, void *ud) {
+  return subscribe(IPC_MSG_DRIVER_NUM, 0, callback, ud);
+}
+
+int ipc_msg_set_rx_buffer(void* buf, int len) {
+  return allow(IPC_MSG_DRIVER_NUM, 0, buf, len);
+}
+
+int ipc_msg_request(int pid, const void* msg, int len) {
+  int err = allow(IPC_MSG_DRIVER_NUM, 1, (void*)msg, len);
+  if (err < TOCK_SUCCESS) return err;
+  return command(IPC_MSG_DRIVER_NUM, 1, pid, 0);
+}
+
+int ipc_msg_reply(int pid, int request_id, const void* msg, int len) {
+  int err = allow(IPC_MSG_DRIVER_NUM, 1, (void*)msg, len);
+  if (err < TOCK_SUCCESS) return err;
+  return command(IPC_MSG_DRIVER_NUM, 2, pid, request_id);
+}
+
+int ipc_msg_receive(void) {
+  return command(IPC_MSG_DRIVER_NUM, 3, 0, 0);
+}
@@ -10,6 +10,18 @@ extern "C" {
 #endif
 
 #define IPC_DRIVER_NUM 0x10000
+#define IPC_MSG_DRIVER_NUM 0x10001
+
+// Maximum payload of a message, in bytes
+#define IPC_MSG_MAX_LEN 32
+
+// Header written before the payload of each received message
+typedef struct {
+  uint16_t sender;     // process id of the sender
+  uint8_t is_reply;    // 1 if this is a reply, 0 if a request
+  uint8_t len;         // length of the payload that follows
+  uint32_t request_id; // id of the request, or of the request replied to
+} ipc_msg_header_t;
 
 // Performs service discovery
 //
@@ -46,9 +58,15 @@ int ipc_register_svc(subscribe_cb callback, void *ud);
 int ipc_register_client_cb(int svc_id, subscribe_cb callback, void *ud);
 
 // Send a notify to the client at the given process id
+//
+// Fails with TOCK_EINVAL or TOCK_ENOMEM if a buffer shared with the client
+// cannot be made accessible to it, see `ipc_share`.
 int ipc_notify_client(int pid);
 
 // Send a notify to the service at the given process id
+//
+// Fails with TOCK_EINVAL or TOCK_ENOMEM if a buffer shared with the service
+// cannot be made accessible to it, see `ipc_share`.
 int ipc_notify_svc(int pid);
 
 // Share a buffer with the given process (either service or client)
@@ -56,8 +74,46 @@ int ipc_notify_svc(int pid);
 // `pid` is the non-zero process id of the recipient.
 // `base` must be aligned to the value of `len`.
 // `len` must be a power-of-two larger than 16.
+//
+// The buffer becomes accessible to the recipient when it is next notified. A
+// process can have at most 4 buffers from other processes shared with it.
+// Notifying fails with TOCK_ENOMEM if the buffer would be a fifth.
 int ipc_share(int pid, void* base, int len);
 
+// Registers the callback called when a message arrives in this process's
+// mailbox. Processes must register before others can send them messages.
+//
+// Message callbacks take the following arguments in order:
+//
+//   int pid     - the sending process's id
+//   int waiting - the number of messages waiting to be received
+//   int unused
+//   void* ud    - `userdata`. same as the argument to this function.
+int ipc_msg_register(subscribe_cb callback, void *ud);
+
+// Sets the buffer messages are received into. It must have room for an
+// `ipc_msg_header_t` followed by the largest expected payload.
+int ipc_msg_set_rx_buffer(void* buf, int len);
+
+// Sends `len` bytes of `msg` as a request to the process with id `pid`.
+//
+// Returns the (positive) id of the request, quoted in the service's reply,
+// TOCK_ENOMEM if the service's mailbox is full, or another negative value on
+// error. Only the last 4 requests a process sent can be replied to.
+int ipc_msg_request(int pid, const void* msg, int len);
+
+// Sends `len` bytes of `msg` as the reply to request `request_id` of the
+// process with id `pid`.
+//
+// Returns TOCK_EINVAL if that process is not waiting for a reply to the
+// request, for example because it was already replied to.
+int ipc_msg_reply(int pid, int request_id, const void* msg, int len);
+
+// Moves the oldest waiting message into the receive buffer.
+//
+// Returns the payload length, or TOCK_FAIL if no message is waiting.
+int ipc_msg_receive(void);
+
 #ifdef __cplusplus
 }
 #endif
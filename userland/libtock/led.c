@@ -15,3 +15,33 @@ int led_off(int led_num) {
 int led_toggle(int led_num) {
   return command(DRIVER_NUM_LEDS, 3, led_num, 0);
 }
+
+int led_blink(int led_num, int count, int on_ms, int period_ms) {
+  return command(DRIVER_NUM_LEDS, 4, (led_num & 0xff) | (count << 8),
+                 (on_ms & 0xffff) | (period_ms << 16));
+}
+
+int led_program(const led_step_t* steps, size_t num_steps, int count) {
+  int ret;
+#pragma GCC diagnostic push
+#pragma GCC diagnostic ignored "-Wcast-qual"
+  void* buf = (void*) steps;
+#pragma GCC diagnostic pop
+
+  ret = allow(DRIVER_NUM_LEDS, 0, buf, num_steps * sizeof(led_step_t));
+  if (ret < 0) return ret;
+
+  return command(DRIVER_NUM_LEDS, 5, count, 0);
+}
+
+int led_program_stop(void) {
+  return command(DRIVER_NUM_LEDS, 6, 0, 0);
+}
+
+int led_subscribe(subscribe_cb callback, void* ud) {
+  return subscribe(DRIVER_NUM_LEDS, 0, callback, ud);
+}
+
+int led_set_brightness(int led_num, int brightness) {
+  return command(DRIVER_NUM_LEDS, 7, led_num, brightness);
+}
@@ -15,6 +15,37 @@ int led_toggle(int led_num);
 // Returns the number of LEDs on the host platform.
 int led_count(void);
 
+// The following are only supported on boards that run LED patterns in the
+// kernel.
+
+// Blink an LED `count` times, or until stopped if 0, on for `on_ms` out of
+// every `period_ms`. Turning the LED on, off or toggling it stops the blink.
+int led_blink(int led_num, int count, int on_ms, int period_ms);
+
+// One step of an LED program: the LEDs in `leds` (a bit mask of LEDs 0 to 7)
+// are on at `brightness` for `duration_ms`, and the program's other LEDs are
+// off.
+typedef struct __attribute__((packed)) {
+  uint8_t leds;
+  uint8_t brightness;
+  uint16_t duration_ms;
+} led_step_t;
+
+// Run a program of `num_steps` steps `count` times, or until stopped if 0.
+// The steps must stay valid while the program runs.
+int led_program(const led_step_t* steps, size_t num_steps, int count);
+int led_program_stop(void);
+
+// Called when a finite blink (with `is_program` 0 and the LED index) or
+// program (with `is_program` 1) ends.
+int led_subscribe(subscribe_cb callback, void* ud);
+
+#define LED_MAX_BRIGHTNESS 255
+
+// Set how bright an LED is when on, from 0 to LED_MAX_BRIGHTNESS. LEDs that
+// cannot be dimmed are on at any non-zero brightness.
+int led_set_brightness(int led_num, int brightness);
+
 #ifdef __cplusplus
 }
 #endif
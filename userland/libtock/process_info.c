@@ -0,0 +1,50 @@
+#include "process_info.h"
+
+#define HEADER_WORDS 8
+
+int process_info_exists(void) {
+  return command(DRIVER_NUM_PROCESS_INFO, 0, 0, 0) >= 0;
+}
+
+int process_info_num_slots(void) {
+  return command(DRIVER_NUM_PROCESS_INFO, 1, 0, 0);
+}
+
+int process_info_stats(int index, process_info_stats_t *stats) {
+  uint32_t words[HEADER_WORDS + 2 * PROCESS_INFO_DRIVER_SLOTS];
+
+  int err = allow(DRIVER_NUM_PROCESS_INFO, 0, (void*) words, sizeof(words));
+  if (err < 0) return err;
+
+  int len = command(DRIVER_NUM_PROCESS_INFO, 2, index, 0);
+  allow(DRIVER_NUM_PROCESS_INFO, 0, NULL, 0);
+  if (len < 0) return len;
+
+  stats->cpu_time_us           = (uint64_t) words[1] << 32 | words[0];
+  stats->timeslice_expirations = words[2];
+  stats->yields                = words[3];
+  stats->syscalls              = words[4];
+  stats->dropped_callbacks     = words[5];
+  stats->restarts              = words[6];
+  stats->other_driver_syscalls = words[7];
+  stats->num_drivers           = (len / 4 - HEADER_WORDS) / 2;
+  for (int i = 0; i < stats->num_drivers; i++) {
+    stats->drivers[i].driver = words[HEADER_WORDS + 2 * i];
+    stats->drivers[i].count  = words[HEADER_WORDS + 2 * i + 1];
+  }
+  return TOCK_SUCCESS;
+}
+
+int process_info_name(int index, char *name, size_t len) {
+  if (len == 0) return TOCK_ESIZE;
+
+  int err = allow(DRIVER_NUM_PROCESS_INFO, 0, (void*) name, len - 1);
+  if (err < 0) return err;
+
+  int name_len = command(DRIVER_NUM_PROCESS_INFO, 3, index, 0);
+  allow(DRIVER_NUM_PROCESS_INFO, 0, NULL, 0);
+  if (name_len < 0) return name_len;
+
+  name[name_len] = '\0';
+  return name_len;
+}
@@ -0,0 +1,52 @@
+#pragma once
+
+#include "tock.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DRIVER_NUM_PROCESS_INFO 0x10002
+
+// Maximum number of drivers whose syscalls the kernel counts per process.
+#define PROCESS_INFO_DRIVER_SLOTS 8
+
+typedef struct {
+  uint32_t driver;
+  uint32_t count;
+} process_info_driver_count_t;
+
+typedef struct {
+  uint64_t cpu_time_us;
+  uint32_t timeslice_expirations;
+  uint32_t yields;
+  uint32_t syscalls;
+  uint32_t dropped_callbacks;
+  uint32_t restarts;
+  // Subscribe, command and allow calls to drivers not in `drivers`
+  uint32_t other_driver_syscalls;
+  // Number of valid entries in `drivers`
+  int num_drivers;
+  process_info_driver_count_t drivers[PROCESS_INFO_DRIVER_SLOTS];
+} process_info_stats_t;
+
+// Does the driver exist?
+int process_info_exists(void);
+
+// Returns the number of process slots. Some slots may be empty.
+int process_info_num_slots(void);
+
+// Reads the accounting information of the process in slot `index`.
+//
+// Returns SUCCESS, or ENOAPP if the slot is empty.
+int process_info_stats(int index, process_info_stats_t *stats);
+
+// Copies the name of the process in slot `index` into `name`, NUL terminated.
+//
+// Returns the length of the name, ENOAPP if the slot is empty or ESIZE if
+// `name` is too short.
+int process_info_name(int index, char *name, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
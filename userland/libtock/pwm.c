@@ -0,0 +1,21 @@
+#include "pwm.h"
+
+int pwm_count(void) {
+  return command(DRIVER_NUM_PWM, 0, 0, 0);
+}
+
+int pwm_start(int pin, uint32_t frequency_hz, uint32_t duty_cycle) {
+  return command(DRIVER_NUM_PWM, 1, (pin & 0xff) | (int) (duty_cycle << 8), (int) frequency_hz);
+}
+
+int pwm_stop(int pin) {
+  return command(DRIVER_NUM_PWM, 2, pin, 0);
+}
+
+int pwm_max_frequency(int pin) {
+  return command(DRIVER_NUM_PWM, 3, pin, 0);
+}
+
+int pwm_max_duty_cycle(int pin) {
+  return command(DRIVER_NUM_PWM, 4, pin, 0);
+}
@@ -0,0 +1,33 @@
+#pragma once
+
+#include "tock.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DRIVER_NUM_PWM 0x00008
+
+// Returns the number of PWM pins, or an error if there is no PWM driver.
+int pwm_count(void);
+
+// Starts a signal on `pin` with the given frequency and duty cycle. The duty
+// cycle is a fraction of `pwm_max_duty_cycle(pin)`.
+//
+// The pin belongs to this process until it calls `pwm_stop`. Returns EPERM if
+// the board assigned the pin to another process, EBUSY if another process has
+// a signal on it and EINVAL if the frequency or duty cycle is out of range.
+int pwm_start(int pin, uint32_t frequency_hz, uint32_t duty_cycle);
+
+// Stops the signal on `pin`, leaving it low.
+int pwm_stop(int pin);
+
+// Returns the highest frequency of `pin`, in hertz.
+int pwm_max_frequency(int pin);
+
+// Returns the duty cycle for which `pin` is always high.
+int pwm_max_duty_cycle(int pin);
+
+#ifdef __cplusplus
+}
+#endif
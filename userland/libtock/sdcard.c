@@ -33,7 +33,10 @@ typedef struct {
 // 3: write_done, write block completed successfully
 //    arg1 - len, number of bytes written
 // 4: error, an error occurred
-//    arg1 - error, number representing the error that occurred
+//    arg1 - error, TOCK_ECANCEL if the card was inserted or removed, TOCK_ETIMEOUT
+//           if it stopped responding, TOCK_FAIL otherwise
+//    arg2 - operation that failed, 0 for initialization, 1 for a read and 2 for
+//           a write
 static void sdcard_cb (int callback_type, int arg1, int arg2, void* callback_args) {
 
   sdcard_data_t* result = (sdcard_data_t*) callback_args;
@@ -0,0 +1,25 @@
+#include "sensor_sampler.h"
+
+int sensor_sampler_available(void) {
+  return command(DRIVER_NUM_SENSOR_SAMPLER, 0, 0, 0);
+}
+
+int sensor_sampler_set_buffer(int sensor, int32_t* buf, size_t len) {
+  return allow(DRIVER_NUM_SENSOR_SAMPLER, sensor, (void*) buf, len * sizeof(int32_t));
+}
+
+int sensor_sampler_subscribe(subscribe_cb callback, void* userdata) {
+  return subscribe(DRIVER_NUM_SENSOR_SAMPLER, 0, callback, userdata);
+}
+
+int sensor_sampler_start(int sensor, size_t batch, uint32_t period_ms) {
+  return command(DRIVER_NUM_SENSOR_SAMPLER, 1, (sensor & 0xff) | (batch << 8), period_ms);
+}
+
+int sensor_sampler_stop(int sensor) {
+  return command(DRIVER_NUM_SENSOR_SAMPLER, 2, sensor, 0);
+}
+
+int sensor_sampler_flush(int sensor) {
+  return command(DRIVER_NUM_SENSOR_SAMPLER, 3, sensor, 0);
+}
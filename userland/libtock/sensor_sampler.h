@@ -0,0 +1,45 @@
+#pragma once
+
+#include "tock.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DRIVER_NUM_SENSOR_SAMPLER 0x60005
+
+// Sensors that can be sampled.
+#define SENSOR_SAMPLER_TEMPERATURE   0
+#define SENSOR_SAMPLER_HUMIDITY      1
+#define SENSOR_SAMPLER_AMBIENT_LIGHT 2
+#define SENSOR_SAMPLER_ACCELEROMETER 3
+#define SENSOR_SAMPLER_MAGNETOMETER  4
+#define SENSOR_SAMPLER_GYROSCOPE     5
+
+// Returns a bitmask of the sensors present, with bit n set for sensor n, or a
+// negative error code.
+int sensor_sampler_available(void);
+
+// Sets the buffer, of `len` 32-bit words, that readings of `sensor` are stored
+// in. A reading is one word for temperature, humidity and ambient light, and
+// three (x, y, z) for the other sensors.
+int sensor_sampler_set_buffer(int sensor, int32_t* buf, size_t len);
+
+// The callback receives the sensor and the number of readings in its buffer.
+int sensor_sampler_subscribe(subscribe_cb callback, void* userdata);
+
+// Samples `sensor` every `period_ms` milliseconds, starting now, and calls
+// back once `batch` readings have been collected. The buffer must already be
+// set and hold a full batch.
+int sensor_sampler_start(int sensor, size_t batch, uint32_t period_ms);
+
+// Stops sampling `sensor`, delivering the readings collected so far.
+int sensor_sampler_stop(int sensor);
+
+// Delivers the readings collected for `sensor` without waiting for the batch
+// to fill.
+int sensor_sampler_flush(int sensor);
+
+#ifdef __cplusplus
+}
+#endif
@@ -108,6 +108,26 @@ int command(uint32_t driver, uint32_t command, int data, int arg2) {
   return ret;
 }
 
+int command_wait(uint32_t driver, uint32_t subscribe, uint32_t command,
+                 int data, int arg2, int results[3]) {
+  register uint32_t r0 asm ("r0") = driver;
+  register uint32_t r1 asm ("r1") = (subscribe << 16) | (command & 0xffff);
+  register uint32_t r2 asm ("r2") = data;
+  register uint32_t r3 asm ("r3") = arg2;
+  asm volatile (
+    "svc 5"
+    : "+r" (r0), "+r" (r1), "+r" (r2), "+r" (r3)
+    :
+    : "memory"
+    );
+  if (results != NULL) {
+    results[0] = r1;
+    results[1] = r2;
+    results[2] = r3;
+  }
+  return r0;
+}
+
 int allow(uint32_t driver, uint32_t allow, void* ptr, size_t size) {
   register uint32_t r0 asm ("r0") = driver;
   register uint32_t r1 asm ("r1") = allow;
@@ -206,6 +226,14 @@ const char* tock_strerror(int tock_errno) {
       return "Device is not physically installed";
     case TOCK_ENOACK:
       return "Packet transmission not acknowledged";
+    case TOCK_EPERM:
+      return "Operation not permitted";
+    case TOCK_ETIMEOUT:
+      return "Operation timed out";
+    case TOCK_ENOAPP:
+      return "Application does not exist";
+    case TOCK_EWOULDBLOCK:
+      return "Operation would block";
   }
   return "Invalid error number";
 }
@@ -18,6 +18,15 @@ void yield_for(bool*);
 __attribute__ ((warn_unused_result))
 int command(uint32_t driver, uint32_t command, int data, int arg2);
 
+// Issue a command, then block until the driver issues the callback for
+// `subscribe` and store its three arguments in `results` (if not NULL).
+// Returns the command's error code if it fails, otherwise TOCK_SUCCESS.
+// Some callback must be subscribed for `subscribe`; it is not called for the
+// callback that ends the wait.
+__attribute__ ((warn_unused_result))
+int command_wait(uint32_t driver, uint32_t subscribe, uint32_t command,
+                 int data, int arg2, int results[3]);
+
 __attribute__ ((warn_unused_result))
 int subscribe(uint32_t driver, uint32_t subscribe,
               subscribe_cb cb, void* userdata);
@@ -58,6 +67,10 @@ bool driver_exists(uint32_t driver);
 #define TOCK_ENODEVICE    -11
 #define TOCK_EUNINSTALLED -12
 #define TOCK_ENOACK       -13
+#define TOCK_EPERM        -14
+#define TOCK_ETIMEOUT     -15
+#define TOCK_ENOAPP       -16
+#define TOCK_EWOULDBLOCK  -17
 
 // Pass this to the subscribe syscall as a function pointer to deactivate the callback.
 #define TOCK_DEACTIVATE_CALLBACK    0